@@ -2,6 +2,10 @@ use clap_generate::generate_to;
 use clap_generate::generators::{Bash, Fish, Zsh};
 use std::env;
 
+#[allow(dead_code)]
+#[path = "src/constants.rs"]
+mod constants;
+
 include!("src/cli.rs");
 
 fn generate_completions() {
@@ -1,6 +1,8 @@
 complete -c orthanc -n "__fish_use_subcommand" -s s -l server -d 'Orthanc server address' -r
 complete -c orthanc -n "__fish_use_subcommand" -s u -l username -d 'Orthanc username' -r
 complete -c orthanc -n "__fish_use_subcommand" -s p -l password -d 'Orthanc password' -r
+complete -c orthanc -n "__fish_use_subcommand" -l iap-client-id -d 'IAP client id' -r
+complete -c orthanc -n "__fish_use_subcommand" -l google-application-credentials -d 'google service account file path' -r
 complete -c orthanc -n "__fish_use_subcommand" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_use_subcommand" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_use_subcommand" -f -a "patient" -d 'Patient-level commands'
@@ -8,6 +10,13 @@ complete -c orthanc -n "__fish_use_subcommand" -f -a "study" -d 'Study-level com
 complete -c orthanc -n "__fish_use_subcommand" -f -a "series" -d 'Series-level commands'
 complete -c orthanc -n "__fish_use_subcommand" -f -a "instance" -d 'Instance-level commands'
 complete -c orthanc -n "__fish_use_subcommand" -f -a "modality" -d 'Modality-level commands'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "changes" -d 'List changes (new, updated, stable or deleted entities) from the server\'s change log'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "query" -d 'Saved queries'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "lookup" -d 'Find the Orthanc ID of an entity from a DICOM UID, PatientID or AccessionNumber'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "system" -d 'Server-level commands'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "download" -d 'Download several patients, studies or series as a single archive'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "plugin" -d 'Plugin-level commands'
+complete -c orthanc -n "__fish_use_subcommand" -f -a "tools" -d 'Server tools'
 complete -c orthanc -n "__fish_use_subcommand" -f -a "help" -d 'Prints this message or the help of the given subcommand(s)'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -s V -l version -d 'Prints version information'
@@ -18,41 +27,119 @@ complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "search" -d '
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "anonymize" -d 'Anonymize patient'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "modify" -d 'Modify patient'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "download" -d 'Download patient'
+complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "tree" -d 'Show the tree of studies, series and instances of a patient'
+complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "stats" -d 'Show the number of studies, series and instances and the size of a patient'
+complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "protect" -d 'Protect patients from recycling when the storage is full'
+complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "unprotect" -d 'Allow patients to be recycled when the storage is full'
+complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "metadata" -d 'Manage the metadata of a patient'
+complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "label" -d 'Manage the labels of a patient'
+complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "reconstruct" -d 'Reconstruct the main DICOM tags of a patient from its DICOM files'
 complete -c orthanc -n "__fish_seen_subcommand_from patient" -f -a "delete" -d 'Delete patient'
-complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID PatientName' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Protected, Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID PatientName Protected meta:LastUpdate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s m -l metadata -d 'Also show the metadata of the patient'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s l -l labels -d 'Also show the labels of the patient'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -d 'Patient ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID AccessionNumber StudyDate meta:RemoteAET' -r
 complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-studies" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separted pairs TagName=TagValue. Wildcards are allowed. Example: PatientSex=F PatientName=*Sanchez*' -r
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID PatientName' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separated TagName=TagValue, TagName>=TagValue, TagName<=TagValue or \'TagName in (TagValue1,TagValue2)\' terms. Wildcards and date ranges are allowed, dates can be relative (e.g. -7d). Tags can be prefixed with a parent level (e.g. Study.StudyDate). Example: PatientSex=F PatientName=*Sanchez*' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l sort -d 'Sort by the columns specified. Space-separated values. Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l limit -d 'Return at most this number of results' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label -d 'Only return the resources that have all these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label-any -d 'Only return the resources that have any of these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label-none -d 'Only return the resources that have none of these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Protected, Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID PatientName Protected meta:LastUpdate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s i -l ignore-case -d 'Case-insensitive matching of query terms'
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l count -d 'Only print the number of results'
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l exists -d 'Print nothing, exit with 0 if anything matches the query, 1 otherwise, 2 on error'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Patient ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s k -l keep -d 'DICOM tags that should be kept intact. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s c -l config -d 'Anonymization configuration file' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s p -l keep-private-tags -d 'Keep private tags intact'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Patient ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l remove -d 'DICOM tags that should be removed. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s c -l config -d 'Modification configuration file' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Patient ID' -r
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l extract -d 'Extract the archive to this directory instead of writing it to a file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l layout -d 'Path template of the extracted files, with {TagName} placeholders replaced with the values of the instance, series, study and patient DICOM tags. Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}_{Modality}/{InstanceNumber}.dcm' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l transcode -d 'Transcode the instances to this transfer syntax. A UID, or one of: implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l media -d 'Download a media archive with a DICOMDIR, e.g. for burning to a CD'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l skip-existing -d 'Don\'t download again what is already there: keep the output file if it is a complete archive, or the files already written with --layout'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l depth -d 'Lowest level to display' -r -f -a "study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l output -d 'Output format' -r -f -a "text json"
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from protect" -d 'Patient IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from protect" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from protect" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from unprotect" -d 'Patient IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from unprotect" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from unprotect" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "list" -d 'List the metadata of a patient'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "get" -d 'Print the value of a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "set" -d 'Set the value of a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "delete" -d 'Delete a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from get" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Metadata value' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "list" -d 'List the labels of a patient'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "add" -d 'Add a label to patients'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "remove" -d 'Remove a label from patients'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Label' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Patient IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Label' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Patient IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -d 'Patient ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Patient ID' -r
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
@@ -65,41 +152,139 @@ complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "search" -d 'Se
 complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "anonymize" -d 'Anonymize study'
 complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "modify" -d 'Modify study'
 complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "download" -d 'Download study'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "download-many" -d 'Download studies in parallel, each to its own archive (or directory with --extract) named after the study ID. A manifest.csv file lists the downloaded studies'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "tree" -d 'Show the tree of series and instances of a study'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "stats" -d 'Show the number of series and instances and the size of a study'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "metadata" -d 'Manage the metadata of a study'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "verify" -d 'Check the MD5 of the attachments of all the instances of a study'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "label" -d 'Manage the labels of a study'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "merge" -d 'Merge studies or series into a study'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "split" -d 'Move series of a study to a new study'
+complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "reconstruct" -d 'Reconstruct the main DICOM tags of a study from its DICOM files'
 complete -c orthanc -n "__fish_seen_subcommand_from study" -f -a "delete" -d 'Delete study'
-complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID AccessionNumber StudyDate meta:RemoteAET' -r
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s m -l metadata -d 'Also show the metadata of the study'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s l -l labels -d 'Also show the labels of the study'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-series" -d 'Study ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID Modality BodyPartExamined meta:RemoteAET' -r
 complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-series" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separted pairs TagName=TagValue. Wildcards are allowed. Example: StudyDescription=*BRAIN* StudyDate=20200101' -r
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID AccessionNumber StudyDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separated TagName=TagValue, TagName>=TagValue, TagName<=TagValue or \'TagName in (TagValue1,TagValue2)\' terms. Wildcards and date ranges are allowed, dates can be relative (e.g. -7d). Tags can be prefixed with a parent level (e.g. Study.StudyDate). Example: StudyDescription=*BRAIN* \'StudyDate>=-7d\'' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l sort -d 'Sort by the columns specified. Space-separated values. Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l limit -d 'Return at most this number of results' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label -d 'Only return the resources that have all these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label-any -d 'Only return the resources that have any of these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label-none -d 'Only return the resources that have none of these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID AccessionNumber StudyDate meta:RemoteAET' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s i -l ignore-case -d 'Case-insensitive matching of query terms'
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l count -d 'Only print the number of results'
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l exists -d 'Print nothing, exit with 0 if anything matches the query, 1 otherwise, 2 on error'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Study ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s k -l keep -d 'DICOM tags that should be kept intact. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s c -l config -d 'Anonymization configuration file' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s p -l keep-private-tags -d 'Keep private tags intact'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Study ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l remove -d 'DICOM tags that should be removed. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s c -l config -d 'Modification configuration file' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l extract -d 'Extract the archive to this directory instead of writing it to a file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l layout -d 'Path template of the extracted files, with {TagName} placeholders replaced with the values of the instance, series, study and patient DICOM tags. Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}_{Modality}/{InstanceNumber}.dcm' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l transcode -d 'Transcode the instances to this transfer syntax. A UID, or one of: implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle' -r
 complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Study ID'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l media -d 'Download a media archive with a DICOMDIR, e.g. for burning to a CD'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l skip-existing -d 'Don\'t download again what is already there: keep the output file if it is a complete archive, or the files already written with --layout'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from download-many" -s q -l query -d 'Only download the studies matching these search query terms (see study search). Example: StudyDate=20200101-20201231' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download-many" -s d -l dir -d 'Output directory. Studies already downloaded there, with the size given in the manifest, are skipped' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download-many" -l concurrency -d 'Number of studies downloaded at the same time' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download-many" -l transcode -d 'Transcode the instances to this transfer syntax. A UID, or one of: implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download-many" -l extract -d 'Extract each archive to a directory instead of keeping it'
+complete -c orthanc -n "__fish_seen_subcommand_from download-many" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from download-many" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from download-many" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l depth -d 'Lowest level to display' -r -f -a "series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l output -d 'Output format' -r -f -a "text json"
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "list" -d 'List the metadata of a study'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "get" -d 'Print the value of a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "set" -d 'Set the value of a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "delete" -d 'Delete a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from get" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Metadata value' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -l concurrency -d 'Number of instances verified at the same time' -r
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "list" -d 'List the labels of a study'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "add" -d 'Add a label to studies'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "remove" -d 'Remove a label from studies'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Label' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Study IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Label' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Study IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from merge" -d 'Target study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from merge" -d 'IDs of the studies or series to merge into the target study' -r
+complete -c orthanc -n "__fish_seen_subcommand_from merge" -s k -l keep-source -d 'Keep the source studies or series'
+complete -c orthanc -n "__fish_seen_subcommand_from merge" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from merge" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from split" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from split" -s s -l series -d 'IDs of the series to move to the new study. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from split" -s r -l replace -d 'DICOM tags of the new study that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: StudyDescription=Thorax AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from split" -s k -l keep-source -d 'Keep the series in the original study'
+complete -c orthanc -n "__fish_seen_subcommand_from split" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from split" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -d 'Study ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Study ID' -r
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
@@ -112,41 +297,111 @@ complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "search" -d 'S
 complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "anonymize" -d 'Anonymize series'
 complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "modify" -d 'Modify series'
 complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "download" -d 'Download series'
+complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "tree" -d 'Show the tree of instances of a series'
+complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "stats" -d 'Show the number of instances and the size of a series'
+complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "metadata" -d 'Manage the metadata of a series'
+complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "label" -d 'Manage the labels of a series'
+complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "reconstruct" -d 'Reconstruct the main DICOM tags of a series from its DICOM files'
 complete -c orthanc -n "__fish_seen_subcommand_from series" -f -a "delete" -d 'Delete series'
-complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID Modality BodyPartExamined meta:RemoteAET' -r
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s m -l metadata -d 'Also show the metadata of the series'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s l -l labels -d 'Also show the labels of the series'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -d 'Series ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID InstanceCreationDate meta:RemoteAET' -r
 complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list-instances" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separted pairs TagName=TagValue. Wildcards are allowed. Example: SeriesDescription=*BRAIN* SeriesDate=20200101' -r
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID Modality BodyPartExamined' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separated TagName=TagValue, TagName>=TagValue, TagName<=TagValue or \'TagName in (TagValue1,TagValue2)\' terms. Wildcards and date ranges are allowed, dates can be relative (e.g. -7d). Tags can be prefixed with a parent level (e.g. Study.StudyDate). Example: \'Modality in (CT,MR)\' Patient.PatientName=*Sanchez*' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l sort -d 'Sort by the columns specified. Space-separated values. Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l limit -d 'Return at most this number of results' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label -d 'Only return the resources that have all these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label-any -d 'Only return the resources that have any of these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label-none -d 'Only return the resources that have none of these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID Modality BodyPartExamined meta:RemoteAET' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s i -l ignore-case -d 'Case-insensitive matching of query terms'
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l count -d 'Only print the number of results'
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l exists -d 'Print nothing, exit with 0 if anything matches the query, 1 otherwise, 2 on error'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Series ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s k -l keep -d 'DICOM tags that should be kept intact. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s c -l config -d 'Anonymization configuration file' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s p -l keep-private-tags -d 'Keep private tags intact'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Series ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l remove -d 'DICOM tags that should be removed. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s c -l config -d 'Modification configuration file' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Series ID' -r
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l extract -d 'Extract the archive to this directory instead of writing it to a file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l layout -d 'Path template of the extracted files, with {TagName} placeholders replaced with the values of the instance, series, study and patient DICOM tags. Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}_{Modality}/{InstanceNumber}.dcm' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l transcode -d 'Transcode the instances to this transfer syntax. A UID, or one of: implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l media -d 'Download a media archive with a DICOMDIR, e.g. for burning to a CD'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l skip-existing -d 'Don\'t download again what is already there: keep the output file if it is a complete archive, or the files already written with --layout'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l depth -d 'Lowest level to display' -r -f -a "instance"
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -l output -d 'Output format' -r -f -a "text json"
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from tree" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "list" -d 'List the metadata of a series'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "get" -d 'Print the value of a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "set" -d 'Set the value of a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "delete" -d 'Delete a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from get" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Metadata value' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "list" -d 'List the labels of a series'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "add" -d 'Add a label to series'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "remove" -d 'Remove a label from series'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Label' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Series IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Label' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Series IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -d 'Series ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Series ID' -r
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
@@ -159,24 +414,45 @@ complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "search" -d
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "anonymize" -d 'Anonymize instance'
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "modify" -d 'Modify instance'
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "download" -d 'Download instance'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "frames" -d 'List instance frames'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "frame" -d 'Download instance frame'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "extract-pdf" -d 'Extract encapsulated PDF document from instance'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "extract-document" -d 'Extract encapsulated document (PDF, CDA, STL, OBJ, MTL) from instance'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "create" -d 'Create instance from DICOM tags and an image or a PDF document'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "stats" -d 'Show the size of an instance'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "metadata" -d 'Manage the metadata of an instance'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "attachments" -d 'List the attachments of an instance'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "attachment" -d 'Manage an attachment of an instance'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "label" -d 'Manage the labels of an instance'
+complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "reconstruct" -d 'Reconstruct the main DICOM tags of an instance from its DICOM files'
 complete -c orthanc -n "__fish_seen_subcommand_from instance" -f -a "delete" -d 'Delete instance'
-complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID InstanceCreationDate meta:RemoteAET' -r
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s m -l metadata -d 'Also show the metadata of the instance'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s l -l labels -d 'Also show the labels of the instance'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from tags" -d 'Instance ID' -r
 complete -c orthanc -n "__fish_seen_subcommand_from tags" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from tags" -s V -l version -d 'Prints version information'
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separted pairs TagName=TagValue. Wildcards are allowed. Example: InstanceNumber=42 InstanceCreationTime=174242' -r
-complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Example: ID InstanceCreationDate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s q -l query -d 'Search query terms. Space-separated TagName=TagValue, TagName>=TagValue, TagName<=TagValue or \'TagName in (TagValue1,TagValue2)\' terms. Wildcards and date ranges are allowed, dates can be relative (e.g. -7d). Tags can be prefixed with a parent level (e.g. Study.StudyDate). Example: InstanceNumber=42 InstanceCreationTime=174242' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l sort -d 'Sort by the columns specified. Space-separated values. Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l limit -d 'Return at most this number of results' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label -d 'Only return the resources that have all these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label-any -d 'Only return the resources that have any of these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l label-none -d 'Only return the resources that have none of these labels. Space-separated values' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID InstanceCreationDate meta:RemoteAET' -r
+complete -c orthanc -n "__fish_seen_subcommand_from search" -s i -l ignore-case -d 'Case-insensitive matching of query terms'
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l count -d 'Only print the number of results'
+complete -c orthanc -n "__fish_seen_subcommand_from search" -l exists -d 'Print nothing, exit with 0 if anything matches the query, 1 otherwise, 2 on error'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s n -l no-header -d 'Don\'t display table header'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from search" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -d 'Instance ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s k -l keep -d 'DICOM tags that should be kept intact. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s c -l config -d 'Anonymization configuration file' -r
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s o -l output -d 'Output file path' -r
@@ -184,7 +460,7 @@ complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s p -l keep-priv
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from anonymize" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -d 'Instance ID' -r
-complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separted pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
+complete -c orthanc -n "__fish_seen_subcommand_from modify" -s r -l replace -d 'DICOM tags that should be replaced with the values specified. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED AccessionNumber=42' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s m -l remove -d 'DICOM tags that should be removed. Space-separated tag names. Example: PatientSex PatientBirthDate' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s c -l config -d 'Modification configuration file' -r
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s o -l output -d 'Output file path' -r
@@ -192,8 +468,106 @@ complete -c orthanc -n "__fish_seen_subcommand_from modify" -s h -l help -d 'Pri
 complete -c orthanc -n "__fish_seen_subcommand_from modify" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Instance ID' -r
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l transcode -d 'Transcode the instances to this transfer syntax. A UID, or one of: implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l skip-existing -d 'Don\'t download the instance again if the output file is already there'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from frames" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from frames" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from frames" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from frame" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from frame" -d 'Frame number (starting from 0)' -r
+complete -c orthanc -n "__fish_seen_subcommand_from frame" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from frame" -l raw -d 'Write the original pixel data of the frame instead of a PNG image, along with a JSON sidecar file (<OUTPUT>.json) describing it'
+complete -c orthanc -n "__fish_seen_subcommand_from frame" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from frame" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from extract-pdf" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from extract-pdf" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from extract-pdf" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from extract-pdf" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from extract-document" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from extract-document" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from extract-document" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from extract-document" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l tags -d 'YAML file with DICOM tags of the instance (TagName: TagValue)' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s t -l tag -d 'DICOM tags of the instance, overriding those in the tags file. Space-separated pairs TagName=TagValue. Example: PatientName=REMOVED Modality=OT' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l image -d 'PNG or JPEG image to use as pixel data' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l pdf -d 'PDF document to encapsulate' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -l parent -d 'ID of the patient, study or series to attach the instance to' -r
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from create" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "list" -d 'List the metadata of an instance'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "get" -d 'Print the value of a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "set" -d 'Set the value of a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from metadata" -f -a "delete" -d 'Delete a metadata'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from get" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Metadata value' -r
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Metadata name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from attachments" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from attachments" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from attachments" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from attachments" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from attachment" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from attachment" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from attachment" -f -a "verify" -d 'Check the MD5 of an attachment on the server'
+complete -c orthanc -n "__fish_seen_subcommand_from attachment" -f -a "compress" -d 'Compress an attachment on the server'
+complete -c orthanc -n "__fish_seen_subcommand_from attachment" -f -a "uncompress" -d 'Uncompress an attachment on the server'
+complete -c orthanc -n "__fish_seen_subcommand_from attachment" -f -a "download" -d 'Download the (uncompressed) data of an attachment'
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -d 'Attachment name. Example: dicom' -r
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from compress" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from compress" -d 'Attachment name. Example: dicom' -r
+complete -c orthanc -n "__fish_seen_subcommand_from compress" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from compress" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from uncompress" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from uncompress" -d 'Attachment name. Example: dicom' -r
+complete -c orthanc -n "__fish_seen_subcommand_from uncompress" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from uncompress" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -d 'Attachment name. Example: dicom' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "list" -d 'List the labels of an instance'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "add" -d 'Add a label to instances'
+complete -c orthanc -n "__fish_seen_subcommand_from label" -f -a "remove" -d 'Remove a label from instances'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Label' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -d 'Instance IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from add" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Label' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -d 'Instance IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from remove" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -d 'Instance ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Instance ID' -r
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
@@ -230,10 +604,114 @@ complete -c orthanc -n "__fish_seen_subcommand_from echo" -s h -l help -d 'Print
 complete -c orthanc -n "__fish_seen_subcommand_from echo" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from store" -d 'Modality name' -r
 complete -c orthanc -n "__fish_seen_subcommand_from store" -s e -l entity-ids -d 'Entity IDs' -r
+complete -c orthanc -n "__fish_seen_subcommand_from store" -l transcode -d 'Transcode the instances to this transfer syntax. A UID, or one of: implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle' -r
 complete -c orthanc -n "__fish_seen_subcommand_from store" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from store" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Modality name' -r
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -l since -d 'Only list changes after this sequence number' -r
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -l type -d 'Only list changes of these types. Comma-separated values. Example: NewStudy,StableSeries' -r
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -l state-file -d 'File keeping the sequence number of the last change seen with --follow [default: changes.seq in the state directory of the server, in the configuration directory]' -r
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -l exec -d 'Run this shell command for every change, with --follow. {{id}}, {{type}}, {{level}} and {{seq}} are replaced with the shell-quoted values of the change, which are also available, along with the main DICOM tags of the entity, in ORC_CHANGE_* and ORC_TAG_* environment variables. Example: \'script.sh {{id}} {{type}}\'' -r
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -l post -d 'POST every change as a JSON document to this URL, with --follow' -r
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -l concurrency -d 'Maximum number of changes handled at the same time with --exec or --post' -r
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -l retries -d 'Number of retries of a failed --exec or --post, with increasing delays' -r
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -l dead-letter -d 'File the changes that could not be handled are appended to, one JSON document per line [default: changes-dead-letter.jsonl in the state directory of the server, in the configuration directory]' -r
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -s f -l follow -d 'Keep polling for new changes, printing them as they come. Without --since, resumes after the last change seen (see --state-file), or starts with the changes to come'
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from changes" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -f -a "save" -d 'Save a search query under a name, replacing any query with the same name'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -f -a "list" -d 'List saved queries'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -f -a "run" -d 'Run a saved query'
+complete -c orthanc -n "__fish_seen_subcommand_from query" -f -a "delete" -d 'Delete a saved query'
+complete -c orthanc -n "__fish_seen_subcommand_from save" -d 'Query name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from save" -s l -l level -d 'Level of the entities to search for' -r -f -a "patient study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from save" -s q -l query -d 'Search query terms, as in the search commands. Values can contain placeholders: {{date:-1d}} for dates relative to the day the query is run, {{name}} for parameters passed to query run. Example: StudyDate={{date:-1d}} AccessionNumber={{accession}}' -r
+complete -c orthanc -n "__fish_seen_subcommand_from save" -s c -l columns -d 'Display only the columns specified. Space-separated values. Labels and metadata (meta:<Name>) columns are only displayed when requested. Example: ID StudyDate meta:RemoteAET' -r
+complete -c orthanc -n "__fish_seen_subcommand_from save" -l sort -d 'Sort by the columns specified. Space-separated values. Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime' -r
+complete -c orthanc -n "__fish_seen_subcommand_from save" -s i -l ignore-case -d 'Case-insensitive matching of query terms'
+complete -c orthanc -n "__fish_seen_subcommand_from save" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from save" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from run" -d 'Query name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from run" -l param -d 'Values of the query placeholders. Space-separated pairs name=value. Example: accession=XYZ' -r
+complete -c orthanc -n "__fish_seen_subcommand_from run" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from run" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from run" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -d 'Query name' -r
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from delete" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from lookup" -d 'Value to look up. Can be prefixed with uid:, accession: or pid: to restrict the lookup to that kind of value' -r
+complete -c orthanc -n "__fish_seen_subcommand_from lookup" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from lookup" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -f -a "info" -d 'Show server version, DICOM settings, storage backends and plugins'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -f -a "stats" -d 'Show the number of stored entities and the disk usage'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -f -a "log-level" -d 'Get or set the log level of the server'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -f -a "verify" -d 'Check the MD5 of the attachments of all the instances of the server'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -f -a "reconstruct-all" -d 'Reconstruct the main DICOM tags of all the studies. An interrupted reconstruction resumes where it stopped'
+complete -c orthanc -n "__fish_seen_subcommand_from system" -f -a "check-main-tags" -d 'List the entities missing some of the main DICOM tags the server is configured with, and the tags they miss. They need to be reconstructed'
+complete -c orthanc -n "__fish_seen_subcommand_from info" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from info" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from stats" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from log-level" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from log-level" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from log-level" -f -a "get" -d 'Show the log level of the server, and of each log category if supported'
+complete -c orthanc -n "__fish_seen_subcommand_from log-level" -f -a "set" -d 'Set the log level of the server, or of a log category'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -l category -d 'Only show the log level of this category' -r -f -a "generic plugins http dicom sqlite lua jobs"
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from get" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -d 'Log level' -r -f -a "default verbose trace"
+complete -c orthanc -n "__fish_seen_subcommand_from set" -l category -d 'Only set the log level of this category (requires Orthanc 1.8.1 or later)' -r -f -a "generic plugins http dicom sqlite lua jobs"
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from set" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -l concurrency -d 'Number of instances verified at the same time' -r
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from verify" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct-all" -s q -l query -d 'Only reconstruct the studies matching these search query terms (see study search). Example: StudyDate=20200101-20201231' -r
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct-all" -l concurrency -d 'Number of studies reconstructed at the same time' -r
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct-all" -l state-file -d 'File keeping the IDs of the studies already reconstructed [default: reconstruct.state in the state directory of the server, in the configuration directory]' -r
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct-all" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct-all" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from reconstruct-all" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from check-main-tags" -s l -l level -d 'Entity level' -r -f -a "patient study series instance"
+complete -c orthanc -n "__fish_seen_subcommand_from check-main-tags" -l concurrency -d 'Number of entities checked at the same time' -r
+complete -c orthanc -n "__fish_seen_subcommand_from check-main-tags" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from check-main-tags" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from check-main-tags" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l ids -d 'Patient, study or series IDs. Read from stdin if omitted or -' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s o -l output -d 'Output file path' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l extract -d 'Extract the archive to this directory instead of writing it to a file' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l transcode -d 'Transcode the instances to this transfer syntax. A UID, or one of: implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle' -r
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l media -d 'Download a media archive with a DICOMDIR, e.g. for burning to a CD'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -l skip-existing -d 'Keep the output file if it is already a complete archive'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from download" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from plugin" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from plugin" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from plugin" -f -a "list" -d 'List plugins loaded by the server'
+complete -c orthanc -n "__fish_seen_subcommand_from plugin" -f -a "show" -d 'Show plugin details, and the orthanc-cli features it enables for well-known plugins'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s n -l no-header -d 'Don\'t display table header'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from list" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -d 'Plugin ID' -r
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from show" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from tools" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from tools" -s V -l version -d 'Prints version information'
+complete -c orthanc -n "__fish_seen_subcommand_from tools" -f -a "exec-lua" -d 'Execute a Lua script on the server and print its output'
+complete -c orthanc -n "__fish_seen_subcommand_from exec-lua" -d 'Lua script file. Use - to read the script from stdin' -r
+complete -c orthanc -n "__fish_seen_subcommand_from exec-lua" -s v -l var -d 'Set a Lua variable before running the script. The value is passed as a string. Example: -v name=value' -r
+complete -c orthanc -n "__fish_seen_subcommand_from exec-lua" -s h -l help -d 'Prints help information'
+complete -c orthanc -n "__fish_seen_subcommand_from exec-lua" -s V -l version -d 'Prints version information'
 complete -c orthanc -n "__fish_seen_subcommand_from help" -s h -l help -d 'Prints help information'
 complete -c orthanc -n "__fish_seen_subcommand_from help" -s V -l version -d 'Prints version information'
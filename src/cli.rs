@@ -1,3 +1,4 @@
+use crate::constants::LOG_CATEGORIES;
 use clap::{crate_authors, crate_description, crate_version, App, Arg};
 
 pub fn build_cli() -> App<'static> {
@@ -70,7 +71,8 @@ pub fn build_cli() -> App<'static> {
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID PatientName",
+                                        "Protected, Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID PatientName Protected meta:LastUpdate",
                                     )
                                 )
                                 .takes_value(true)
@@ -90,6 +92,18 @@ pub fn build_cli() -> App<'static> {
                                 .about("Patient ID")
                                 .required(true)
                                 .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("metadata")
+                                .about("Also show the metadata of the patient")
+                                .short('m')
+                                .long("metadata"),
+                        )
+                        .arg(
+                            Arg::new("labels")
+                                .about("Also show the labels of the patient")
+                                .short('l')
+                                .long("labels"),
                         ),
                 )
                 .subcommand(
@@ -113,7 +127,8 @@ pub fn build_cli() -> App<'static> {
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID AccessionNumber StudyDate",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID AccessionNumber StudyDate meta:RemoteAET",
                                     )
                                 )
                                 .takes_value(true)
@@ -131,10 +146,12 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("query")
                                 .about(concat!(
-                                    "Search query terms. Space-separted pairs TagName=TagValue. ",
-                                    "Wildcards are allowed. Example: PatientSex=F PatientName=*Sanchez*",
+                                    "Search query terms. Space-separated TagName=TagValue, TagName>=TagValue, ",
+                                    "TagName<=TagValue or 'TagName in (TagValue1,TagValue2)' terms. ",
+                                    "Wildcards and date ranges are allowed, dates can be relative (e.g. -7d). ",
+                                    "Tags can be prefixed with a parent level (e.g. Study.StudyDate). Example: PatientSex=F PatientName=*Sanchez*",
                                 ))
-                                .required(true)
+                                .required_unless_present_any(&["label", "label_any", "label_none"])
                                 .takes_value(true)
                                 .short('q')
                                 .long("query")
@@ -142,6 +159,73 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("QUERY"),
                         )
+                        .arg(
+                            Arg::new("ignore_case")
+                                .about("Case-insensitive matching of query terms")
+                                .short('i')
+                                .long("ignore-case"),
+                        )
+                        .arg(
+                            Arg::new("sort")
+                                .about(concat!(
+                                    "Sort by the columns specified. Space-separated values. ",
+                                    "Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime",
+                                ))
+                                .takes_value(true)
+                                .long("sort")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .allow_hyphen_values(true)
+                                .value_name("COLUMNS"),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Return at most this number of results")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<u64>())
+                                .long("limit")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("count")
+                                .about("Only print the number of results")
+                                .conflicts_with_all(&["exists", "limit"])
+                                .long("count"),
+                        )
+                        .arg(
+                            Arg::new("exists")
+                                .about("Print nothing, exit with 0 if anything matches the query, 1 otherwise, 2 on error")
+                                .long("exists"),
+                        )
+                        .arg(
+                            Arg::new("label")
+                                .about("Only return the resources that have all these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .conflicts_with_all(&["label_any", "label_none"])
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("label_any")
+                                .about("Only return the resources that have any of these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label-any")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .conflicts_with("label_none")
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("label_none")
+                                .about("Only return the resources that have none of these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label-none")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("LABELS"),
+                        )
                         .arg(
                             Arg::new("no_header")
                                 .about("Don't display table header")
@@ -153,7 +237,8 @@ pub fn build_cli() -> App<'static> {
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID PatientName",
+                                        "Protected, Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID PatientName Protected meta:LastUpdate",
                                     )
                                 )
                                 .takes_value(true)
@@ -178,7 +263,7 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("replace")
                                 .about(concat!(
                                     "DICOM tags that should be replaced with the values specified. ",
-                                    "Space-separted pairs TagName=TagValue. ",
+                                    "Space-separated pairs TagName=TagValue. ",
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
@@ -234,7 +319,7 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("replace")
                                 .about(concat!(
                                     "DICOM tags that should be replaced with the values specified. ",
-                                    "Space-separted pairs TagName=TagValue. ",
+                                    "Space-separated pairs TagName=TagValue. ",
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
@@ -288,13 +373,261 @@ pub fn build_cli() -> App<'static> {
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
-                                .required(true)
+                                .required_unless_present("extract")
+                                .conflicts_with("extract")
                                 .value_name("OUTPUT"),
+                        )
+                        .arg(
+                            Arg::new("media")
+                                .about("Download a media archive with a DICOMDIR, e.g. for burning to a CD")
+                                .conflicts_with("layout")
+                                .long("media"),
+                        )
+                        .arg(
+                            Arg::new("extract")
+                                .about("Extract the archive to this directory instead of writing it to a file")
+                                .takes_value(true)
+                                .long("extract")
+                                .value_name("DIR"),
+                        )
+                        .arg(
+                            Arg::new("layout")
+                                .about(concat!(
+                                    "Path template of the extracted files, with {TagName} placeholders replaced ",
+                                    "with the values of the instance, series, study and patient DICOM tags. ",
+                                    "Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}_{Modality}/{InstanceNumber}.dcm",
+                                ))
+                                .takes_value(true)
+                                .requires("extract")
+                                .long("layout")
+                                .value_name("TEMPLATE"),
+                        )
+                        .arg(
+                            Arg::new("transcode")
+                                .about(concat!(
+                                    "Transcode the instances to this transfer syntax. A UID, or one of: ",
+                                    "implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, ",
+                                    "jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle",
+                                ))
+                                .takes_value(true)
+                                .long("transcode")
+                                .value_name("SYNTAX"),
+                        )
+                        .arg(
+                            Arg::new("skip_existing")
+                                .about(concat!(
+                                    "Don't download again what is already there: keep the output file if it is a complete ",
+                                    "archive, or the files already written with --layout",
+                                ))
+                                .long("skip-existing"),
                         ),
                 )
                 .subcommand(
-                    App::new("delete")
+                    App::new("tree")
                         .display_order(7)
+                        .about("Show the tree of studies, series and instances of a patient")
+                        .arg(
+                            Arg::new("id")
+                                .about("Patient ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("depth")
+                                .about("Lowest level to display")
+                                .takes_value(true)
+                                .possible_values(&["study", "series", "instance"])
+                                .long("depth")
+                                .value_name("LEVEL"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .about("Output format")
+                                .takes_value(true)
+                                .possible_values(&["text", "json"])
+                                .default_value("text")
+                                .long("output")
+                                .value_name("FORMAT"),
+                        ),
+                )
+                .subcommand(
+                    App::new("stats")
+                        .display_order(8)
+                        .about("Show the number of studies, series and instances and the size of a patient")
+                        .arg(
+                            Arg::new("id")
+                                .about("Patient ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("protect")
+                        .display_order(9)
+                        .about("Protect patients from recycling when the storage is full")
+                        .arg(
+                            Arg::new("ids")
+                                .about("Patient IDs. Read from stdin if omitted or -")
+                                .multiple_values(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("unprotect")
+                        .display_order(10)
+                        .about("Allow patients to be recycled when the storage is full")
+                        .arg(
+                            Arg::new("ids")
+                                .about("Patient IDs. Read from stdin if omitted or -")
+                                .multiple_values(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("metadata")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(11)
+                        .about("Manage the metadata of a patient")
+                        .subcommand(
+                            App::new("list")
+                                .display_order(0)
+                                .about("List the metadata of a patient")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Patient ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("no_header")
+                                        .about("Don't display table header")
+                                        .short('n')
+                                        .long("no-header"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("get")
+                                .display_order(1)
+                                .about("Print the value of a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Patient ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("set")
+                                .display_order(2)
+                                .about("Set the value of a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Patient ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                )
+                                .arg(
+                                    Arg::new("value")
+                                        .about("Metadata value")
+                                        .required(true)
+                                        .value_name("VALUE"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("delete")
+                                .display_order(3)
+                                .about("Delete a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Patient ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("label")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(12)
+                        .about("Manage the labels of a patient")
+                        .subcommand(
+                            App::new("list")
+                                .display_order(0)
+                                .about("List the labels of a patient")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Patient ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("add")
+                                .display_order(1)
+                                .about("Add a label to patients")
+                                .arg(
+                                    Arg::new("label")
+                                        .about("Label")
+                                        .required(true)
+                                        .value_name("LABEL"),
+                                )
+                                .arg(
+                                    Arg::new("ids")
+                                        .about("Patient IDs. Read from stdin if omitted or -")
+                                        .multiple_values(true)
+                                        .value_name("ID"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("remove")
+                                .display_order(2)
+                                .about("Remove a label from patients")
+                                .arg(
+                                    Arg::new("label")
+                                        .about("Label")
+                                        .required(true)
+                                        .value_name("LABEL"),
+                                )
+                                .arg(
+                                    Arg::new("ids")
+                                        .about("Patient IDs. Read from stdin if omitted or -")
+                                        .multiple_values(true)
+                                        .value_name("ID"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("reconstruct")
+                        .display_order(13)
+                        .about("Reconstruct the main DICOM tags of a patient from its DICOM files")
+                        .arg(
+                            Arg::new("id")
+                                .about("Patient ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("delete")
+                        .display_order(14)
                         .about("Delete patient")
                         .arg(
                             Arg::new("id")
@@ -324,7 +657,8 @@ pub fn build_cli() -> App<'static> {
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID AccessionNumber StudyDate",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID AccessionNumber StudyDate meta:RemoteAET",
                                     )
                                 )
                                 .takes_value(true)
@@ -344,6 +678,18 @@ pub fn build_cli() -> App<'static> {
                                 .about("Study ID")
                                 .required(true)
                                 .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("metadata")
+                                .about("Also show the metadata of the study")
+                                .short('m')
+                                .long("metadata"),
+                        )
+                        .arg(
+                            Arg::new("labels")
+                                .about("Also show the labels of the study")
+                                .short('l')
+                                .long("labels"),
                         ),
                 )
                 .subcommand(
@@ -367,7 +713,8 @@ pub fn build_cli() -> App<'static> {
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID Modality BodyPartExamined",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID Modality BodyPartExamined meta:RemoteAET",
                                     )
                                 )
                                 .takes_value(true)
@@ -385,10 +732,12 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("query")
                                 .about(concat!(
-                                    "Search query terms. Space-separted pairs TagName=TagValue. ",
-                                    "Wildcards are allowed. Example: StudyDescription=*BRAIN* StudyDate=20200101",
+                                    "Search query terms. Space-separated TagName=TagValue, TagName>=TagValue, ",
+                                    "TagName<=TagValue or 'TagName in (TagValue1,TagValue2)' terms. ",
+                                    "Wildcards and date ranges are allowed, dates can be relative (e.g. -7d). ",
+                                    "Tags can be prefixed with a parent level (e.g. Study.StudyDate). Example: StudyDescription=*BRAIN* 'StudyDate>=-7d'",
                                 ))
-                                .required(true)
+                                .required_unless_present_any(&["label", "label_any", "label_none"])
                                 .takes_value(true)
                                 .short('q')
                                 .long("query")
@@ -396,6 +745,73 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("QUERY"),
                         )
+                        .arg(
+                            Arg::new("ignore_case")
+                                .about("Case-insensitive matching of query terms")
+                                .short('i')
+                                .long("ignore-case"),
+                        )
+                        .arg(
+                            Arg::new("sort")
+                                .about(concat!(
+                                    "Sort by the columns specified. Space-separated values. ",
+                                    "Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime",
+                                ))
+                                .takes_value(true)
+                                .long("sort")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .allow_hyphen_values(true)
+                                .value_name("COLUMNS"),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Return at most this number of results")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<u64>())
+                                .long("limit")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("count")
+                                .about("Only print the number of results")
+                                .conflicts_with_all(&["exists", "limit"])
+                                .long("count"),
+                        )
+                        .arg(
+                            Arg::new("exists")
+                                .about("Print nothing, exit with 0 if anything matches the query, 1 otherwise, 2 on error")
+                                .long("exists"),
+                        )
+                        .arg(
+                            Arg::new("label")
+                                .about("Only return the resources that have all these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .conflicts_with_all(&["label_any", "label_none"])
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("label_any")
+                                .about("Only return the resources that have any of these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label-any")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .conflicts_with("label_none")
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("label_none")
+                                .about("Only return the resources that have none of these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label-none")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("LABELS"),
+                        )
                         .arg(
                             Arg::new("no_header")
                                 .about("Don't display table header")
@@ -407,7 +823,8 @@ pub fn build_cli() -> App<'static> {
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID AccessionNumber StudyDate",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID AccessionNumber StudyDate meta:RemoteAET",
                                     )
                                 )
                                 .takes_value(true)
@@ -432,7 +849,7 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("replace")
                                 .about(concat!(
                                     "DICOM tags that should be replaced with the values specified. ",
-                                    "Space-separted pairs TagName=TagValue. ",
+                                    "Space-separated pairs TagName=TagValue. ",
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
@@ -488,7 +905,7 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("replace")
                                 .about(concat!(
                                     "DICOM tags that should be replaced with the values specified. ",
-                                    "Space-separted pairs TagName=TagValue. ",
+                                    "Space-separated pairs TagName=TagValue. ",
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
@@ -537,107 +954,68 @@ pub fn build_cli() -> App<'static> {
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
-                                .required(true)
+                                .required_unless_present("extract")
+                                .conflicts_with("extract")
                                 .value_name("OUTPUT"),
-                        ),
-                )
-                .subcommand(
-                    App::new("delete")
-                        .display_order(7)
-                        .about("Delete study")
-                        .arg(
-                            Arg::new("id")
-                                .about("Study ID")
-                                .required(true)
-                                .value_name("ID"),
-                        ),
-                ),
-        )
-        .subcommand(
-            App::new("series")
-                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
-                .display_order(2)
-                .about("Series-level commands")
-                .subcommand(
-                    App::new("list")
-                    .display_order(0)
-                    .about("List all series")
-                    .arg(
-                        Arg::new("no_header")
-                            .about("Don't display table header")
-                            .short('n')
-                            .long("no-header"),
-                    )
-                    .arg(
-                        Arg::new("columns")
-                            .about(
-                                concat!(
-                                    "Display only the columns specified. Space-separated values. ",
-                                    "Example: ID Modality BodyPartExamined",
-                                )
-                            )
-                            .takes_value(true)
-                            .short('c')
-                            .long("columns")
-                            .multiple_occurrences(true)
-                            .multiple_values(true)
-                            .value_name("COLUMNS"),
-                    )
-                )
-                .subcommand(
-                    App::new("show")
-                        .display_order(1)
-                        .about("Show series details")
+                        )
                         .arg(
-                            Arg::new("id")
-                                .about("Series ID")
-                                .required(true)
-                                .value_name("ID"),
-                        ),
-                )
-                .subcommand(
-                    App::new("list-instances")
-                        .display_order(2)
-                        .about("List all instances of a series")
+                            Arg::new("media")
+                                .about("Download a media archive with a DICOMDIR, e.g. for burning to a CD")
+                                .conflicts_with("layout")
+                                .long("media"),
+                        )
                         .arg(
-                            Arg::new("id")
-                                .about("Series ID")
-                                .required(true)
-                                .value_name("ID"),
+                            Arg::new("extract")
+                                .about("Extract the archive to this directory instead of writing it to a file")
+                                .takes_value(true)
+                                .long("extract")
+                                .value_name("DIR"),
                         )
                         .arg(
-                            Arg::new("no_header")
-                                .about("Don't display table header")
-                                .short('n')
-                                .long("no-header"),
+                            Arg::new("layout")
+                                .about(concat!(
+                                    "Path template of the extracted files, with {TagName} placeholders replaced ",
+                                    "with the values of the instance, series, study and patient DICOM tags. ",
+                                    "Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}_{Modality}/{InstanceNumber}.dcm",
+                                ))
+                                .takes_value(true)
+                                .requires("extract")
+                                .long("layout")
+                                .value_name("TEMPLATE"),
                         )
                         .arg(
-                            Arg::new("columns")
-                                .about(
-                                    concat!(
-                                        "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID InstanceCreationDate",
-                                    )
-                                )
+                            Arg::new("transcode")
+                                .about(concat!(
+                                    "Transcode the instances to this transfer syntax. A UID, or one of: ",
+                                    "implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, ",
+                                    "jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle",
+                                ))
                                 .takes_value(true)
-                                .short('c')
-                                .long("columns")
-                                .multiple_occurrences(true)
-                                .multiple_values(true)
-                                .value_name("COLUMNS"),
+                                .long("transcode")
+                                .value_name("SYNTAX"),
                         )
+                        .arg(
+                            Arg::new("skip_existing")
+                                .about(concat!(
+                                    "Don't download again what is already there: keep the output file if it is a complete ",
+                                    "archive, or the files already written with --layout",
+                                ))
+                                .long("skip-existing"),
+                        ),
                 )
                 .subcommand(
-                    App::new("search")
-                        .display_order(3)
-                        .about("Search for series")
+                    App::new("download-many")
+                        .display_order(7)
+                        .about(concat!(
+                            "Download studies in parallel, each to its own archive (or directory with --extract) ",
+                            "named after the study ID. A manifest.csv file lists the downloaded studies",
+                        ))
                         .arg(
                             Arg::new("query")
                                 .about(concat!(
-                                    "Search query terms. Space-separted pairs TagName=TagValue. ",
-                                    "Wildcards are allowed. Example: SeriesDescription=*BRAIN* SeriesDate=20200101",
+                                    "Only download the studies matching these search query terms ",
+                                    "(see study search). Example: StudyDate=20200101-20201231",
                                 ))
-                                .required(true)
                                 .takes_value(true)
                                 .short('q')
                                 .long("query")
@@ -646,17 +1024,522 @@ pub fn build_cli() -> App<'static> {
                                 .value_name("QUERY"),
                         )
                         .arg(
-                            Arg::new("no_header")
-                                .about("Don't display table header")
-                                .short('n')
-                                .long("no-header"),
+                            Arg::new("dir")
+                                .about(concat!(
+                                    "Output directory. Studies already downloaded there, with the size ",
+                                    "given in the manifest, are skipped",
+                                ))
+                                .takes_value(true)
+                                .short('d')
+                                .long("dir")
+                                .required(true)
+                                .value_name("DIR"),
+                        )
+                        .arg(
+                            Arg::new("concurrency")
+                                .about("Number of studies downloaded at the same time")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<usize>())
+                                .default_value("4")
+                                .long("concurrency")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("extract")
+                                .about("Extract each archive to a directory instead of keeping it")
+                                .long("extract"),
+                        )
+                        .arg(
+                            Arg::new("transcode")
+                                .about(concat!(
+                                    "Transcode the instances to this transfer syntax. A UID, or one of: ",
+                                    "implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, ",
+                                    "jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle",
+                                ))
+                                .takes_value(true)
+                                .long("transcode")
+                                .value_name("SYNTAX"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("tree")
+                        .display_order(8)
+                        .about("Show the tree of series and instances of a study")
+                        .arg(
+                            Arg::new("id")
+                                .about("Study ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("depth")
+                                .about("Lowest level to display")
+                                .takes_value(true)
+                                .possible_values(&["series", "instance"])
+                                .long("depth")
+                                .value_name("LEVEL"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .about("Output format")
+                                .takes_value(true)
+                                .possible_values(&["text", "json"])
+                                .default_value("text")
+                                .long("output")
+                                .value_name("FORMAT"),
+                        ),
+                )
+                .subcommand(
+                    App::new("stats")
+                        .display_order(9)
+                        .about("Show the number of series and instances and the size of a study")
+                        .arg(
+                            Arg::new("id")
+                                .about("Study ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("metadata")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(10)
+                        .about("Manage the metadata of a study")
+                        .subcommand(
+                            App::new("list")
+                                .display_order(0)
+                                .about("List the metadata of a study")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Study ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("no_header")
+                                        .about("Don't display table header")
+                                        .short('n')
+                                        .long("no-header"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("get")
+                                .display_order(1)
+                                .about("Print the value of a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Study ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("set")
+                                .display_order(2)
+                                .about("Set the value of a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Study ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                )
+                                .arg(
+                                    Arg::new("value")
+                                        .about("Metadata value")
+                                        .required(true)
+                                        .value_name("VALUE"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("delete")
+                                .display_order(3)
+                                .about("Delete a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Study ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("verify")
+                        .display_order(11)
+                        .about("Check the MD5 of the attachments of all the instances of a study")
+                        .arg(
+                            Arg::new("id")
+                                .about("Study ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("concurrency")
+                                .about("Number of instances verified at the same time")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<usize>())
+                                .default_value("4")
+                                .long("concurrency")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("label")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(12)
+                        .about("Manage the labels of a study")
+                        .subcommand(
+                            App::new("list")
+                                .display_order(0)
+                                .about("List the labels of a study")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Study ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("add")
+                                .display_order(1)
+                                .about("Add a label to studies")
+                                .arg(
+                                    Arg::new("label")
+                                        .about("Label")
+                                        .required(true)
+                                        .value_name("LABEL"),
+                                )
+                                .arg(
+                                    Arg::new("ids")
+                                        .about("Study IDs. Read from stdin if omitted or -")
+                                        .multiple_values(true)
+                                        .value_name("ID"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("remove")
+                                .display_order(2)
+                                .about("Remove a label from studies")
+                                .arg(
+                                    Arg::new("label")
+                                        .about("Label")
+                                        .required(true)
+                                        .value_name("LABEL"),
+                                )
+                                .arg(
+                                    Arg::new("ids")
+                                        .about("Study IDs. Read from stdin if omitted or -")
+                                        .multiple_values(true)
+                                        .value_name("ID"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("merge")
+                        .display_order(13)
+                        .about("Merge studies or series into a study")
+                        .arg(
+                            Arg::new("id")
+                                .about("Target study ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("sources")
+                                .about("IDs of the studies or series to merge into the target study")
+                                .required(true)
+                                .multiple_values(true)
+                                .value_name("SOURCE_ID"),
+                        )
+                        .arg(
+                            Arg::new("keep_source")
+                                .about("Keep the source studies or series")
+                                .short('k')
+                                .long("keep-source"),
+                        ),
+                )
+                .subcommand(
+                    App::new("split")
+                        .display_order(14)
+                        .about("Move series of a study to a new study")
+                        .arg(
+                            Arg::new("id")
+                                .about("Study ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("series")
+                                .about("IDs of the series to move to the new study. Space-separated values")
+                                .required(true)
+                                .takes_value(true)
+                                .short('s')
+                                .long("series")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("SERIES_ID"),
+                        )
+                        .arg(
+                            Arg::new("replace")
+                                .about(concat!(
+                                    "DICOM tags of the new study that should be replaced with the values specified. ",
+                                    "Space-separated pairs TagName=TagValue. ",
+                                    "Example: StudyDescription=Thorax AccessionNumber=42",
+                                ))
+                                .takes_value(true)
+                                .short('r')
+                                .long("replace")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("TAG"),
+                        )
+                        .arg(
+                            Arg::new("keep_source")
+                                .about("Keep the series in the original study")
+                                .short('k')
+                                .long("keep-source"),
+                        ),
+                )
+                .subcommand(
+                    App::new("reconstruct")
+                        .display_order(15)
+                        .about("Reconstruct the main DICOM tags of a study from its DICOM files")
+                        .arg(
+                            Arg::new("id")
+                                .about("Study ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("delete")
+                        .display_order(16)
+                        .about("Delete study")
+                        .arg(
+                            Arg::new("id")
+                                .about("Study ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                ),
+        )
+        .subcommand(
+            App::new("series")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .display_order(2)
+                .about("Series-level commands")
+                .subcommand(
+                    App::new("list")
+                    .display_order(0)
+                    .about("List all series")
+                    .arg(
+                        Arg::new("no_header")
+                            .about("Don't display table header")
+                            .short('n')
+                            .long("no-header"),
+                    )
+                    .arg(
+                        Arg::new("columns")
+                            .about(
+                                concat!(
+                                    "Display only the columns specified. Space-separated values. ",
+                                    "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                    "Example: ID Modality BodyPartExamined meta:RemoteAET",
+                                )
+                            )
+                            .takes_value(true)
+                            .short('c')
+                            .long("columns")
+                            .multiple_occurrences(true)
+                            .multiple_values(true)
+                            .value_name("COLUMNS"),
+                    )
+                )
+                .subcommand(
+                    App::new("show")
+                        .display_order(1)
+                        .about("Show series details")
+                        .arg(
+                            Arg::new("id")
+                                .about("Series ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("metadata")
+                                .about("Also show the metadata of the series")
+                                .short('m')
+                                .long("metadata"),
+                        )
+                        .arg(
+                            Arg::new("labels")
+                                .about("Also show the labels of the series")
+                                .short('l')
+                                .long("labels"),
+                        ),
+                )
+                .subcommand(
+                    App::new("list-instances")
+                        .display_order(2)
+                        .about("List all instances of a series")
+                        .arg(
+                            Arg::new("id")
+                                .about("Series ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        )
+                        .arg(
+                            Arg::new("columns")
+                                .about(
+                                    concat!(
+                                        "Display only the columns specified. Space-separated values. ",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID InstanceCreationDate meta:RemoteAET",
+                                    )
+                                )
+                                .takes_value(true)
+                                .short('c')
+                                .long("columns")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("COLUMNS"),
+                        )
+                )
+                .subcommand(
+                    App::new("search")
+                        .display_order(3)
+                        .about("Search for series")
+                        .arg(
+                            Arg::new("query")
+                                .about(concat!(
+                                    "Search query terms. Space-separated TagName=TagValue, TagName>=TagValue, ",
+                                    "TagName<=TagValue or 'TagName in (TagValue1,TagValue2)' terms. ",
+                                    "Wildcards and date ranges are allowed, dates can be relative (e.g. -7d). ",
+                                    "Tags can be prefixed with a parent level (e.g. Study.StudyDate). Example: 'Modality in (CT,MR)' Patient.PatientName=*Sanchez*",
+                                ))
+                                .required_unless_present_any(&["label", "label_any", "label_none"])
+                                .takes_value(true)
+                                .short('q')
+                                .long("query")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("QUERY"),
+                        )
+                        .arg(
+                            Arg::new("ignore_case")
+                                .about("Case-insensitive matching of query terms")
+                                .short('i')
+                                .long("ignore-case"),
+                        )
+                        .arg(
+                            Arg::new("sort")
+                                .about(concat!(
+                                    "Sort by the columns specified. Space-separated values. ",
+                                    "Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime",
+                                ))
+                                .takes_value(true)
+                                .long("sort")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .allow_hyphen_values(true)
+                                .value_name("COLUMNS"),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Return at most this number of results")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<u64>())
+                                .long("limit")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("count")
+                                .about("Only print the number of results")
+                                .conflicts_with_all(&["exists", "limit"])
+                                .long("count"),
+                        )
+                        .arg(
+                            Arg::new("exists")
+                                .about("Print nothing, exit with 0 if anything matches the query, 1 otherwise, 2 on error")
+                                .long("exists"),
+                        )
+                        .arg(
+                            Arg::new("label")
+                                .about("Only return the resources that have all these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .conflicts_with_all(&["label_any", "label_none"])
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("label_any")
+                                .about("Only return the resources that have any of these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label-any")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .conflicts_with("label_none")
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("label_none")
+                                .about("Only return the resources that have none of these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label-none")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
                         )
                         .arg(
                             Arg::new("columns")
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID Modality BodyPartExamined",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID Modality BodyPartExamined meta:RemoteAET",
                                     )
                                 )
                                 .takes_value(true)
@@ -681,7 +1564,7 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("replace")
                                 .about(concat!(
                                     "DICOM tags that should be replaced with the values specified. ",
-                                    "Space-separted pairs TagName=TagValue. ",
+                                    "Space-separated pairs TagName=TagValue. ",
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
@@ -737,7 +1620,7 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("replace")
                                 .about(concat!(
                                     "DICOM tags that should be replaced with the values specified. ",
-                                    "Space-separted pairs TagName=TagValue. ",
+                                    "Space-separated pairs TagName=TagValue. ",
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
@@ -791,13 +1674,239 @@ pub fn build_cli() -> App<'static> {
                                 .takes_value(true)
                                 .short('o')
                                 .long("output")
-                                .required(true)
+                                .required_unless_present("extract")
+                                .conflicts_with("extract")
                                 .value_name("OUTPUT"),
+                        )
+                        .arg(
+                            Arg::new("media")
+                                .about("Download a media archive with a DICOMDIR, e.g. for burning to a CD")
+                                .conflicts_with("layout")
+                                .long("media"),
+                        )
+                        .arg(
+                            Arg::new("extract")
+                                .about("Extract the archive to this directory instead of writing it to a file")
+                                .takes_value(true)
+                                .long("extract")
+                                .value_name("DIR"),
+                        )
+                        .arg(
+                            Arg::new("layout")
+                                .about(concat!(
+                                    "Path template of the extracted files, with {TagName} placeholders replaced ",
+                                    "with the values of the instance, series, study and patient DICOM tags. ",
+                                    "Example: {PatientID}/{StudyDate}_{StudyDescription}/{SeriesNumber}_{Modality}/{InstanceNumber}.dcm",
+                                ))
+                                .takes_value(true)
+                                .requires("extract")
+                                .long("layout")
+                                .value_name("TEMPLATE"),
+                        )
+                        .arg(
+                            Arg::new("transcode")
+                                .about(concat!(
+                                    "Transcode the instances to this transfer syntax. A UID, or one of: ",
+                                    "implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, ",
+                                    "jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle",
+                                ))
+                                .takes_value(true)
+                                .long("transcode")
+                                .value_name("SYNTAX"),
+                        )
+                        .arg(
+                            Arg::new("skip_existing")
+                                .about(concat!(
+                                    "Don't download again what is already there: keep the output file if it is a complete ",
+                                    "archive, or the files already written with --layout",
+                                ))
+                                .long("skip-existing"),
                         ),
                 )
                 .subcommand(
-                    App::new("delete")
+                    App::new("tree")
                         .display_order(7)
+                        .about("Show the tree of instances of a series")
+                        .arg(
+                            Arg::new("id")
+                                .about("Series ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("depth")
+                                .about("Lowest level to display")
+                                .takes_value(true)
+                                .possible_values(&["instance"])
+                                .long("depth")
+                                .value_name("LEVEL"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .about("Output format")
+                                .takes_value(true)
+                                .possible_values(&["text", "json"])
+                                .default_value("text")
+                                .long("output")
+                                .value_name("FORMAT"),
+                        ),
+                )
+                .subcommand(
+                    App::new("stats")
+                        .display_order(8)
+                        .about("Show the number of instances and the size of a series")
+                        .arg(
+                            Arg::new("id")
+                                .about("Series ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("metadata")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(9)
+                        .about("Manage the metadata of a series")
+                        .subcommand(
+                            App::new("list")
+                                .display_order(0)
+                                .about("List the metadata of a series")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Series ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("no_header")
+                                        .about("Don't display table header")
+                                        .short('n')
+                                        .long("no-header"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("get")
+                                .display_order(1)
+                                .about("Print the value of a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Series ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("set")
+                                .display_order(2)
+                                .about("Set the value of a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Series ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                )
+                                .arg(
+                                    Arg::new("value")
+                                        .about("Metadata value")
+                                        .required(true)
+                                        .value_name("VALUE"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("delete")
+                                .display_order(3)
+                                .about("Delete a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Series ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("label")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(10)
+                        .about("Manage the labels of a series")
+                        .subcommand(
+                            App::new("list")
+                                .display_order(0)
+                                .about("List the labels of a series")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Series ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("add")
+                                .display_order(1)
+                                .about("Add a label to series")
+                                .arg(
+                                    Arg::new("label")
+                                        .about("Label")
+                                        .required(true)
+                                        .value_name("LABEL"),
+                                )
+                                .arg(
+                                    Arg::new("ids")
+                                        .about("Series IDs. Read from stdin if omitted or -")
+                                        .multiple_values(true)
+                                        .value_name("ID"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("remove")
+                                .display_order(2)
+                                .about("Remove a label from series")
+                                .arg(
+                                    Arg::new("label")
+                                        .about("Label")
+                                        .required(true)
+                                        .value_name("LABEL"),
+                                )
+                                .arg(
+                                    Arg::new("ids")
+                                        .about("Series IDs. Read from stdin if omitted or -")
+                                        .multiple_values(true)
+                                        .value_name("ID"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("reconstruct")
+                        .display_order(11)
+                        .about("Reconstruct the main DICOM tags of a series from its DICOM files")
+                        .arg(
+                            Arg::new("id")
+                                .about("Series ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("delete")
+                        .display_order(12)
                         .about("Delete series")
                         .arg(
                             Arg::new("id")
@@ -827,7 +1936,8 @@ pub fn build_cli() -> App<'static> {
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID InstanceCreationDate",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID InstanceCreationDate meta:RemoteAET",
                                     )
                                 )
                                 .takes_value(true)
@@ -847,6 +1957,18 @@ pub fn build_cli() -> App<'static> {
                                 .about("Instance ID")
                                 .required(true)
                                 .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("metadata")
+                                .about("Also show the metadata of the instance")
+                                .short('m')
+                                .long("metadata"),
+                        )
+                        .arg(
+                            Arg::new("labels")
+                                .about("Also show the labels of the instance")
+                                .short('l')
+                                .long("labels"),
                         ),
                 )
                 .subcommand(
@@ -867,10 +1989,12 @@ pub fn build_cli() -> App<'static> {
                         .arg(
                             Arg::new("query")
                                 .about(concat!(
-                                    "Search query terms. Space-separted pairs TagName=TagValue. ",
-                                    "Wildcards are allowed. Example: InstanceNumber=42 InstanceCreationTime=174242",
+                                    "Search query terms. Space-separated TagName=TagValue, TagName>=TagValue, ",
+                                    "TagName<=TagValue or 'TagName in (TagValue1,TagValue2)' terms. ",
+                                    "Wildcards and date ranges are allowed, dates can be relative (e.g. -7d). ",
+                                    "Tags can be prefixed with a parent level (e.g. Study.StudyDate). Example: InstanceNumber=42 InstanceCreationTime=174242",
                                 ))
-                                .required(true)
+                                .required_unless_present_any(&["label", "label_any", "label_none"])
                                 .takes_value(true)
                                 .short('q')
                                 .long("query")
@@ -878,6 +2002,73 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_values(true)
                                 .value_name("QUERY"),
                         )
+                        .arg(
+                            Arg::new("ignore_case")
+                                .about("Case-insensitive matching of query terms")
+                                .short('i')
+                                .long("ignore-case"),
+                        )
+                        .arg(
+                            Arg::new("sort")
+                                .about(concat!(
+                                    "Sort by the columns specified. Space-separated values. ",
+                                    "Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime",
+                                ))
+                                .takes_value(true)
+                                .long("sort")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .allow_hyphen_values(true)
+                                .value_name("COLUMNS"),
+                        )
+                        .arg(
+                            Arg::new("limit")
+                                .about("Return at most this number of results")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<u64>())
+                                .long("limit")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("count")
+                                .about("Only print the number of results")
+                                .conflicts_with_all(&["exists", "limit"])
+                                .long("count"),
+                        )
+                        .arg(
+                            Arg::new("exists")
+                                .about("Print nothing, exit with 0 if anything matches the query, 1 otherwise, 2 on error")
+                                .long("exists"),
+                        )
+                        .arg(
+                            Arg::new("label")
+                                .about("Only return the resources that have all these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .conflicts_with_all(&["label_any", "label_none"])
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("label_any")
+                                .about("Only return the resources that have any of these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label-any")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .conflicts_with("label_none")
+                                .value_name("LABELS"),
+                        )
+                        .arg(
+                            Arg::new("label_none")
+                                .about("Only return the resources that have none of these labels. Space-separated values")
+                                .takes_value(true)
+                                .long("label-none")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("LABELS"),
+                        )
                         .arg(
                             Arg::new("no_header")
                                 .about("Don't display table header")
@@ -889,7 +2080,8 @@ pub fn build_cli() -> App<'static> {
                                 .about(
                                     concat!(
                                         "Display only the columns specified. Space-separated values. ",
-                                        "Example: ID InstanceCreationDate",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID InstanceCreationDate meta:RemoteAET",
                                     )
                                 )
                                 .takes_value(true)
@@ -914,7 +2106,7 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("replace")
                                 .about(concat!(
                                     "DICOM tags that should be replaced with the values specified. ",
-                                    "Space-separted pairs TagName=TagValue. ",
+                                    "Space-separated pairs TagName=TagValue. ",
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
@@ -979,7 +2171,7 @@ pub fn build_cli() -> App<'static> {
                             Arg::new("replace")
                                 .about(concat!(
                                     "DICOM tags that should be replaced with the values specified. ",
-                                    "Space-separted pairs TagName=TagValue. ",
+                                    "Space-separated pairs TagName=TagValue. ",
                                     "Example: PatientName=REMOVED AccessionNumber=42",
                                 ))
                                 .conflicts_with("config")
@@ -1027,28 +2219,431 @@ pub fn build_cli() -> App<'static> {
                         ),
                 )
                 .subcommand(
-                    App::new("download")
-                        .display_order(6)
-                        .about("Download instance")
+                    App::new("download")
+                        .display_order(6)
+                        .about("Download instance")
+                        .arg(
+                            Arg::new("id")
+                                .about("Instance ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .about("Output file path")
+                                .takes_value(true)
+                                .short('o')
+                                .long("output")
+                                .required(true)
+                                .value_name("OUTPUT"),
+                        )
+                        .arg(
+                            Arg::new("transcode")
+                                .about(concat!(
+                                    "Transcode the instances to this transfer syntax. A UID, or one of: ",
+                                    "implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, ",
+                                    "jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle",
+                                ))
+                                .takes_value(true)
+                                .long("transcode")
+                                .value_name("SYNTAX"),
+                        )
+                        .arg(
+                            Arg::new("skip_existing")
+                                .about("Don't download the instance again if the output file is already there")
+                                .long("skip-existing"),
+                        ),
+                )
+                .subcommand(
+                    App::new("frames")
+                        .display_order(7)
+                        .about("List instance frames")
+                        .arg(
+                            Arg::new("id")
+                                .about("Instance ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("frame")
+                        .display_order(8)
+                        .about("Download instance frame")
+                        .arg(
+                            Arg::new("id")
+                                .about("Instance ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("number")
+                                .about("Frame number (starting from 0)")
+                                .required(true)
+                                .validator(|v| v.parse::<u32>())
+                                .value_name("NUMBER"),
+                        )
+                        .arg(
+                            Arg::new("raw")
+                                .about(concat!(
+                                    "Write the original pixel data of the frame instead of a PNG image, ",
+                                    "along with a JSON sidecar file (<OUTPUT>.json) describing it",
+                                ))
+                                .long("raw"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .about("Output file path")
+                                .takes_value(true)
+                                .short('o')
+                                .long("output")
+                                .required(true)
+                                .value_name("OUTPUT"),
+                        ),
+                )
+                .subcommand(
+                    App::new("extract-pdf")
+                        .display_order(9)
+                        .about("Extract encapsulated PDF document from instance")
+                        .arg(
+                            Arg::new("id")
+                                .about("Instance ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .about("Output file path")
+                                .takes_value(true)
+                                .short('o')
+                                .long("output")
+                                .required(true)
+                                .value_name("OUTPUT"),
+                        ),
+                )
+                .subcommand(
+                    App::new("extract-document")
+                        .display_order(10)
+                        .about("Extract encapsulated document (PDF, CDA, STL, OBJ, MTL) from instance")
+                        .arg(
+                            Arg::new("id")
+                                .about("Instance ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("output")
+                                .about("Output file path")
+                                .takes_value(true)
+                                .short('o')
+                                .long("output")
+                                .required(true)
+                                .value_name("OUTPUT"),
+                        ),
+                )
+                .subcommand(
+                    App::new("create")
+                        .display_order(11)
+                        .about("Create instance from DICOM tags and an image or a PDF document")
+                        .arg(
+                            Arg::new("tags")
+                                .about("YAML file with DICOM tags of the instance (TagName: TagValue)")
+                                .takes_value(true)
+                                .long("tags")
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .about(concat!(
+                                    "DICOM tags of the instance, overriding those in the tags file. ",
+                                    "Space-separated pairs TagName=TagValue. ",
+                                    "Example: PatientName=REMOVED Modality=OT",
+                                ))
+                                .takes_value(true)
+                                .short('t')
+                                .long("tag")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("TAG"),
+                        )
+                        .arg(
+                            Arg::new("image")
+                                .about("PNG or JPEG image to use as pixel data")
+                                .conflicts_with("pdf")
+                                .takes_value(true)
+                                .long("image")
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("pdf")
+                                .about("PDF document to encapsulate")
+                                .takes_value(true)
+                                .long("pdf")
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("parent")
+                                .about("ID of the patient, study or series to attach the instance to")
+                                .takes_value(true)
+                                .long("parent")
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("stats")
+                        .display_order(12)
+                        .about("Show the size of an instance")
+                        .arg(
+                            Arg::new("id")
+                                .about("Instance ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                )
+                .subcommand(
+                    App::new("metadata")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(13)
+                        .about("Manage the metadata of an instance")
+                        .subcommand(
+                            App::new("list")
+                                .display_order(0)
+                                .about("List the metadata of an instance")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("no_header")
+                                        .about("Don't display table header")
+                                        .short('n')
+                                        .long("no-header"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("get")
+                                .display_order(1)
+                                .about("Print the value of a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("set")
+                                .display_order(2)
+                                .about("Set the value of a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                )
+                                .arg(
+                                    Arg::new("value")
+                                        .about("Metadata value")
+                                        .required(true)
+                                        .value_name("VALUE"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("delete")
+                                .display_order(3)
+                                .about("Delete a metadata")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Metadata name")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("attachments")
+                        .display_order(14)
+                        .about("List the attachments of an instance")
+                        .arg(
+                            Arg::new("id")
+                                .about("Instance ID")
+                                .required(true)
+                                .value_name("ID"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("attachment")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(15)
+                        .about("Manage an attachment of an instance")
+                        .subcommand(
+                            App::new("verify")
+                                .display_order(0)
+                                .about("Check the MD5 of an attachment on the server")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Attachment name. Example: dicom")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("compress")
+                                .display_order(1)
+                                .about("Compress an attachment on the server")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Attachment name. Example: dicom")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("uncompress")
+                                .display_order(2)
+                                .about("Uncompress an attachment on the server")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Attachment name. Example: dicom")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("download")
+                                .display_order(3)
+                                .about("Download the (uncompressed) data of an attachment")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                )
+                                .arg(
+                                    Arg::new("name")
+                                        .about("Attachment name. Example: dicom")
+                                        .required(true)
+                                        .value_name("NAME"),
+                                )
+                                .arg(
+                                    Arg::new("output")
+                                        .about("Output file path")
+                                        .takes_value(true)
+                                        .short('o')
+                                        .long("output")
+                                        .required(true)
+                                        .value_name("OUTPUT"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("label")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(16)
+                        .about("Manage the labels of an instance")
+                        .subcommand(
+                            App::new("list")
+                                .display_order(0)
+                                .about("List the labels of an instance")
+                                .arg(
+                                    Arg::new("id")
+                                        .about("Instance ID")
+                                        .required(true)
+                                        .value_name("ID"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("add")
+                                .display_order(1)
+                                .about("Add a label to instances")
+                                .arg(
+                                    Arg::new("label")
+                                        .about("Label")
+                                        .required(true)
+                                        .value_name("LABEL"),
+                                )
+                                .arg(
+                                    Arg::new("ids")
+                                        .about("Instance IDs. Read from stdin if omitted or -")
+                                        .multiple_values(true)
+                                        .value_name("ID"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("remove")
+                                .display_order(2)
+                                .about("Remove a label from instances")
+                                .arg(
+                                    Arg::new("label")
+                                        .about("Label")
+                                        .required(true)
+                                        .value_name("LABEL"),
+                                )
+                                .arg(
+                                    Arg::new("ids")
+                                        .about("Instance IDs. Read from stdin if omitted or -")
+                                        .multiple_values(true)
+                                        .value_name("ID"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("reconstruct")
+                        .display_order(17)
+                        .about("Reconstruct the main DICOM tags of an instance from its DICOM files")
                         .arg(
                             Arg::new("id")
                                 .about("Instance ID")
                                 .required(true)
                                 .value_name("ID"),
-                        )
-                        .arg(
-                            Arg::new("output")
-                                .about("Output file path")
-                                .takes_value(true)
-                                .short('o')
-                                .long("output")
-                                .required(true)
-                                .value_name("OUTPUT"),
                         ),
                 )
                 .subcommand(
                     App::new("delete")
-                        .display_order(7)
+                        .display_order(18)
                         .about("Delete instance")
                         .arg(
                             Arg::new("id")
@@ -1206,6 +2801,17 @@ pub fn build_cli() -> App<'static> {
                                 .multiple_occurrences(true)
                                 .multiple_values(true)
                                 .value_name("IDS"),
+                        )
+                        .arg(
+                            Arg::new("transcode")
+                                .about(concat!(
+                                    "Transcode the instances to this transfer syntax. A UID, or one of: ",
+                                    "implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, ",
+                                    "jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle",
+                                ))
+                                .takes_value(true)
+                                .long("transcode")
+                                .value_name("SYNTAX"),
                         ),
                 )
                 .subcommand(
@@ -1220,6 +2826,517 @@ pub fn build_cli() -> App<'static> {
                         ),
                 ),
         )
+        .subcommand(
+            App::new("changes")
+                .display_order(6)
+                .about("List changes (new, updated, stable or deleted entities) from the server's change log")
+                .arg(
+                    Arg::new("since")
+                        .about("Only list changes after this sequence number")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<u64>())
+                        .long("since")
+                        .value_name("SEQ"),
+                )
+                .arg(
+                    Arg::new("type")
+                        .about("Only list changes of these types. Comma-separated values. Example: NewStudy,StableSeries")
+                        .takes_value(true)
+                        .long("type")
+                        .multiple_occurrences(true)
+                        .use_delimiter(true)
+                        .value_name("TYPES"),
+                )
+                .arg(
+                    Arg::new("follow")
+                        .about(concat!(
+                            "Keep polling for new changes, printing them as they come. ",
+                            "Without --since, resumes after the last change seen (see --state-file), ",
+                            "or starts with the changes to come",
+                        ))
+                        .short('f')
+                        .long("follow"),
+                )
+                .arg(
+                    Arg::new("state_file")
+                        .about(concat!(
+                            "File keeping the sequence number of the last change seen with --follow ",
+                            "[default: changes.seq in the state directory of the server, in the configuration directory]",
+                        ))
+                        .takes_value(true)
+                        .requires("follow")
+                        .long("state-file")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("exec")
+                        .about(concat!(
+                            "Run this shell command for every change, with --follow. ",
+                            "{{id}}, {{type}}, {{level}} and {{seq}} are replaced with the shell-quoted values of the change, ",
+                            "which are also available, along with the main DICOM tags of the entity, ",
+                            "in ORC_CHANGE_* and ORC_TAG_* environment variables. ",
+                            "Example: 'script.sh {{id}} {{type}}'",
+                        ))
+                        .takes_value(true)
+                        .requires("follow")
+                        .conflicts_with("post")
+                        .long("exec")
+                        .value_name("COMMAND"),
+                )
+                .arg(
+                    Arg::new("post")
+                        .about("POST every change as a JSON document to this URL, with --follow")
+                        .takes_value(true)
+                        .requires("follow")
+                        .long("post")
+                        .value_name("URL"),
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .about("Maximum number of changes handled at the same time with --exec or --post")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<usize>())
+                        .default_value("4")
+                        .long("concurrency")
+                        .value_name("N"),
+                )
+                .arg(
+                    Arg::new("retries")
+                        .about("Number of retries of a failed --exec or --post, with increasing delays")
+                        .takes_value(true)
+                        .validator(|v| v.parse::<u32>())
+                        .default_value("3")
+                        .long("retries")
+                        .value_name("N"),
+                )
+                .arg(
+                    Arg::new("dead_letter")
+                        .about(concat!(
+                            "File the changes that could not be handled are appended to, one JSON document per line ",
+                            "[default: changes-dead-letter.jsonl in the state directory of the server, in the configuration directory]",
+                        ))
+                        .takes_value(true)
+                        .long("dead-letter")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("no_header")
+                        .about("Don't display table header")
+                        .short('n')
+                        .long("no-header"),
+                ),
+        )
+        .subcommand(
+            App::new("query")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .display_order(5)
+                .about("Saved queries")
+                .subcommand(
+                    App::new("save")
+                        .display_order(0)
+                        .about("Save a search query under a name, replacing any query with the same name")
+                        .arg(
+                            Arg::new("name")
+                                .about("Query name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("level")
+                                .about("Level of the entities to search for")
+                                .required(true)
+                                .takes_value(true)
+                                .possible_values(&["patient", "study", "series", "instance"])
+                                .short('l')
+                                .long("level")
+                                .value_name("LEVEL"),
+                        )
+                        .arg(
+                            Arg::new("query")
+                                .about(concat!(
+                                    "Search query terms, as in the search commands. Values can contain ",
+                                    "placeholders: {{date:-1d}} for dates relative to the day the query is run, ",
+                                    "{{name}} for parameters passed to query run. ",
+                                    "Example: StudyDate={{date:-1d}} AccessionNumber={{accession}}",
+                                ))
+                                .required(true)
+                                .takes_value(true)
+                                .short('q')
+                                .long("query")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("QUERY"),
+                        )
+                        .arg(
+                            Arg::new("ignore_case")
+                                .about("Case-insensitive matching of query terms")
+                                .short('i')
+                                .long("ignore-case"),
+                        )
+                        .arg(
+                            Arg::new("columns")
+                                .about(
+                                    concat!(
+                                        "Display only the columns specified. Space-separated values. ",
+                                        "Labels and metadata (meta:<Name>) columns are only displayed when requested. ",
+                                        "Example: ID StudyDate meta:RemoteAET",
+                                    )
+                                )
+                                .takes_value(true)
+                                .short('c')
+                                .long("columns")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("COLUMNS"),
+                        )
+                        .arg(
+                            Arg::new("sort")
+                                .about(concat!(
+                                    "Sort by the columns specified. Space-separated values. ",
+                                    "Prefix a column with - to sort in descending order. Example: StudyDate -StudyTime",
+                                ))
+                                .takes_value(true)
+                                .long("sort")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .allow_hyphen_values(true)
+                                .value_name("COLUMNS"),
+                        ),
+                )
+                .subcommand(
+                    App::new("list")
+                        .display_order(1)
+                        .about("List saved queries")
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("run")
+                        .display_order(2)
+                        .about("Run a saved query")
+                        .arg(
+                            Arg::new("name")
+                                .about("Query name")
+                                .required(true)
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::new("param")
+                                .about(concat!(
+                                    "Values of the query placeholders. Space-separated pairs name=value. ",
+                                    "Example: accession=XYZ",
+                                ))
+                                .takes_value(true)
+                                .long("param")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("PARAM"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("delete")
+                        .display_order(3)
+                        .about("Delete a saved query")
+                        .arg(
+                            Arg::new("name")
+                                .about("Query name")
+                                .required(true)
+                                .value_name("NAME"),
+                        ),
+                ),
+        )
+        .subcommand(
+            App::new("lookup")
+                .display_order(4)
+                .about("Find the Orthanc ID of an entity from a DICOM UID, PatientID or AccessionNumber")
+                .arg(
+                    Arg::new("value")
+                        .about(concat!(
+                            "Value to look up. Can be prefixed with uid:, accession: or pid: ",
+                            "to restrict the lookup to that kind of value",
+                        ))
+                        .required(true)
+                        .value_name("VALUE"),
+                ),
+        )
+        .subcommand(
+            App::new("system")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .display_order(7)
+                .about("Server-level commands")
+                .subcommand(
+                    App::new("info")
+                        .display_order(0)
+                        .about("Show server version, DICOM settings, storage backends and plugins"),
+                )
+                .subcommand(
+                    App::new("stats")
+                        .display_order(1)
+                        .about("Show the number of stored entities and the disk usage"),
+                )
+                .subcommand(
+                    App::new("log-level")
+                        .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                        .display_order(2)
+                        .about("Get or set the log level of the server")
+                        .subcommand(
+                            App::new("get")
+                                .display_order(0)
+                                .about(concat!(
+                                    "Show the log level of the server, ",
+                                    "and of each log category if supported",
+                                ))
+                                .arg(
+                                    Arg::new("category")
+                                        .about("Only show the log level of this category")
+                                        .takes_value(true)
+                                        .possible_values(LOG_CATEGORIES)
+                                        .long("category")
+                                        .value_name("CATEGORY"),
+                                ),
+                        )
+                        .subcommand(
+                            App::new("set")
+                                .display_order(1)
+                                .about("Set the log level of the server, or of a log category")
+                                .arg(
+                                    Arg::new("level")
+                                        .about("Log level")
+                                        .required(true)
+                                        .possible_values(&["default", "verbose", "trace"])
+                                        .value_name("LEVEL"),
+                                )
+                                .arg(
+                                    Arg::new("category")
+                                        .about(concat!(
+                                            "Only set the log level of this category ",
+                                            "(requires Orthanc 1.8.1 or later)",
+                                        ))
+                                        .takes_value(true)
+                                        .possible_values(LOG_CATEGORIES)
+                                        .long("category")
+                                        .value_name("CATEGORY"),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    App::new("verify")
+                        .display_order(3)
+                        .about("Check the MD5 of the attachments of all the instances of the server")
+                        .arg(
+                            Arg::new("concurrency")
+                                .about("Number of instances verified at the same time")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<usize>())
+                                .default_value("4")
+                                .long("concurrency")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("reconstruct-all")
+                        .display_order(4)
+                        .about(concat!(
+                            "Reconstruct the main DICOM tags of all the studies. ",
+                            "An interrupted reconstruction resumes where it stopped",
+                        ))
+                        .arg(
+                            Arg::new("query")
+                                .about(concat!(
+                                    "Only reconstruct the studies matching these search query terms ",
+                                    "(see study search). Example: StudyDate=20200101-20201231",
+                                ))
+                                .takes_value(true)
+                                .short('q')
+                                .long("query")
+                                .multiple_occurrences(true)
+                                .multiple_values(true)
+                                .value_name("QUERY"),
+                        )
+                        .arg(
+                            Arg::new("concurrency")
+                                .about("Number of studies reconstructed at the same time")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<usize>())
+                                .default_value("4")
+                                .long("concurrency")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("state_file")
+                                .about(concat!(
+                                    "File keeping the IDs of the studies already reconstructed ",
+                                    "[default: reconstruct.state in the state directory of the server, in the configuration directory]",
+                                ))
+                                .takes_value(true)
+                                .long("state-file")
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("check-main-tags")
+                        .display_order(5)
+                        .about(concat!(
+                            "List the entities missing some of the main DICOM tags the server is ",
+                            "configured with, and the tags they miss. They need to be reconstructed",
+                        ))
+                        .arg(
+                            Arg::new("level")
+                                .about("Entity level")
+                                .takes_value(true)
+                                .possible_values(&["patient", "study", "series", "instance"])
+                                .default_value("study")
+                                .short('l')
+                                .long("level")
+                                .value_name("LEVEL"),
+                        )
+                        .arg(
+                            Arg::new("concurrency")
+                                .about("Number of entities checked at the same time")
+                                .takes_value(true)
+                                .validator(|v| v.parse::<usize>())
+                                .default_value("4")
+                                .long("concurrency")
+                                .value_name("N"),
+                        )
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                ),
+        )
+        .subcommand(
+            App::new("download")
+                .display_order(10)
+                .about("Download several patients, studies or series as a single archive")
+                .arg(
+                    Arg::new("ids")
+                        .about("Patient, study or series IDs. Read from stdin if omitted or -")
+                        .takes_value(true)
+                        .long("ids")
+                        .multiple_values(true)
+                        .value_name("ID"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .about("Output file path")
+                        .takes_value(true)
+                        .short('o')
+                        .long("output")
+                        .required_unless_present("extract")
+                        .conflicts_with("extract")
+                        .value_name("OUTPUT"),
+                )
+                .arg(
+                    Arg::new("media")
+                        .about("Download a media archive with a DICOMDIR, e.g. for burning to a CD")
+                        .long("media"),
+                )
+                .arg(
+                    Arg::new("extract")
+                        .about("Extract the archive to this directory instead of writing it to a file")
+                        .takes_value(true)
+                        .long("extract")
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new("transcode")
+                        .about(concat!(
+                            "Transcode the instances to this transfer syntax. A UID, or one of: ",
+                            "implicit-little, explicit-little, explicit-big, deflated, jpeg-baseline, ",
+                            "jpeg-extended, jpeg-lossless, jpeg-ls-lossless, jpeg-ls, j2k-lossless, j2k, rle",
+                        ))
+                        .takes_value(true)
+                        .long("transcode")
+                        .value_name("SYNTAX"),
+                )
+                .arg(
+                    Arg::new("skip_existing")
+                        .about("Keep the output file if it is already a complete archive")
+                        .long("skip-existing"),
+                ),
+        )
+        .subcommand(
+            App::new("plugin")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .display_order(8)
+                .about("Plugin-level commands")
+                .subcommand(
+                    App::new("list")
+                        .display_order(0)
+                        .about("List plugins loaded by the server")
+                        .arg(
+                            Arg::new("no_header")
+                                .about("Don't display table header")
+                                .short('n')
+                                .long("no-header"),
+                        ),
+                )
+                .subcommand(
+                    App::new("show")
+                        .display_order(1)
+                        .about("Show plugin details, and the orthanc-cli features it enables for well-known plugins")
+                        .arg(
+                            Arg::new("id")
+                                .about("Plugin ID")
+                                .required(true)
+                                .value_name("ID"),
+                        ),
+                ),
+        )
+        .subcommand(
+            App::new("tools")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .display_order(9)
+                .about("Server tools")
+                .subcommand(
+                    App::new("exec-lua")
+                        .display_order(0)
+                        .about("Execute a Lua script on the server and print its output")
+                        .arg(
+                            Arg::new("script")
+                                .about("Lua script file. Use - to read the script from stdin")
+                                .required(true)
+                                .value_name("FILE"),
+                        )
+                        .arg(
+                            Arg::new("var")
+                                .about(concat!(
+                                    "Set a Lua variable before running the script. ",
+                                    "The value is passed as a string. Example: -v name=value",
+                                ))
+                                .takes_value(true)
+                                .multiple_occurrences(true)
+                                .short('v')
+                                .long("var")
+                                .value_name("NAME=VALUE"),
+                        ),
+                ),
+        )
 }
 
 #[cfg(test)]
@@ -5,6 +5,8 @@ pub const ABSENT_DICOM_TAG_PLACEHOLDER: &str = "undefined";
 pub const PATIENTS_LIST_HEADER: &[&str] =
     &["ID", "PatientID", "PatientName", "Number of Studies"];
 pub const PATIENTS_LIST_DICOM_TAGS: &[&str] = &["PatientID", "PatientName"];
+pub const PATIENTS_LIST_EXTRA_COLUMNS: &[&str] = &["Protected", "Labels"];
+pub const LIST_EXTRA_COLUMNS: &[&str] = &["Labels"];
 pub const PATIENT_DICOM_TAGS: &[&str] =
     &["PatientID", "PatientName", "PatientSex", "PatientBirthDate"];
 
@@ -79,6 +81,162 @@ pub const INSTANCE_DICOM_TAGS: &[&str] = &[
     "InstanceCreationDate",
     "InstanceCreationTime",
 ];
+pub const FRAME_DICOM_TAGS: &[&str] = &["Rows", "Columns"];
+pub const FRAME_SIDECAR_DICOM_TAGS: &[&str] = &[
+    "Rows",
+    "Columns",
+    "BitsAllocated",
+    "PhotometricInterpretation",
+    "RescaleSlope",
+    "RescaleIntercept",
+];
+
+pub const ENCAPSULATED_DOCUMENT_TAG: &str = "0042-0011";
+pub const ENCAPSULATED_PDF: &str = "PDF";
+// SOP Class UID -> document type
+pub const ENCAPSULATED_DOCUMENT_SOP_CLASSES: &[(&str, &str)] = &[
+    ("1.2.840.10008.5.1.4.1.1.104.1", ENCAPSULATED_PDF),
+    ("1.2.840.10008.5.1.4.1.1.104.2", "CDA"),
+    ("1.2.840.10008.5.1.4.1.1.104.3", "STL"),
+    ("1.2.840.10008.5.1.4.1.1.104.4", "OBJ"),
+    ("1.2.840.10008.5.1.4.1.1.104.5", "MTL"),
+];
 
 pub const MODALITIES_LIST_HEADER: &[&str] =
     &["Name", "AET", "Host", "Port", "Manufacturer"];
+
+pub const ID_PREFIX_DICOM_UID: &str = "uid:";
+pub const ID_PREFIX_ACCESSION_NUMBER: &str = "accession:";
+pub const ID_PREFIX_PATIENT_ID: &str = "pid:";
+pub const PATIENT_ID_TAG: &str = "PatientID";
+pub const ACCESSION_NUMBER_TAG: &str = "AccessionNumber";
+
+pub const ENTITY_LEVELS: &[&str] = &["Patient", "Study", "Series", "Instance"];
+// Entity level -> main DICOM tags displayed in the tree view
+pub const TREE_DICOM_TAGS: &[(&str, &[&str])] = &[
+    ("Patient", &["PatientID", "PatientName"]),
+    ("Study", &["StudyDate", "StudyDescription"]),
+    ("Series", &["SeriesNumber", "Modality", "SeriesDescription"]),
+    ("Instance", &["InstanceNumber"]),
+];
+pub const TREE_CHILDREN_KEYS: &[&str] = &["Studies", "Series", "Instances"];
+pub const TREE_SIZE_KEY: &str = "FileSize";
+
+pub const DICOM_DATE_FORMAT: &str = "%Y%m%d";
+pub const SEARCH_TERM_FORMAT: &str = concat!(
+    "Must be of format 'TagName=TagValue', 'TagName>=TagValue', 'TagName<=TagValue' ",
+    "or 'TagName in (TagValue1,TagValue2)'"
+);
+
+pub const CONFIG_DIR_NAME: &str = "orthanc-cli";
+pub const SAVED_QUERIES_FILE: &str = "queries.yml";
+// Subdirectory of the configuration directory holding the state files of each server
+pub const SERVERS_STATE_DIR: &str = "servers";
+pub const SAVED_QUERIES_LIST_HEADER: &[&str] =
+    &["Name", "Level", "Query", "Columns", "Sort"];
+
+pub const CHANGES_LIST_HEADER: &[&str] = &["Seq", "Type", "Level", "ID", "Date"];
+pub const CHANGES_STATE_FILE: &str = "changes.seq";
+// Polling interval of the changes feed, doubled every time there is nothing new
+pub const CHANGES_POLL_MIN_INTERVAL: u64 = 1;
+pub const CHANGES_POLL_MAX_INTERVAL: u64 = 30;
+pub const CHANGES_DEAD_LETTER_FILE: &str = "changes-dead-letter.jsonl";
+// Delay before retrying a failed change delivery, doubled on every retry
+pub const CHANGE_DELIVERY_RETRY_INTERVAL: u64 = 1;
+
+// Seconds
+pub const JOB_POLL_INTERVAL: u64 = 1;
+pub const JOB_TIMEOUT: u64 = 3600;
+
+pub const BUILTIN_DATABASE_BACKEND: &str = "SQLite (built-in)";
+pub const BUILTIN_STORAGE_AREA: &str = "Filesystem (built-in)";
+
+pub const PLUGINS_LIST_HEADER: &[&str] = &["ID", "Version", "Explorer", "Description"];
+// orthanc-cli features that the well-known plugins enable, as shown by `plugin show`
+pub const KNOWN_PLUGIN_FEATURES: &[(&str, &str)] = &[
+    (
+        "dicom-web",
+        "None, DICOMweb is only served to DICOMweb clients under /dicom-web",
+    ),
+    (
+        "gdcm",
+        "--transcode to JPEG 2000 and JPEG-LS, and instance frame on such instances",
+    ),
+    (
+        "postgresql-index",
+        "search, --count, changes and system check-main-tags stay fast on large databases",
+    ),
+    (
+        "postgresql-storage",
+        "instance verify and system verify check the files stored in PostgreSQL",
+    ),
+    (
+        "transfers",
+        "None, accelerated transfers between Orthanc peers aren't exposed by orthanc-cli",
+    ),
+    (
+        "worklists",
+        "None, worklists are only answered to C-FIND requests from the modalities",
+    ),
+];
+
+pub const LOG_CATEGORIES: &[&str] = &[
+    "generic", "plugins", "http", "dicom", "sqlite", "lua", "jobs",
+];
+pub const LOG_CATEGORIES_MIN_VERSION: &str = "1.8.1";
+
+pub const LABELS_MIN_VERSION: &str = "1.12.0";
+
+// Exit code of `search --exists` when the search failed, as 1 means nothing was found
+pub const SEARCH_ERROR_EXIT_CODE: i32 = 2;
+
+pub const METADATA_COLUMN_PREFIX: &str = "meta:";
+pub const METADATA_LIST_HEADER: &[&str] = &["Name", "Value"];
+
+pub const ATTACHMENTS_LIST_HEADER: &[&str] = &[
+    "Name",
+    "Uncompressed size",
+    "Compressed size",
+    "MD5",
+    "UUID",
+];
+pub const VERIFICATION_REPORT_HEADER: &[&str] = &["Instance ID", "Attachment", "Error"];
+
+pub const DOWNLOAD_ARCHIVE_FILE: &str = ".orthanc-download.zip";
+pub const DOWNLOAD_PARTIAL_SUFFIX: &str = ".part";
+pub const DOWNLOAD_MANIFEST_FILE: &str = "manifest.csv";
+pub const DOWNLOAD_MANIFEST_TAGS: &[&str] = &[
+    "PatientID",
+    "PatientName",
+    "StudyInstanceUID",
+    "AccessionNumber",
+    "StudyDate",
+    "StudyDescription",
+];
+pub const DOWNLOAD_REPORT_HEADER: &[&str] = &["Study ID", "Error"];
+
+// Milliseconds
+pub const DOWNLOAD_PROGRESS_INTERVAL: u64 = 200;
+
+pub const TRANSCODING_MIN_VERSION: &str = "1.7.0";
+pub const TRANSFER_SYNTAX_UID_ROOT: &str = "1.2.840.10008.1.2";
+pub const TRANSFER_SYNTAX_ALIASES: &[(&str, &str)] = &[
+    ("implicit-little", "1.2.840.10008.1.2"),
+    ("explicit-little", "1.2.840.10008.1.2.1"),
+    ("explicit-big", "1.2.840.10008.1.2.2"),
+    ("deflated", "1.2.840.10008.1.2.1.99"),
+    ("jpeg-baseline", "1.2.840.10008.1.2.4.50"),
+    ("jpeg-extended", "1.2.840.10008.1.2.4.51"),
+    ("jpeg-lossless", "1.2.840.10008.1.2.4.70"),
+    ("jpeg-ls-lossless", "1.2.840.10008.1.2.4.80"),
+    ("jpeg-ls", "1.2.840.10008.1.2.4.81"),
+    ("j2k-lossless", "1.2.840.10008.1.2.4.90"),
+    ("j2k", "1.2.840.10008.1.2.4.91"),
+    ("rle", "1.2.840.10008.1.2.5"),
+];
+
+pub const RECONSTRUCTION_STATE_FILE: &str = "reconstruct.state";
+pub const RECONSTRUCTION_REPORT_HEADER: &[&str] = &["Study ID", "Error"];
+pub const MAIN_DICOM_TAGS_MIN_VERSION: &str = "1.11.0";
+pub const MAIN_DICOM_TAGS_SIGNATURE_METADATA: &str = "MainDicomTagsSignature";
+pub const STALE_MAIN_DICOM_TAGS_HEADER: &[&str] = &["ID", "Missing tags"];
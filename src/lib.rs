@@ -1,12 +1,20 @@
+use chrono::Local;
 use comfy_table::Table;
 use constants::*;
 use orthanc::client::Client;
 use orthanc::entity::*;
 use orthanc::error::Error;
-use orthanc::models::Modality;
+use orthanc::models::{
+    Change, FindRequest, Job, Merge, Modality, ModificationResult, Split,
+};
 use serde_json::Value;
 use serde_yaml;
-use std::{fs, io, result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{fs, io, result, thread};
 use utils::*;
 
 pub mod cli;
@@ -18,6 +26,7 @@ pub type Result<T> = result::Result<T, CliError>;
 #[derive(Debug)]
 pub struct Orthanc {
     pub client: Client,
+    server_address: String,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -60,6 +69,18 @@ impl From<serde_yaml::Error> for CliError {
     }
 }
 
+impl From<serde_json::Error> for CliError {
+    fn from(e: serde_json::Error) -> Self {
+        CliError::new(&e.to_string(), None, None)
+    }
+}
+
+impl From<zip::result::ZipError> for CliError {
+    fn from(e: zip::result::ZipError) -> Self {
+        CliError::new(&e.to_string(), None, None)
+    }
+}
+
 impl From<io::Error> for CliError {
     fn from(e: io::Error) -> Self {
         CliError::new(&e.to_string(), None, None)
@@ -74,7 +95,7 @@ impl Orthanc {
         iap_client_id: Option<String>,
         google_application_credentials: Option<String>,
     ) -> Result<Orthanc> {
-        let mut client = Client::new(server_address);
+        let mut client = Client::new(server_address.clone());
         client = match (username, password) {
             (Some(u), Some(p)) => client.auth(u, p),
             _ => client,
@@ -83,7 +104,10 @@ impl Orthanc {
             (Some(id), Some(sa)) => client.google_oidc(id, sa),
             _ => client,
         };
-        Ok(Orthanc { client })
+        Ok(Orthanc {
+            client,
+            server_address,
+        })
     }
 
     ////////// PATIENT //////////
@@ -95,21 +119,58 @@ impl Orthanc {
     ) -> Result<Table> {
         let header = &mut PATIENTS_LIST_HEADER.to_vec();
         let dicom_tags = &mut PATIENTS_LIST_DICOM_TAGS.to_vec();
-        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+        get_header_and_dicom_tags(
+            header,
+            dicom_tags,
+            PATIENTS_LIST_EXTRA_COLUMNS,
+            columns,
+        )?;
 
+        let patients = self.client.patients_expanded()?;
+        let extra_values = self.get_extra_values(&patients, header)?;
         Ok(utils::create_list_table(
-            self.client.patients_expanded()?,
+            patients,
             header,
             dicom_tags,
+            &extra_values,
             no_header,
         ))
     }
 
-    pub fn show_patient(&self, patient_id: &str) -> Result<Table> {
-        Ok(create_show_table(
-            self.client.patient(patient_id)?,
-            &PATIENT_DICOM_TAGS,
-        ))
+    pub fn show_patient(
+        &self,
+        patient_id: &str,
+        metadata: bool,
+        labels: bool,
+    ) -> Result<Table> {
+        let patient_id = &self.resolve_id(patient_id, Some(EntityKind::Patient))?;
+        let mut table =
+            create_show_table(self.client.patient(patient_id)?, &PATIENT_DICOM_TAGS);
+        let protected = self.client.patient_protected(patient_id)?;
+        table.add_row(["Protected", &format!("{}", protected)].iter());
+        if metadata {
+            self.add_metadata_rows(&mut table, EntityKind::Patient, patient_id)?;
+        }
+        if labels {
+            self.add_labels_row(&mut table, EntityKind::Patient, patient_id)?;
+        }
+        Ok(table)
+    }
+
+    /// Protects the patients from (or exposes them to) recycling when the storage is full
+    pub fn protect_patients(&self, ids: Vec<String>, protect: bool) -> Result<()> {
+        let ids = ids
+            .iter()
+            .map(|i| self.resolve_id(i, Some(EntityKind::Patient)))
+            .collect::<Result<Vec<String>>>()?;
+        for id in ids {
+            if protect {
+                self.client.protect_patient(&id)?;
+            } else {
+                self.client.unprotect_patient(&id)?;
+            }
+        }
+        Ok(())
     }
 
     pub fn anonymize_patient(
@@ -120,6 +181,7 @@ impl Orthanc {
         keep_private_tags: Option<bool>,
         config_file: Option<&str>,
     ) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Patient))?;
         match self.client.anonymize_patient(
             id,
             get_anonymization_config(replace, keep, keep_private_tags, config_file)?,
@@ -136,6 +198,7 @@ impl Orthanc {
         remove: Option<Vec<&str>>,
         config_file: Option<&str>,
     ) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Patient))?;
         match self
             .client
             .modify_patient(id, get_modification_config(replace, remove, config_file)?)
@@ -145,14 +208,51 @@ impl Orthanc {
         }
     }
 
-    pub fn download_patient(&self, id: &str, output_file: &str) -> Result<()> {
-        let mut file = fs::File::create(output_file)?;
-        self.client
-            .patient_dicom(id, &mut file)
-            .map_err(Into::<_>::into)
+    pub fn download_patient(
+        &self,
+        id: &str,
+        output_file: Option<&str>,
+        options: DownloadOptions,
+    ) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Patient))?;
+        self.download(EntityKind::Patient, id, output_file, options)
+    }
+
+    pub fn show_patient_tree(
+        &self,
+        id: &str,
+        depth: Option<&str>,
+        json: bool,
+    ) -> Result<String> {
+        let id = &self.resolve_id(id, Some(EntityKind::Patient))?;
+        render_tree(self.patient_tree(id, depth)?, json)
+    }
+
+    fn patient_tree(&self, id: &str, depth: Option<&str>) -> Result<Value> {
+        let patient = self.client.patient(id)?;
+        if is_tree_leaf(EntityKind::Patient, depth) {
+            return Ok(create_tree_leaf(
+                &patient,
+                self.client.patient_statistics(id)?,
+            ));
+        }
+        let studies = patient
+            .studies
+            .iter()
+            .map(|s| self.study_tree(s, depth))
+            .collect::<Result<Vec<Value>>>()?;
+        Ok(create_tree_node(&patient, studies))
+    }
+
+    pub fn patient_stats(&self, id: &str) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Patient))?;
+        Ok(create_entity_stats_table(
+            self.client.patient_statistics(id)?,
+        ))
     }
 
     pub fn delete_patient(&self, id: &str) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Patient))?;
         match self.client.delete_patient(id) {
             Ok(_) => Ok(()),
             Err(e) => Err(e.into()),
@@ -169,28 +269,46 @@ impl Orthanc {
     ) -> Result<Table> {
         let header = &mut STUDIES_LIST_HEADER.to_vec();
         let dicom_tags = &mut STUDIES_LIST_DICOM_TAGS.to_vec();
-        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+        get_header_and_dicom_tags(header, dicom_tags, LIST_EXTRA_COLUMNS, columns)?;
+        let patient_id = patient_id
+            .map(|i| self.resolve_id(i, Some(EntityKind::Patient)))
+            .transpose()?;
 
-        if let Some(pid) = patient_id {
+        if let Some(pid) = patient_id.as_deref() {
             self.client.patient(pid)?; // Check if the patient exists
         }
 
         let mut studies = self.client.studies_expanded()?;
 
-        if let Some(pid) = patient_id {
+        if let Some(pid) = patient_id.as_deref() {
             studies.retain(|s| s.parent_id().unwrap() == pid);
         };
 
+        let extra_values = self.get_extra_values(&studies, header)?;
         Ok(utils::create_list_table(
-            studies, header, dicom_tags, no_header,
+            studies,
+            header,
+            dicom_tags,
+            &extra_values,
+            no_header,
         ))
     }
 
-    pub fn show_study(&self, study_id: &str) -> Result<Table> {
-        Ok(create_show_table(
-            self.client.study(study_id)?,
-            &STUDY_DICOM_TAGS,
-        ))
+    pub fn show_study(
+        &self,
+        study_id: &str,
+        metadata: bool,
+        labels: bool,
+    ) -> Result<Table> {
+        let study_id = &self.resolve_id(study_id, Some(EntityKind::Study))?;
+        let mut table = create_show_table(self.client.study(study_id)?, &STUDY_DICOM_TAGS);
+        if metadata {
+            self.add_metadata_rows(&mut table, EntityKind::Study, study_id)?;
+        }
+        if labels {
+            self.add_labels_row(&mut table, EntityKind::Study, study_id)?;
+        }
+        Ok(table)
     }
 
     pub fn anonymize_study(
@@ -201,6 +319,7 @@ impl Orthanc {
         keep_private_tags: Option<bool>,
         config_file: Option<&str>,
     ) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Study))?;
         match self.client.anonymize_study(
             id,
             get_anonymization_config(replace, keep, keep_private_tags, config_file)?,
@@ -217,6 +336,7 @@ impl Orthanc {
         remove: Option<Vec<&str>>,
         config_file: Option<&str>,
     ) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Study))?;
         match self
             .client
             .modify_study(id, get_modification_config(replace, remove, config_file)?)
@@ -226,14 +346,164 @@ impl Orthanc {
         }
     }
 
-    pub fn download_study(&self, id: &str, output_file: &str) -> Result<()> {
-        let mut file = fs::File::create(output_file)?;
-        self.client
-            .study_dicom(id, &mut file)
-            .map_err(Into::<_>::into)
+    /// Merges studies or series into the target study
+    pub fn merge_study(
+        &self,
+        target_id: &str,
+        source_ids: Vec<&str>,
+        keep_source: bool,
+    ) -> Result<Table> {
+        let target_id = &self.resolve_id(target_id, Some(EntityKind::Study))?;
+        let mut resources = vec![];
+        for id in source_ids {
+            resources.push(self.resolve_id(id, None)?);
+        }
+        let job = self.client.merge_study(
+            target_id,
+            Merge {
+                resources,
+                keep_source: Some(keep_source),
+            },
+        )?;
+        self.get_target_study_table(self.wait_for_job(&job.id)?)
+    }
+
+    /// Moves series out of a study into a new study
+    pub fn split_study(
+        &self,
+        id: &str,
+        series_ids: Vec<&str>,
+        replace: Option<Vec<&str>>,
+        keep_source: bool,
+    ) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Study))?;
+        let mut series = vec![];
+        for series_id in series_ids {
+            series.push(self.resolve_id(series_id, Some(EntityKind::Series))?);
+        }
+        let job = self.client.split_study(
+            id,
+            Split {
+                series,
+                replace: replace.map(parse_tag_kv_pairs).transpose()?,
+                keep_source: Some(keep_source),
+            },
+        )?;
+        self.get_target_study_table(self.wait_for_job(&job.id)?)
+    }
+
+    /// Waits for a job to complete, showing its progress on stderr. Fails if the job fails, is
+    /// paused, or doesn't complete within `JOB_TIMEOUT` seconds (it then keeps running on the
+    /// server).
+    fn wait_for_job(&self, id: &str) -> Result<Job> {
+        let show_progress = atty::is(atty::Stream::Stderr);
+        let start = Instant::now();
+        let mut shown = false;
+        let result = loop {
+            let job = self.client.job(id)?;
+            match job.state.as_str() {
+                "Success" => break Ok(job),
+                "Failure" => {
+                    break Err(CliError::new(
+                        "Command error",
+                        Some(&format!("Job {} failed", id)),
+                        job.error_description.as_deref(),
+                    ))
+                }
+                "Paused" => {
+                    break Err(CliError::new(
+                        "Command error",
+                        Some(&format!("Job {} was paused", id)),
+                        Some("Resume it with the Orthanc REST API to complete it"),
+                    ))
+                }
+                _ if start.elapsed() >= Duration::from_secs(JOB_TIMEOUT) => {
+                    break Err(CliError::new(
+                        "Command error",
+                        Some(&format!("Job {} did not complete in time", id)),
+                        Some(&format!(
+                            "Still {} ({}%) after {} seconds",
+                            job.state, job.progress, JOB_TIMEOUT
+                        )),
+                    ))
+                }
+                _ => {
+                    if show_progress {
+                        eprint!("\rJob {}: {}%    ", id, job.progress);
+                        shown = true;
+                    }
+                    thread::sleep(Duration::from_secs(JOB_POLL_INTERVAL))
+                }
+            }
+        };
+        if shown {
+            eprintln!();
+        }
+        result
+    }
+
+    /// Result table of a merge or split job, like the one of a modification
+    fn get_target_study_table(&self, job: Job) -> Result<Table> {
+        let id = match job.content["TargetStudy"].as_str() {
+            Some(i) => i.to_string(),
+            None => {
+                return Err(CliError::new(
+                    "Command error",
+                    Some(&format!("Job {} returned no target study", job.id)),
+                    None,
+                ))
+            }
+        };
+        let study = self.client.study(&id)?;
+        Ok(create_new_entity_table(ModificationResult {
+            path: format!("/studies/{}", id),
+            id,
+            patient_id: study.parent_patient,
+            entity: EntityKind::Study,
+        }))
+    }
+
+    pub fn download_study(
+        &self,
+        id: &str,
+        output_file: Option<&str>,
+        options: DownloadOptions,
+    ) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Study))?;
+        self.download(EntityKind::Study, id, output_file, options)
+    }
+
+    pub fn show_study_tree(
+        &self,
+        id: &str,
+        depth: Option<&str>,
+        json: bool,
+    ) -> Result<String> {
+        let id = &self.resolve_id(id, Some(EntityKind::Study))?;
+        render_tree(self.study_tree(id, depth)?, json)
+    }
+
+    /// Tree of a study, down to the `depth` level. Children below it aren't fetched.
+    fn study_tree(&self, id: &str, depth: Option<&str>) -> Result<Value> {
+        let study = self.client.study(id)?;
+        if is_tree_leaf(EntityKind::Study, depth) {
+            return Ok(create_tree_leaf(&study, self.client.study_statistics(id)?));
+        }
+        let series = study
+            .series
+            .iter()
+            .map(|s| self.series_tree(s, depth))
+            .collect::<Result<Vec<Value>>>()?;
+        Ok(create_tree_node(&study, series))
+    }
+
+    pub fn study_stats(&self, id: &str) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Study))?;
+        Ok(create_entity_stats_table(self.client.study_statistics(id)?))
     }
 
     pub fn delete_study(&self, id: &str) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Study))?;
         match self.client.delete_study(id) {
             Ok(_) => Ok(()),
             Err(e) => Err(e.into()),
@@ -250,28 +520,47 @@ impl Orthanc {
     ) -> Result<Table> {
         let header = &mut SERIES_LIST_HEADER.to_vec();
         let dicom_tags = &mut SERIES_LIST_DICOM_TAGS.to_vec();
-        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+        get_header_and_dicom_tags(header, dicom_tags, LIST_EXTRA_COLUMNS, columns)?;
+        let study_id = study_id
+            .map(|i| self.resolve_id(i, Some(EntityKind::Study)))
+            .transpose()?;
 
-        if let Some(pid) = study_id {
+        if let Some(pid) = study_id.as_deref() {
             self.client.study(pid)?; // Check if the study exists
         }
 
         let mut series = self.client.series_expanded()?;
 
-        if let Some(sid) = study_id {
+        if let Some(sid) = study_id.as_deref() {
             series.retain(|s| s.parent_id().unwrap() == sid);
         };
 
+        let extra_values = self.get_extra_values(&series, header)?;
         Ok(utils::create_list_table(
-            series, header, dicom_tags, no_header,
+            series,
+            header,
+            dicom_tags,
+            &extra_values,
+            no_header,
         ))
     }
 
-    pub fn show_series(&self, series_id: &str) -> Result<Table> {
-        Ok(create_show_table(
-            self.client.series(series_id)?,
-            &SERIES_DICOM_TAGS,
-        ))
+    pub fn show_series(
+        &self,
+        series_id: &str,
+        metadata: bool,
+        labels: bool,
+    ) -> Result<Table> {
+        let series_id = &self.resolve_id(series_id, Some(EntityKind::Series))?;
+        let mut table =
+            create_show_table(self.client.series(series_id)?, &SERIES_DICOM_TAGS);
+        if metadata {
+            self.add_metadata_rows(&mut table, EntityKind::Series, series_id)?;
+        }
+        if labels {
+            self.add_labels_row(&mut table, EntityKind::Series, series_id)?;
+        }
+        Ok(table)
     }
 
     pub fn anonymize_series(
@@ -282,6 +571,7 @@ impl Orthanc {
         keep_private_tags: Option<bool>,
         config_file: Option<&str>,
     ) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Series))?;
         match self.client.anonymize_series(
             id,
             get_anonymization_config(replace, keep, keep_private_tags, config_file)?,
@@ -298,6 +588,7 @@ impl Orthanc {
         remove: Option<Vec<&str>>,
         config_file: Option<&str>,
     ) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Series))?;
         match self
             .client
             .modify_series(id, get_modification_config(replace, remove, config_file)?)
@@ -307,14 +598,59 @@ impl Orthanc {
         }
     }
 
-    pub fn download_series(&self, id: &str, output_file: &str) -> Result<()> {
-        let mut file = fs::File::create(output_file)?;
-        self.client
-            .series_dicom(id, &mut file)
-            .map_err(Into::<_>::into)
+    pub fn download_series(
+        &self,
+        id: &str,
+        output_file: Option<&str>,
+        options: DownloadOptions,
+    ) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Series))?;
+        self.download(EntityKind::Series, id, output_file, options)
+    }
+
+    pub fn show_series_tree(
+        &self,
+        id: &str,
+        depth: Option<&str>,
+        json: bool,
+    ) -> Result<String> {
+        let id = &self.resolve_id(id, Some(EntityKind::Series))?;
+        render_tree(self.series_tree(id, depth)?, json)
+    }
+
+    fn series_tree(&self, id: &str, depth: Option<&str>) -> Result<Value> {
+        let series = self.client.series(id)?;
+        if is_tree_leaf(EntityKind::Series, depth) {
+            return Ok(create_tree_leaf(
+                &series,
+                self.client.series_statistics(id)?,
+            ));
+        }
+        let mut instances = self.client.series_instances(id)?;
+        // Keep the order of the series' instances list
+        let order: HashMap<&String, usize> = series
+            .instances
+            .iter()
+            .enumerate()
+            .map(|(n, i)| (i, n))
+            .collect();
+        instances.sort_by_key(|i| order.get(&i.id).copied());
+        let instances = instances
+            .iter()
+            .map(|i| create_tree_node(i, vec![]))
+            .collect();
+        Ok(create_tree_node(&series, instances))
+    }
+
+    pub fn series_stats(&self, id: &str) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Series))?;
+        Ok(create_entity_stats_table(
+            self.client.series_statistics(id)?,
+        ))
     }
 
     pub fn delete_series(&self, id: &str) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Series))?;
         match self.client.delete_series(id) {
             Ok(_) => Ok(()),
             Err(e) => Err(e.into()),
@@ -331,27 +667,73 @@ impl Orthanc {
     ) -> Result<Table> {
         let header = &mut INSTANCES_LIST_HEADER.to_vec();
         let dicom_tags = &mut INSTANCES_LIST_DICOM_TAGS.to_vec();
-        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+        get_header_and_dicom_tags(header, dicom_tags, LIST_EXTRA_COLUMNS, columns)?;
+        let series_id = series_id
+            .map(|i| self.resolve_id(i, Some(EntityKind::Series)))
+            .transpose()?;
 
-        if let Some(pid) = series_id {
+        if let Some(pid) = series_id.as_deref() {
             self.client.series(pid)?; // Check if the series exists
         }
 
         let mut instances = self.client.instances_expanded()?;
 
-        if let Some(sid) = series_id {
+        if let Some(sid) = series_id.as_deref() {
             instances.retain(|s| s.parent_id().unwrap() == sid);
         };
 
+        let extra_values = self.get_extra_values(&instances, header)?;
         Ok(utils::create_list_table(
-            instances, header, dicom_tags, no_header,
+            instances,
+            header,
+            dicom_tags,
+            &extra_values,
+            no_header,
         ))
     }
 
-    pub fn show_instance(&self, instance_id: &str) -> Result<Table> {
-        Ok(create_show_table(
-            self.client.instance(instance_id)?,
-            &INSTANCE_DICOM_TAGS,
+    pub fn show_instance(
+        &self,
+        instance_id: &str,
+        metadata: bool,
+        labels: bool,
+    ) -> Result<Table> {
+        let instance_id = &self.resolve_id(instance_id, Some(EntityKind::Instance))?;
+        let mut table =
+            create_show_table(self.client.instance(instance_id)?, &INSTANCE_DICOM_TAGS);
+        if metadata {
+            self.add_metadata_rows(&mut table, EntityKind::Instance, instance_id)?;
+        }
+        if labels {
+            self.add_labels_row(&mut table, EntityKind::Instance, instance_id)?;
+        }
+        Ok(table)
+    }
+
+    pub fn create_instance(
+        &self,
+        tags: Option<Vec<&str>>,
+        tags_file: Option<&str>,
+        image: Option<&str>,
+        pdf: Option<&str>,
+        parent: Option<&str>,
+    ) -> Result<Table> {
+        let parent = parent.map(|p| self.resolve_id(p, None)).transpose()?;
+        let result = self.client.create_instance(get_instance_creation(
+            tags,
+            tags_file,
+            image,
+            pdf,
+            parent.as_deref(),
+        )?)?;
+        let instance = self.client.instance(&result.id)?;
+        let series = self.client.series(&instance.parent_series)?;
+        let study = self.client.study(&series.parent_study)?;
+        Ok(create_new_instance_table(
+            &instance.id,
+            &series.id,
+            &study.id,
+            &study.parent_patient,
         ))
     }
 
@@ -364,14 +746,14 @@ impl Orthanc {
         config_file: Option<&str>,
         path: &str,
     ) -> Result<()> {
-        let mut file = fs::File::create(path)?;
-        self.client
-            .anonymize_instance(
-                id,
-                get_anonymization_config(replace, keep, keep_private_tags, config_file)?,
-                &mut file,
-            )
-            .map_err(Into::<_>::into)
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        let config =
+            get_anonymization_config(replace, keep, keep_private_tags, config_file)?;
+        write_file_atomically(Path::new(path), |file| {
+            self.client
+                .anonymize_instance(id, config, file)
+                .map_err(Into::into)
+        })
     }
 
     pub fn modify_instance(
@@ -382,24 +764,47 @@ impl Orthanc {
         config_file: Option<&str>,
         path: &str,
     ) -> Result<()> {
-        let mut file = fs::File::create(path)?;
-        self.client
-            .modify_instance(
-                id,
-                get_modification_config(replace, remove, config_file)?,
-                &mut file,
-            )
-            .map_err(Into::<_>::into)
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        let config = get_modification_config(replace, remove, config_file)?;
+        write_file_atomically(Path::new(path), |file| {
+            self.client
+                .modify_instance(id, config, file)
+                .map_err(Into::into)
+        })
     }
 
-    pub fn download_instance(&self, id: &str, output_file: &str) -> Result<()> {
-        let mut file = fs::File::create(output_file)?;
-        self.client
-            .instance_dicom(id, &mut file)
-            .map_err(Into::<_>::into)
+    pub fn download_instance(
+        &self,
+        id: &str,
+        output_file: &str,
+        transcode: Option<&str>,
+        skip_existing: bool,
+    ) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        let transcode = self.check_transcode(transcode)?;
+        let path = Path::new(output_file);
+        // Files are only put in place once complete, so an existing one needs no download
+        if skip_existing && path.exists() {
+            return Ok(());
+        }
+        write_file_atomically(path, |file| {
+            download_to(file, |writer| {
+                self.client
+                    .instance_dicom(id, transcode.as_deref(), writer)
+                    .map_err(Into::into)
+            })
+        })
+    }
+
+    pub fn instance_stats(&self, id: &str) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        Ok(create_entity_stats_table(
+            self.client.instance_statistics(id)?,
+        ))
     }
 
     pub fn delete_instance(&self, id: &str) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
         match self.client.delete_instance(id) {
             Ok(_) => Ok(()),
             Err(e) => Err(e.into()),
@@ -407,6 +812,7 @@ impl Orthanc {
     }
 
     pub fn show_instance_tags(&self, id: &str) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
         match self.client.instance_tags_expanded(id) {
             Ok(tags) => {
                 let mut table = create_table(None);
@@ -438,10 +844,597 @@ impl Orthanc {
         }
     }
 
+    pub fn list_instance_frames(&self, id: &str) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        let frames = self.client.instance_frames(id)?;
+        let tags = self.client.instance_tags(id)?;
+        Ok(create_frames_table(&frames, &tags))
+    }
+
+    pub fn download_instance_frame(
+        &self,
+        id: &str,
+        frame: u32,
+        raw: bool,
+        output_file: &str,
+    ) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        let frames = self.client.instance_frames(id)?;
+        if !frames.contains(&frame) {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!(
+                    "Invalid frame number: {}. Number of frames: {}",
+                    frame,
+                    frames.len()
+                )),
+                None,
+            ));
+        }
+
+        if raw {
+            let tags = self.client.instance_tags(id)?;
+            write_file_atomically(Path::new(output_file), |file| {
+                self.client
+                    .instance_frame_raw(id, frame, file)
+                    .map_err(Into::into)
+            })?;
+            write_file_atomically(Path::new(&format!("{}.json", output_file)), |file| {
+                serde_json::to_writer_pretty(file, &create_frame_sidecar(frame, &tags))
+                    .map_err(Into::into)
+            })
+        } else {
+            write_file_atomically(Path::new(output_file), |file| {
+                self.client
+                    .instance_frame_preview(id, frame, file)
+                    .map_err(Into::into)
+            })
+        }
+    }
+
+    pub fn extract_instance_pdf(&self, id: &str, output_file: &str) -> Result<()> {
+        self.extract_instance_document(id, Some(ENCAPSULATED_PDF), output_file)
+    }
+
+    pub fn extract_instance_document(
+        &self,
+        id: &str,
+        document_type: Option<&str>,
+        output_file: &str,
+    ) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        get_encapsulated_document_type(&self.client.instance_tags(id)?, document_type)?;
+        write_file_atomically(Path::new(output_file), |file| {
+            self.client
+                .instance_tag_content(id, ENCAPSULATED_DOCUMENT_TAG, file)
+                .map_err(Into::into)
+        })
+    }
+
+    ////////// ATTACHMENT //////////
+
+    pub fn list_attachments(&self, id: &str, no_header: bool) -> Result<Table> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        let mut attachments = vec![];
+        for name in self.client.instance_attachments(id)? {
+            let info = self.client.instance_attachment_info(id, &name)?;
+            attachments.push((name, info));
+        }
+        Ok(create_attachments_table(&attachments, no_header))
+    }
+
+    pub fn verify_attachment(&self, id: &str, name: &str) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        Ok(self.client.verify_instance_attachment(id, name)?)
+    }
+
+    pub fn compress_attachment(&self, id: &str, name: &str) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        Ok(self.client.compress_instance_attachment(id, name)?)
+    }
+
+    pub fn uncompress_attachment(&self, id: &str, name: &str) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        Ok(self.client.uncompress_instance_attachment(id, name)?)
+    }
+
+    pub fn download_attachment(
+        &self,
+        id: &str,
+        name: &str,
+        output_file: &str,
+    ) -> Result<()> {
+        let id = &self.resolve_id(id, Some(EntityKind::Instance))?;
+        write_file_atomically(Path::new(output_file), |file| {
+            self.client
+                .instance_attachment_data(id, name, file)
+                .map_err(Into::into)
+        })
+    }
+
+    /// Verifies the attachments of all the instances of a study. Returns the table of the
+    /// corrupted attachments, and their number.
+    pub fn verify_study(
+        &self,
+        id: &str,
+        concurrency: usize,
+        no_header: bool,
+    ) -> Result<(Table, usize)> {
+        let id = &self.resolve_id(id, Some(EntityKind::Study))?;
+        let mut instances = vec![];
+        for series_id in self.client.study(id)?.series {
+            instances.extend(self.client.series(&series_id)?.instances);
+        }
+        self.verify_instances(instances, concurrency, no_header)
+    }
+
+    /// Verifies the attachments of all the instances stored on the server
+    pub fn verify_all(
+        &self,
+        concurrency: usize,
+        no_header: bool,
+    ) -> Result<(Table, usize)> {
+        self.verify_instances(self.client.instances()?, concurrency, no_header)
+    }
+
+    fn verify_instances(
+        &self,
+        ids: Vec<String>,
+        concurrency: usize,
+        no_header: bool,
+    ) -> Result<(Table, usize)> {
+        let (receiver, workers) =
+            spawn_workers(&self.client, ids, concurrency, verify_instance_attachments);
+        let mut failures: Vec<(String, String, String)> =
+            receiver.iter().flat_map(|(_, f)| f).collect();
+        join_workers(workers)?;
+        failures.sort();
+        Ok((
+            create_verification_table(&failures, no_header),
+            failures.len(),
+        ))
+    }
+
+    ////////// RECONSTRUCTION //////////
+
+    /// Reconstructs the main DICOM tags of an entity from its DICOM files
+    pub fn reconstruct(&self, level: &str, id: &str) -> Result<()> {
+        let kind = get_entity_kind(level);
+        let id = &self.resolve_id(id, Some(kind.clone()))?;
+        Ok(self.client.reconstruct(&kind, id)?)
+    }
+
+    /// Reconstructs all the studies, or the ones matching the query. The studies reconstructed
+    /// are kept in a state file, so that an interrupted reconstruction resumes where it stopped.
+    pub fn reconstruct_all(
+        &self,
+        query: Option<Vec<&str>>,
+        concurrency: usize,
+        state_file: Option<&str>,
+        no_header: bool,
+    ) -> Result<(Table, usize)> {
+        let state_file = match state_file {
+            Some(f) => PathBuf::from(f),
+            None => get_server_state_file(&self.server_address, RECONSTRUCTION_STATE_FILE)?,
+        };
+        let ids: Vec<String> = match query {
+            Some(q) => self
+                .find::<Study>(q, SearchOptions::default(), None)?
+                .into_iter()
+                .map(|s| s.id)
+                .collect(),
+            None => self.client.studies()?,
+        };
+        let done = read_reconstruction_state(&state_file)?;
+        let total = ids.len();
+        let (ids, skipped): (Vec<String>, Vec<String>) =
+            ids.into_iter().partition(|i| !done.contains(i));
+        let mut count = skipped.len();
+        if count > 0 {
+            eprintln!(
+                "Resuming, {}/{} studies already reconstructed",
+                count, total
+            );
+        }
+
+        let (receiver, workers) =
+            spawn_workers(&self.client, ids, concurrency, |client, id| {
+                client
+                    .reconstruct(&EntityKind::Study, id)
+                    .map_err(get_error_message)
+            });
+        let mut failures = vec![];
+        for (id, result) in receiver.iter() {
+            count += 1;
+            match result {
+                Ok(_) => {
+                    append_reconstruction_state(&state_file, &id)?;
+                    eprintln!("[{}/{}] {}", count, total, id);
+                }
+                Err(e) => {
+                    eprintln!("[{}/{}] {}: {}", count, total, id, e);
+                    failures.push((id, e));
+                }
+            }
+        }
+        join_workers(workers)?;
+        if failures.is_empty() && state_file.exists() {
+            fs::remove_file(&state_file)?;
+        }
+        failures.sort();
+        Ok((
+            create_report_table(RECONSTRUCTION_REPORT_HEADER, &failures, no_header),
+            failures.len(),
+        ))
+    }
+
+    /// Entities of a level missing some of the main DICOM tags the server is configured to
+    /// index, e.g. after a change of `ExtraMainDicomTags`. They need to be reconstructed.
+    pub fn check_main_dicom_tags(
+        &self,
+        level: &str,
+        concurrency: usize,
+        no_header: bool,
+    ) -> Result<(Table, usize)> {
+        let system = self.client.system()?;
+        check_server_version(
+            &system.version,
+            MAIN_DICOM_TAGS_MIN_VERSION,
+            "Main DICOM tags configuration",
+        )?;
+        let kind = get_entity_kind(level);
+        let expected = system
+            .main_dicom_tags
+            .unwrap_or_default()
+            .remove(&format!("{:?}", kind))
+            .unwrap_or_default();
+        let ids = match kind {
+            EntityKind::Patient => self.client.patients()?,
+            EntityKind::Study => self.client.studies()?,
+            EntityKind::Series => self.client.series_list()?,
+            EntityKind::Instance => self.client.instances()?,
+        };
+        let (receiver, workers) =
+            spawn_workers(&self.client, ids, concurrency, move |client, id| {
+                client
+                    .metadata(&kind, id)
+                    .map(|mut m| m.remove(MAIN_DICOM_TAGS_SIGNATURE_METADATA))
+                    .map_err(get_error_message)
+            });
+        let signatures: Vec<(String, std::result::Result<Option<String>, String>)> =
+            receiver.iter().collect();
+        join_workers(workers)?;
+        let mut stale = vec![];
+        for (id, signature) in signatures {
+            let signature = signature.map_err(|e| {
+                CliError::new(
+                    "Command error",
+                    Some(&format!("Could not read the metadata of {}", id)),
+                    Some(&e),
+                )
+            })?;
+            if let Some(missing) =
+                get_missing_main_dicom_tags(signature.as_deref(), &expected)
+            {
+                stale.push((id, missing));
+            }
+        }
+        stale.sort();
+        Ok((
+            create_report_table(STALE_MAIN_DICOM_TAGS_HEADER, &stale, no_header),
+            stale.len(),
+        ))
+    }
+
+    ////////// DOWNLOAD //////////
+
+    /// Downloads an archive of several patients, studies or series
+    pub fn download_resources(
+        &self,
+        ids: Vec<String>,
+        output_file: Option<&str>,
+        options: DownloadOptions,
+    ) -> Result<()> {
+        let transcode = self.check_transcode(options.transcode.as_deref())?;
+        let mut resources = vec![];
+        for id in ids {
+            resources.push(self.resolve_id(&id, None)?);
+        }
+        self.save_archive(output_file, &options, |writer| {
+            if options.media {
+                self.client
+                    .create_media(&resources, transcode.as_deref(), writer)
+            } else {
+                self.client
+                    .create_archive(&resources, transcode.as_deref(), writer)
+            }
+            .map_err(Into::into)
+        })
+    }
+
+    /// Downloads the studies matching a query, each to its own archive or directory in `dir`,
+    /// and lists them in a manifest. Studies the manifest of a previous run lists with the same
+    /// path and size are skipped, as are complete archives it doesn't list.
+    pub fn download_studies(
+        &self,
+        query: Option<Vec<&str>>,
+        dir: &str,
+        concurrency: usize,
+        extract: bool,
+        transcode: Option<&str>,
+        no_header: bool,
+    ) -> Result<(Table, usize)> {
+        let transcode = self.check_transcode(transcode)?;
+        let studies = match query {
+            Some(q) => self.find::<Study>(q, SearchOptions::default(), None)?,
+            None => self.client.studies_expanded()?,
+        };
+        let dir = PathBuf::from(dir);
+        fs::create_dir_all(&dir)?;
+        let manifest_path = dir.join(DOWNLOAD_MANIFEST_FILE);
+        let mut manifest = read_manifest(&manifest_path)?;
+
+        let total = studies.len();
+        let mut rows = HashMap::new();
+        let mut ids = vec![];
+        let mut recovered = false;
+        for study in studies {
+            let path = get_study_download_path(&study.id, extract);
+            let row = get_manifest_row(&study);
+            match manifest.get(&study.id) {
+                Some(r) if is_downloaded(&dir, r, &path) => (),
+                // An archive the manifest doesn't list, e.g. if a run was interrupted before
+                // updating it, is kept if it's complete
+                None if !extract && is_archive_complete(&dir.join(&path)) => {
+                    let mut row = row.clone();
+                    row.push(path.clone());
+                    row.push(get_path_size(&dir.join(&path))?.to_string());
+                    manifest.insert(study.id.clone(), row);
+                    recovered = true;
+                }
+                _ => ids.push(study.id.clone()),
+            }
+            rows.insert(study.id.clone(), row);
+        }
+        if recovered {
+            write_manifest(&manifest_path, &manifest)?;
+        }
+        let mut count = total - ids.len();
+        if count > 0 {
+            eprintln!("Resuming, {}/{} studies already downloaded", count, total);
+        }
+
+        let output_dir = dir.clone();
+        let (receiver, workers) =
+            spawn_workers(&self.client, ids, concurrency, move |client, id| {
+                let path = output_dir.join(get_study_download_path(id, extract));
+                save_study(client, id, &path, extract, transcode.as_deref())
+                    .map_err(|e| e.message.unwrap_or(e.error))
+            });
+        let mut failures = vec![];
+        for (id, result) in receiver.iter() {
+            count += 1;
+            match result {
+                Ok(size) => {
+                    // The manifest is kept up to date, for an interrupted run to be resumed
+                    let mut row = rows.remove(&id).unwrap_or_default();
+                    row.push(get_study_download_path(&id, extract));
+                    row.push(size.to_string());
+                    manifest.insert(id.clone(), row);
+                    write_manifest(&manifest_path, &manifest)?;
+                    eprintln!("[{}/{}] {} ({})", count, total, id, format_bytes(size));
+                }
+                Err(e) => {
+                    eprintln!("[{}/{}] {}: {}", count, total, id, e);
+                    failures.push((id, e));
+                }
+            }
+        }
+        join_workers(workers)?;
+        failures.sort();
+        Ok((
+            create_report_table(DOWNLOAD_REPORT_HEADER, &failures, no_header),
+            failures.len(),
+        ))
+    }
+
+    /// Writes the archive of an entity to the output file, or extracts it to a directory,
+    /// possibly following a layout
+    fn download(
+        &self,
+        kind: EntityKind,
+        id: &str,
+        output_file: Option<&str>,
+        options: DownloadOptions,
+    ) -> Result<()> {
+        let transcode = self.check_transcode(options.transcode.as_deref())?;
+        let transcode = transcode.as_deref();
+        if let (Some(dir), Some(layout)) = (&options.extract, &options.layout) {
+            return self.download_with_layout(
+                kind,
+                id,
+                Path::new(dir),
+                layout,
+                transcode,
+                options.skip_existing,
+            );
+        }
+        self.save_archive(output_file, &options, |writer| {
+            self.write_archive(&kind, id, options.media, transcode, writer)
+        })
+    }
+
+    /// Writes an archive to the output file, or extracts it to a directory, once its integrity
+    /// is verified
+    fn save_archive<F: FnOnce(&mut dyn Write) -> Result<()>>(
+        &self,
+        output_file: Option<&str>,
+        options: &DownloadOptions,
+        write: F,
+    ) -> Result<()> {
+        match (options.extract.as_deref(), output_file) {
+            (Some(dir), _) => {
+                let dir = Path::new(dir);
+                fs::create_dir_all(dir)?;
+                // The archive is kept aside until extracted, as it can be larger than memory
+                let archive_path = dir.join(DOWNLOAD_ARCHIVE_FILE);
+                let mut file = fs::OpenOptions::new()
+                    .read(true)
+                    .write(true)
+                    .create(true)
+                    .truncate(true)
+                    .open(&archive_path)?;
+                let result = download_to(&mut file, write)
+                    .and_then(|_| verify_archive(&mut file))
+                    .and_then(|_| extract_archive(file, dir));
+                // A failed cleanup mustn't hide the error of the download
+                let removed = fs::remove_file(&archive_path);
+                result?;
+                removed.map_err(Into::into)
+            }
+            (None, Some(f)) => {
+                let path = Path::new(f);
+                // Orthanc builds archives on the fly, so an incomplete one can't be continued
+                if options.skip_existing
+                    && path.exists()
+                    && verify_archive(fs::File::open(path)?).is_ok()
+                {
+                    return Ok(());
+                }
+                write_file_atomically(path, |file| {
+                    download_to(file, write)?;
+                    verify_archive(file)
+                })
+            }
+            (None, None) => Err(CliError::new(
+                "Command error",
+                Some("No output file or directory given"),
+                None,
+            )),
+        }
+    }
+
+    /// UID of the transfer syntax to transcode to, if any. Servers that don't support
+    /// transcoding are rejected, as they would silently send the data untranscoded.
+    fn check_transcode(&self, transcode: Option<&str>) -> Result<Option<String>> {
+        match transcode {
+            Some(t) => {
+                let uid = get_transfer_syntax(t)?;
+                check_server_version(
+                    &self.client.system()?.version,
+                    TRANSCODING_MIN_VERSION,
+                    "Transcoding",
+                )?;
+                Ok(Some(uid))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn write_archive<W: Write>(
+        &self,
+        kind: &EntityKind,
+        id: &str,
+        media: bool,
+        transcode: Option<&str>,
+        writer: W,
+    ) -> Result<()> {
+        match (kind, media) {
+            (EntityKind::Patient, false) => {
+                self.client.patient_dicom(id, transcode, writer)
+            }
+            (EntityKind::Patient, true) => self.client.patient_media(id, transcode, writer),
+            (EntityKind::Study, false) => self.client.study_dicom(id, transcode, writer),
+            (EntityKind::Study, true) => self.client.study_media(id, transcode, writer),
+            (EntityKind::Series, false) => self.client.series_dicom(id, transcode, writer),
+            (EntityKind::Series, true) => self.client.series_media(id, transcode, writer),
+            (EntityKind::Instance, _) => self.client.instance_dicom(id, transcode, writer),
+        }
+        .map_err(Into::into)
+    }
+
+    /// Downloads the instances of an entity one by one, to the paths given by the layout
+    fn download_with_layout(
+        &self,
+        kind: EntityKind,
+        id: &str,
+        dir: &Path,
+        layout: &str,
+        transcode: Option<&str>,
+        skip_existing: bool,
+    ) -> Result<()> {
+        expand_layout(layout, &[])?; // Fail before downloading anything if the layout is wrong
+        let series_ids = match kind {
+            EntityKind::Patient => {
+                let mut ids = vec![];
+                for study_id in self.client.patient(id)?.studies {
+                    ids.extend(self.client.study(&study_id)?.series);
+                }
+                ids
+            }
+            EntityKind::Study => self.client.study(id)?.series,
+            _ => vec![id.to_string()],
+        };
+        let mut paths = HashSet::new();
+        let mut progress = Progress::new(atty::is(atty::Stream::Stderr));
+        for series_id in series_ids {
+            let series = self.client.series(&series_id)?;
+            let study = self.client.study(&series.parent_study)?;
+            for instance_id in &series.instances {
+                let instance = self.client.instance(instance_id)?;
+                let path = dir.join(expand_layout(
+                    layout,
+                    &[
+                        &instance.main_dicom_tags,
+                        &series.main_dicom_tags,
+                        &study.main_dicom_tags,
+                        &study.patient_main_dicom_tags,
+                    ],
+                )?);
+                if !paths.insert(path.clone()) {
+                    return Err(CliError::new(
+                        "Command error",
+                        Some(&format!(
+                            "Several instances have the same path: {}",
+                            path.display()
+                        )),
+                        Some("The layout must give a unique path to each instance, e.g. with {SOPInstanceUID}"),
+                    ));
+                }
+                if skip_existing && path.exists() {
+                    continue;
+                }
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                write_file_atomically(&path, |file| {
+                    self.client
+                        .instance_dicom(instance_id, transcode, progress.writer(file))
+                        .map_err(Into::into)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
     ////////// MODALITY //////////
 
-    pub fn do_store(&self, modality: &str, ids: &Vec<&str>) -> Result<Table> {
-        match self.client.modality_store(modality, ids) {
+    pub fn do_store(
+        &self,
+        modality: &str,
+        ids: &Vec<&str>,
+        transcode: Option<&str>,
+    ) -> Result<Table> {
+        let ids = ids
+            .iter()
+            .map(|i| self.resolve_id(i, None))
+            .collect::<Result<Vec<String>>>()?;
+        let ids: Vec<&str> = ids.iter().map(|i| i.as_str()).collect();
+        let transcode = self.check_transcode(transcode)?;
+        match self
+            .client
+            .modality_store(modality, &ids, transcode.as_deref())
+        {
             Ok(r) => {
                 let mut table = create_table(None);
                 table.add_row(["Remote AET", &r.remote_aet].iter());
@@ -630,68 +1623,779 @@ impl Orthanc {
     pub fn search_patients(
         &self,
         query: Vec<&str>,
+        options: SearchOptions,
+        sort: Option<Vec<&str>>,
         columns: Option<Vec<&str>>,
         no_header: bool,
     ) -> Result<Table> {
         let header = &mut PATIENTS_LIST_HEADER.to_vec();
         let dicom_tags = &mut PATIENTS_LIST_DICOM_TAGS.to_vec();
-        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+        get_header_and_dicom_tags(
+            header,
+            dicom_tags,
+            PATIENTS_LIST_EXTRA_COLUMNS,
+            columns,
+        )?;
 
-        let patients: Vec<Patient> = self.client.search(parse_tag_kv_pairs(query)?)?;
+        let patients: Vec<Patient> = self.find(query, options, sort)?;
+        let extra_values = self.get_extra_values(&patients, header)?;
 
         Ok(utils::create_list_table(
-            patients, header, dicom_tags, no_header,
+            patients,
+            header,
+            dicom_tags,
+            &extra_values,
+            no_header,
         ))
     }
 
     pub fn search_studies(
         &self,
         query: Vec<&str>,
+        options: SearchOptions,
+        sort: Option<Vec<&str>>,
         columns: Option<Vec<&str>>,
         no_header: bool,
     ) -> Result<Table> {
         let header = &mut STUDIES_LIST_HEADER.to_vec();
         let dicom_tags = &mut STUDIES_LIST_DICOM_TAGS.to_vec();
-        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+        get_header_and_dicom_tags(header, dicom_tags, LIST_EXTRA_COLUMNS, columns)?;
 
-        let studies: Vec<Study> = self.client.search(parse_tag_kv_pairs(query)?)?;
+        let studies: Vec<Study> = self.find(query, options, sort)?;
 
+        let extra_values = self.get_extra_values(&studies, header)?;
         Ok(utils::create_list_table(
-            studies, header, dicom_tags, no_header,
+            studies,
+            header,
+            dicom_tags,
+            &extra_values,
+            no_header,
         ))
     }
 
     pub fn search_series(
         &self,
         query: Vec<&str>,
+        options: SearchOptions,
+        sort: Option<Vec<&str>>,
         columns: Option<Vec<&str>>,
         no_header: bool,
     ) -> Result<Table> {
         let header = &mut SERIES_LIST_HEADER.to_vec();
         let dicom_tags = &mut SERIES_LIST_DICOM_TAGS.to_vec();
-        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+        get_header_and_dicom_tags(header, dicom_tags, LIST_EXTRA_COLUMNS, columns)?;
 
-        let series: Vec<Series> = self.client.search(parse_tag_kv_pairs(query)?)?;
+        let series: Vec<Series> = self.find(query, options, sort)?;
 
+        let extra_values = self.get_extra_values(&series, header)?;
         Ok(utils::create_list_table(
-            series, header, dicom_tags, no_header,
+            series,
+            header,
+            dicom_tags,
+            &extra_values,
+            no_header,
         ))
     }
 
     pub fn search_instances(
         &self,
         query: Vec<&str>,
+        options: SearchOptions,
+        sort: Option<Vec<&str>>,
         columns: Option<Vec<&str>>,
         no_header: bool,
     ) -> Result<Table> {
         let header = &mut INSTANCES_LIST_HEADER.to_vec();
         let dicom_tags = &mut INSTANCES_LIST_DICOM_TAGS.to_vec();
-        get_header_and_dicom_tags(header, dicom_tags, columns)?;
+        get_header_and_dicom_tags(header, dicom_tags, LIST_EXTRA_COLUMNS, columns)?;
 
-        let instances: Vec<Instance> = self.client.search(parse_tag_kv_pairs(query)?)?;
+        let instances: Vec<Instance> = self.find(query, options, sort)?;
 
+        let extra_values = self.get_extra_values(&instances, header)?;
         Ok(utils::create_list_table(
-            instances, header, dicom_tags, no_header,
+            instances,
+            header,
+            dicom_tags,
+            &extra_values,
+            no_header,
+        ))
+    }
+
+    /// Number of search results. Only the IDs of the results are fetched, not the entities.
+    pub fn count_search_results(
+        &self,
+        level: &str,
+        query: Vec<&str>,
+        options: SearchOptions,
+    ) -> Result<usize> {
+        let kind = get_entity_kind(level);
+        let request = self.get_find_request(query, kind.clone(), options, false)?;
+        Ok(self.client.find_ids(kind, request)?.len())
+    }
+
+    fn find<T: Entity>(
+        &self,
+        query: Vec<&str>,
+        options: SearchOptions,
+        sort: Option<Vec<&str>>,
+    ) -> Result<Vec<T>> {
+        let limit = options.limit;
+        let request = self.get_find_request(query, T::kind(), options, sort.is_some())?;
+        let mut entities = self.client.find(request)?;
+        if let Some(s) = sort {
+            sort_entities(&mut entities, &s);
+            if let Some(l) = limit {
+                entities.truncate(l as usize);
+            }
+        }
+        Ok(entities)
+    }
+
+    fn get_find_request(
+        &self,
+        query: Vec<&str>,
+        kind: EntityKind,
+        options: SearchOptions,
+        sorted: bool,
+    ) -> Result<FindRequest> {
+        if let Some(labels) = &options.labels {
+            self.check_labels()?;
+            for l in labels {
+                check_label(l)?;
+            }
+        }
+        Ok(FindRequest {
+            query: parse_search_query(query, kind, Local::now().naive_local().date())?,
+            case_sensitive: if options.ignore_case {
+                Some(false)
+            } else {
+                None
+            },
+            // The server would return an arbitrary subset, not the first results of the sort
+            limit: if sorted { None } else { options.limit },
+            labels: options.labels,
+            labels_constraint: options.labels_constraint,
+        })
+    }
+
+    ////////// SAVED QUERY //////////
+
+    pub fn save_query(
+        &self,
+        name: &str,
+        level: &str,
+        query: Vec<&str>,
+        ignore_case: bool,
+        columns: Option<Vec<&str>>,
+        sort: Option<Vec<&str>>,
+    ) -> Result<()> {
+        if columns.is_some() {
+            let (header, extra_columns) = match level {
+                "patient" => (PATIENTS_LIST_HEADER, PATIENTS_LIST_EXTRA_COLUMNS),
+                "study" => (STUDIES_LIST_HEADER, LIST_EXTRA_COLUMNS),
+                "series" => (SERIES_LIST_HEADER, LIST_EXTRA_COLUMNS),
+                _ => (INSTANCES_LIST_HEADER, LIST_EXTRA_COLUMNS),
+            };
+            get_header_and_dicom_tags(
+                &mut header.to_vec(),
+                &mut vec![],
+                extra_columns,
+                columns.clone(),
+            )?;
+        }
+        let to_strings = |v: Vec<&str>| v.into_iter().map(String::from).collect();
+        let path = get_saved_queries_file()?;
+        let mut queries = load_saved_queries(&path)?;
+        queries.insert(
+            name.to_string(),
+            SavedQuery {
+                level: level.to_string(),
+                query: to_strings(query),
+                ignore_case,
+                columns: columns.map(to_strings),
+                sort: sort.map(to_strings),
+            },
+        );
+        store_saved_queries(&path, &queries)
+    }
+
+    pub fn list_queries(&self, no_header: bool) -> Result<Table> {
+        Ok(create_saved_queries_table(
+            load_saved_queries(&get_saved_queries_file()?)?,
+            no_header,
         ))
     }
+
+    pub fn run_query(
+        &self,
+        name: &str,
+        params: Option<Vec<&str>>,
+        no_header: bool,
+    ) -> Result<Table> {
+        let saved_query = self.get_saved_query(name)?;
+        let params = match params {
+            Some(p) => parse_tag_kv_pairs(p)?,
+            None => HashMap::new(),
+        };
+        let today = Local::now().naive_local().date();
+        let query = saved_query
+            .query
+            .iter()
+            .map(|t| expand_query_placeholders(t, &params, today))
+            .collect::<Result<Vec<String>>>()?;
+        let query = query.iter().map(|t| t.as_str()).collect();
+        let columns = saved_query
+            .columns
+            .as_ref()
+            .map(|c| c.iter().map(|v| v.as_str()).collect());
+        let sort = saved_query
+            .sort
+            .as_ref()
+            .map(|s| s.iter().map(|v| v.as_str()).collect());
+        let options = SearchOptions {
+            ignore_case: saved_query.ignore_case,
+            ..Default::default()
+        };
+        match saved_query.level.as_str() {
+            "patient" => self.search_patients(query, options, sort, columns, no_header),
+            "study" => self.search_studies(query, options, sort, columns, no_header),
+            "series" => self.search_series(query, options, sort, columns, no_header),
+            _ => self.search_instances(query, options, sort, columns, no_header),
+        }
+    }
+
+    pub fn delete_query(&self, name: &str) -> Result<()> {
+        self.get_saved_query(name)?;
+        let path = get_saved_queries_file()?;
+        let mut queries = load_saved_queries(&path)?;
+        queries.remove(name);
+        store_saved_queries(&path, &queries)
+    }
+
+    fn get_saved_query(&self, name: &str) -> Result<SavedQuery> {
+        load_saved_queries(&get_saved_queries_file()?)?
+            .remove(name)
+            .ok_or_else(|| CliError::new(&format!("Query {} not found", name), None, None))
+    }
+
+    ////////// CHANGES //////////
+
+    pub fn list_changes(
+        &self,
+        since: Option<u64>,
+        types: Option<Vec<&str>>,
+        no_header: bool,
+    ) -> Result<Table> {
+        let mut changes = vec![];
+        let mut since = since.unwrap_or(0);
+        loop {
+            let batch = self.client.changes(Some(since), None)?;
+            changes.extend(filter_changes(batch.changes, &types));
+            since = batch.last;
+            if batch.done {
+                break;
+            }
+        }
+        Ok(create_changes_table(&changes, no_header))
+    }
+
+    /// Polls the changes feed forever, printing the changes as they come. Polling slows down
+    /// (up to a limit) while there is nothing new, and server errors are retried the same way.
+    /// The sequence number of the last seen change is kept in the state file, so that following
+    /// can be resumed from there.
+    ///
+    /// With a delivery, every change is also handed over to its handler. The state is only written once the
+    /// whole batch has been handled, so a change may be delivered more than once, but never lost.
+    pub fn follow_changes(
+        &self,
+        since: Option<u64>,
+        types: Option<Vec<&str>>,
+        state_file: Option<&str>,
+        delivery: Option<ChangeDelivery>,
+    ) -> Result<()> {
+        let state_file = match state_file {
+            Some(f) => PathBuf::from(f),
+            None => get_server_state_file(&self.server_address, CHANGES_STATE_FILE)?,
+        };
+        let mut since = match since {
+            Some(s) => s,
+            None => match read_sequence_state(&state_file)? {
+                Some(s) => s,
+                None => self.client.changes_last()?.last,
+            },
+        };
+        let mut interval = CHANGES_POLL_MIN_INTERVAL;
+        loop {
+            match self.client.changes(Some(since), None) {
+                Ok(batch) => {
+                    let got_changes = !batch.changes.is_empty();
+                    let changes = filter_changes(batch.changes, &types);
+                    for c in &changes {
+                        println!("{}", format_change(c));
+                    }
+                    if let Some(d) = &delivery {
+                        let payloads = changes
+                            .iter()
+                            .map(|c| create_change_payload(c, self.get_main_dicom_tags(c)))
+                            .collect();
+                        deliver_changes(payloads, d)?;
+                    }
+                    if batch.last > since {
+                        since = batch.last;
+                        write_sequence_state(&state_file, since)?;
+                    }
+                    if !batch.done {
+                        // More changes are available right away
+                        continue;
+                    }
+                    interval = if got_changes {
+                        CHANGES_POLL_MIN_INTERVAL
+                    } else {
+                        (interval * 2).min(CHANGES_POLL_MAX_INTERVAL)
+                    };
+                }
+                Err(e) => {
+                    eprintln!("{}", create_error_table(e.into()));
+                    interval = (interval * 2).min(CHANGES_POLL_MAX_INTERVAL);
+                }
+            }
+            thread::sleep(Duration::from_secs(interval));
+        }
+    }
+
+    /// Main DICOM tags of the entity a change is about. Deleted entities have none.
+    fn get_main_dicom_tags(&self, change: &Change) -> HashMap<String, String> {
+        let tags = match change.resource_type.as_str() {
+            "Patient" => self.client.patient(&change.id).map(|p| p.main_dicom_tags),
+            "Study" => self.client.study(&change.id).map(|s| s.main_dicom_tags),
+            "Series" => self.client.series(&change.id).map(|s| s.main_dicom_tags),
+            "Instance" => self.client.instance(&change.id).map(|i| i.main_dicom_tags),
+            _ => return HashMap::new(),
+        };
+        tags.unwrap_or_default()
+    }
+
+    ////////// METADATA //////////
+
+    pub fn list_metadata(&self, level: &str, id: &str, no_header: bool) -> Result<Table> {
+        let kind = get_entity_kind(level);
+        let id = &self.resolve_id(id, Some(kind.clone()))?;
+        Ok(create_metadata_table(
+            self.client.metadata(&kind, id)?,
+            no_header,
+        ))
+    }
+
+    pub fn get_metadata(&self, level: &str, id: &str, name: &str) -> Result<String> {
+        let kind = get_entity_kind(level);
+        let id = &self.resolve_id(id, Some(kind.clone()))?;
+        Ok(self.client.metadata_value(&kind, id, name)?)
+    }
+
+    pub fn set_metadata(
+        &self,
+        level: &str,
+        id: &str,
+        name: &str,
+        value: &str,
+    ) -> Result<()> {
+        let kind = get_entity_kind(level);
+        let id = &self.resolve_id(id, Some(kind.clone()))?;
+        Ok(self.client.set_metadata(&kind, id, name, value)?)
+    }
+
+    pub fn delete_metadata(&self, level: &str, id: &str, name: &str) -> Result<()> {
+        let kind = get_entity_kind(level);
+        let id = &self.resolve_id(id, Some(kind.clone()))?;
+        Ok(self.client.delete_metadata(&kind, id, name)?)
+    }
+
+    /// Values of the columns of the header that don't come from the entities themselves
+    /// (`Protected`, `Labels`, `meta:<Name>`), by entity ID
+    fn get_extra_values<T: Entity>(
+        &self,
+        entities: &[T],
+        header: &[&str],
+    ) -> Result<HashMap<String, Vec<String>>> {
+        let mut extra_values = HashMap::new();
+        let extra_columns: Vec<&str> = header
+            .iter()
+            .filter(|c| {
+                PATIENTS_LIST_EXTRA_COLUMNS.contains(c) || get_metadata_name(c).is_some()
+            })
+            .cloned()
+            .collect();
+        if extra_columns.is_empty() {
+            return Ok(extra_values);
+        }
+        let with_metadata = extra_columns.iter().any(|c| get_metadata_name(c).is_some());
+        if extra_columns.contains(&"Labels") {
+            self.check_labels()?;
+        }
+        for e in entities {
+            let metadata = if with_metadata {
+                self.client.metadata(&T::kind(), e.id())?
+            } else {
+                HashMap::new()
+            };
+            let mut values = vec![];
+            for c in &extra_columns {
+                values.push(match (get_metadata_name(c), *c) {
+                    (Some(name), _) => metadata
+                        .get(name)
+                        .map_or(ABSENT_DICOM_TAG_PLACEHOLDER, String::as_str)
+                        .to_string(),
+                    (None, "Labels") => self.get_labels(&T::kind(), e.id())?.join(","),
+                    (None, "Protected") => {
+                        format!("{}", self.client.patient_protected(e.id())?)
+                    }
+                    // Only the extra columns and metadata columns were kept above
+                    _ => unreachable!("Unknown extra column {}", c),
+                });
+            }
+            extra_values.insert(e.id().to_string(), values);
+        }
+        Ok(extra_values)
+    }
+
+    /// Adds the metadata of an entity to its `show` table
+    fn add_metadata_rows(
+        &self,
+        table: &mut Table,
+        kind: EntityKind,
+        id: &str,
+    ) -> Result<()> {
+        let metadata: BTreeMap<String, String> =
+            self.client.metadata(&kind, id)?.into_iter().collect();
+        for (name, value) in metadata {
+            table.add_row([format!("{}{}", METADATA_COLUMN_PREFIX, name), value].iter());
+        }
+        Ok(())
+    }
+
+    ////////// LABEL //////////
+
+    pub fn list_labels(&self, level: &str, id: &str) -> Result<Vec<String>> {
+        self.check_labels()?;
+        let kind = get_entity_kind(level);
+        let id = &self.resolve_id(id, Some(kind.clone()))?;
+        self.get_labels(&kind, id)
+    }
+
+    /// Adds the label to (or removes it from) all the entities
+    pub fn label_entities(
+        &self,
+        level: &str,
+        label: &str,
+        ids: Vec<String>,
+        add: bool,
+    ) -> Result<()> {
+        check_label(label)?;
+        self.check_labels()?;
+        let kind = get_entity_kind(level);
+        let ids = ids
+            .iter()
+            .map(|i| self.resolve_id(i, Some(kind.clone())))
+            .collect::<Result<Vec<String>>>()?;
+        for id in ids {
+            if add {
+                self.client.add_label(&kind, &id, label)?;
+            } else {
+                self.client.remove_label(&kind, &id, label)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the labels of an entity to its `show` table
+    fn add_labels_row(&self, table: &mut Table, kind: EntityKind, id: &str) -> Result<()> {
+        self.check_labels()?;
+        table.add_row(["Labels", &self.get_labels(&kind, id)?.join(",")].iter());
+        Ok(())
+    }
+
+    fn get_labels(&self, kind: &EntityKind, id: &str) -> Result<Vec<String>> {
+        let mut labels = self.client.labels(kind, id)?;
+        labels.sort();
+        Ok(labels)
+    }
+
+    fn check_labels(&self) -> Result<()> {
+        check_server_version(
+            &self.client.system()?.version,
+            LABELS_MIN_VERSION,
+            "Labeling",
+        )
+    }
+
+    ////////// SYSTEM //////////
+
+    pub fn system_info(&self) -> Result<Table> {
+        let system = self.client.system()?;
+        let plugins = if system.plugins_enabled {
+            self.client.plugins()?
+        } else {
+            vec![]
+        };
+        Ok(create_system_info_table(system, &plugins))
+    }
+
+    pub fn system_stats(&self) -> Result<Table> {
+        Ok(create_system_stats_table(self.client.statistics()?))
+    }
+
+    /// Log level of a category, or of the whole server along with all the categories the
+    /// server supports if no category is given
+    pub fn get_log_level(&self, category: Option<&str>) -> Result<Table> {
+        let mut levels = vec![];
+        match category {
+            Some(c) => {
+                self.check_log_categories()?;
+                levels.push((c, self.client.log_category_level(c)?));
+            }
+            None => {
+                levels.push(("default", self.client.log_level()?));
+                if self.check_log_categories().is_ok() {
+                    for c in LOG_CATEGORIES {
+                        levels.push((c, self.client.log_category_level(c)?));
+                    }
+                }
+            }
+        }
+        Ok(create_log_levels_table(&levels))
+    }
+
+    pub fn set_log_level(&self, level: &str, category: Option<&str>) -> Result<()> {
+        match category {
+            Some(c) => {
+                self.check_log_categories()?;
+                self.client.set_log_category_level(c, level)?;
+            }
+            None => self.client.set_log_level(level)?,
+        };
+        Ok(())
+    }
+
+    fn check_log_categories(&self) -> Result<()> {
+        check_server_version(
+            &self.client.system()?.version,
+            LOG_CATEGORIES_MIN_VERSION,
+            "Per-category logging",
+        )
+    }
+
+    ////////// PLUGIN //////////
+
+    pub fn list_plugins(&self, no_header: bool) -> Result<Table> {
+        let mut plugins = vec![];
+        for id in self.client.plugins()? {
+            plugins.push(self.client.plugin(&id)?);
+        }
+        Ok(create_plugins_table(&plugins, no_header))
+    }
+
+    pub fn show_plugin(&self, id: &str) -> Result<Table> {
+        if !self.client.plugins()?.iter().any(|p| p == id) {
+            return Err(CliError::new(
+                &format!("Plugin {} not found", id),
+                None,
+                None,
+            ));
+        }
+        Ok(create_plugin_table(self.client.plugin(id)?))
+    }
+
+    ////////// TOOLS //////////
+
+    pub fn exec_lua(&self, script_file: &str, vars: Option<Vec<&str>>) -> Result<String> {
+        let script = create_lua_script(&read_script(script_file)?, vars)?;
+        Ok(self.client.execute_script(&script)?)
+    }
+
+    ////////// LOOKUP //////////
+
+    pub fn lookup(&self, value: &str) -> Result<Table> {
+        let entities = match parse_entity_id(value) {
+            EntityId::Plain(v) => {
+                let mut entities = self.lookup_entities(v)?;
+                // The lookup tool doesn't know about accession numbers
+                if entities.is_empty() {
+                    entities =
+                        self.find_entities(ACCESSION_NUMBER_TAG, v, EntityKind::Study)?;
+                }
+                entities
+            }
+            EntityId::DicomUid(uid) => self.lookup_entities(uid)?,
+            EntityId::PatientId(pid) => {
+                self.find_entities(PATIENT_ID_TAG, pid, EntityKind::Patient)?
+            }
+            EntityId::AccessionNumber(a) => {
+                self.find_entities(ACCESSION_NUMBER_TAG, a, EntityKind::Study)?
+            }
+        };
+        if entities.is_empty() {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!("No entity found for {}", value)),
+                None,
+            ));
+        }
+        Ok(create_lookup_table(&entities))
+    }
+
+    /// Resolves an ID given in one of the prefixed forms (`uid:`, `accession:`, `pid:`) to the
+    /// Orthanc ID of the matching entity. Plain IDs are returned as is.
+    fn resolve_id(&self, id: &str, kind: Option<EntityKind>) -> Result<String> {
+        let mut entities = match parse_entity_id(id) {
+            EntityId::Plain(i) => return Ok(i.to_string()),
+            EntityId::DicomUid(uid) => self.lookup_entities(uid)?,
+            EntityId::PatientId(pid) => self.find_entities(
+                PATIENT_ID_TAG,
+                pid,
+                kind.clone().unwrap_or(EntityKind::Patient),
+            )?,
+            EntityId::AccessionNumber(a) => self.find_entities(
+                ACCESSION_NUMBER_TAG,
+                a,
+                kind.clone().unwrap_or(EntityKind::Study),
+            )?,
+        };
+        if let Some(k) = &kind {
+            entities.retain(|(e, _)| e == k);
+        }
+        match entities.len() {
+            0 => Err(CliError::new(
+                "Command error",
+                Some(&format!(
+                    "No {} found for {}",
+                    kind.map_or("entity".to_string(), |k| format!("{:?}", k)
+                        .to_lowercase()),
+                    id
+                )),
+                None,
+            )),
+            1 => Ok(entities.remove(0).1),
+            n => Err(CliError::new(
+                "Command error",
+                Some(&format!("Ambiguous ID {}: {} entities found", id, n)),
+                Some(
+                    &entities
+                        .iter()
+                        .map(|(_, i)| i.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", "),
+                ),
+            )),
+        }
+    }
+
+    fn lookup_entities(&self, value: &str) -> Result<Vec<(EntityKind, String)>> {
+        Ok(self
+            .client
+            .lookup(value)?
+            .into_iter()
+            .map(|r| (r.resource_type, r.id))
+            .collect())
+    }
+
+    fn find_entities(
+        &self,
+        tag: &str,
+        value: &str,
+        kind: EntityKind,
+    ) -> Result<Vec<(EntityKind, String)>> {
+        let mut query = HashMap::new();
+        query.insert(tag.to_string(), value.to_string());
+        let ids: Vec<String> = match kind {
+            EntityKind::Patient => self
+                .client
+                .search::<Patient>(query)?
+                .into_iter()
+                .map(|e| e.id)
+                .collect(),
+            EntityKind::Study => self
+                .client
+                .search::<Study>(query)?
+                .into_iter()
+                .map(|e| e.id)
+                .collect(),
+            EntityKind::Series => self
+                .client
+                .search::<Series>(query)?
+                .into_iter()
+                .map(|e| e.id)
+                .collect(),
+            EntityKind::Instance => self
+                .client
+                .search::<Instance>(query)?
+                .into_iter()
+                .map(|e| e.id)
+                .collect(),
+        };
+        Ok(ids.into_iter().map(|i| (kind.clone(), i)).collect())
+    }
+}
+
+/// Runs the task on every ID with a pool of `concurrency` threads. The results are sent on the
+/// returned channel as they come, along with their ID.
+fn spawn_workers<T, F>(
+    client: &Client,
+    ids: Vec<String>,
+    concurrency: usize,
+    task: F,
+) -> (mpsc::Receiver<(String, T)>, Vec<thread::JoinHandle<()>>)
+where
+    T: Send + 'static,
+    F: Fn(&Client, &str) -> T + Send + Sync + 'static,
+{
+    let queue = Arc::new(Mutex::new(ids));
+    let task = Arc::new(task);
+    let (sender, receiver) = mpsc::channel();
+    let workers = (0..concurrency.max(1))
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let task = Arc::clone(&task);
+            let sender = sender.clone();
+            let client = client.clone();
+            thread::spawn(move || loop {
+                let id = match queue.lock().unwrap().pop() {
+                    Some(i) => i,
+                    None => break,
+                };
+                let result = task(&client, &id);
+                sender.send((id, result)).ok();
+            })
+        })
+        .collect();
+    (receiver, workers)
+}
+
+fn join_workers(workers: Vec<thread::JoinHandle<()>>) -> Result<()> {
+    for worker in workers {
+        worker.join().map_err(|_| {
+            CliError::new("Command error", Some("Worker thread panicked"), None)
+        })?;
+    }
+    Ok(())
+}
+
+fn get_error_message(e: Error) -> String {
+    let e = CliError::from(e);
+    e.message.unwrap_or(e.error)
+}
+
+/// Verifies the MD5 of all the attachments of an instance. Returns (instance ID, attachment
+/// name, error) for those that are corrupted, or can't be checked.
+fn verify_instance_attachments(client: &Client, id: &str) -> Vec<(String, String, String)> {
+    let names = match client.instance_attachments(id) {
+        Ok(n) => n,
+        Err(e) => return vec![(id.to_string(), "".to_string(), get_error_message(e))],
+    };
+    names
+        .into_iter()
+        .filter_map(|name| match client.verify_instance_attachment(id, &name) {
+            Ok(_) => None,
+            Err(e) => Some((id.to_string(), name, get_error_message(e))),
+        })
+        .collect()
 }
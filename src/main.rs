@@ -1,3 +1,4 @@
+use clap::ArgMatches;
 use cli::*;
 use orthanc_cli::*;
 use utils::*;
@@ -11,11 +12,13 @@ fn main() {
         Err(e) => exit_with_error(e),
     };
     let o = match Orthanc::new(
-        server_address,
+        server_address.clone(),
         get_username(matches.value_of("username")),
         get_password(matches.value_of("password")),
         get_iap_client_id(matches.value_of("iap_client_id")),
-        get_google_application_credentials(matches.value_of("google_application_credentials")),
+        get_google_application_credentials(
+            matches.value_of("google_application_credentials"),
+        ),
     ) {
         Ok(o) => o,
         Err(e) => return exit_with_error(e),
@@ -30,10 +33,24 @@ fn main() {
                 Ok(t) => print_table(t),
                 Err(e) => exit_with_error(e),
             },
-            Some(("show", show)) => match o.show_patient(show.value_of("id").unwrap()) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
+            Some(("show", show)) => {
+                match o.show_patient(
+                    show.value_of("id").unwrap(),
+                    show.is_present("metadata"),
+                    show.is_present("labels"),
+                ) {
+                    Ok(t) => print_table(t),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("metadata", metadata)) => run_metadata_command(&o, "patient", metadata),
+            Some(("label", label)) => run_label_command(&o, "patient", label),
+            Some(("reconstruct", reconstruct)) => {
+                match o.reconstruct("patient", reconstruct.value_of("id").unwrap()) {
+                    Ok(_) => (),
+                    Err(e) => exit_with_error(e),
+                }
+            }
             Some(("list-studies", list_studies)) => {
                 match o.list_studies(
                     list_studies.value_of("id"),
@@ -61,13 +78,32 @@ fn main() {
                 }
             }
             Some(("search", search)) => {
-                match o.search_patients(
-                    search.values_of("query").unwrap().collect(),
-                    search.values_of("columns").map(|c| c.collect()),
-                    search.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
-                    Err(e) => exit_with_error(e),
+                if search.is_present("count") || search.is_present("exists") {
+                    match o.count_search_results(
+                        "patient",
+                        search
+                            .values_of("query")
+                            .map(|q| q.collect())
+                            .unwrap_or_default(),
+                        get_search_options(search),
+                    ) {
+                        Ok(c) => print_search_count(c, search.is_present("exists")),
+                        Err(e) => exit_with_search_error(e, search.is_present("exists")),
+                    }
+                } else {
+                    match o.search_patients(
+                        search
+                            .values_of("query")
+                            .map(|q| q.collect())
+                            .unwrap_or_default(),
+                        get_search_options(search),
+                        search.values_of("sort").map(|s| s.collect()),
+                        search.values_of("columns").map(|c| c.collect()),
+                        search.is_present("no_header"),
+                    ) {
+                        Ok(t) => print_table(t),
+                        Err(e) => exit_with_error(e),
+                    }
                 }
             }
             Some(("modify", modify)) => match o.modify_patient(
@@ -81,11 +117,44 @@ fn main() {
             },
             Some(("download", download)) => match o.download_patient(
                 download.value_of("id").unwrap(),
-                download.value_of("output").unwrap(),
+                download.value_of("output"),
+                get_download_options(download),
             ) {
                 Ok(_) => (),
                 Err(e) => exit_with_error(e),
             },
+            Some(("tree", tree)) => match o.show_patient_tree(
+                tree.value_of("id").unwrap(),
+                tree.value_of("depth"),
+                tree.value_of("output") == Some("json"),
+            ) {
+                Ok(t) => println!("{}", t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("stats", stats)) => {
+                match o.patient_stats(stats.value_of("id").unwrap()) {
+                    Ok(t) => print_table(t),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("protect", protect)) => {
+                match get_ids(protect.values_of("ids").map(|i| i.collect())) {
+                    Ok(ids) => match o.protect_patients(ids, true) {
+                        Ok(_) => (),
+                        Err(e) => exit_with_error(e),
+                    },
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("unprotect", unprotect)) => {
+                match get_ids(unprotect.values_of("ids").map(|i| i.collect())) {
+                    Ok(ids) => match o.protect_patients(ids, false) {
+                        Ok(_) => (),
+                        Err(e) => exit_with_error(e),
+                    },
+                    Err(e) => exit_with_error(e),
+                }
+            }
             Some(("delete", delete)) => {
                 match o.delete_patient(delete.value_of("id").unwrap()) {
                     Ok(_) => (),
@@ -105,7 +174,38 @@ fn main() {
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("show", show)) => match o.show_study(show.value_of("id").unwrap()) {
+            Some(("show", show)) => {
+                match o.show_study(
+                    show.value_of("id").unwrap(),
+                    show.is_present("metadata"),
+                    show.is_present("labels"),
+                ) {
+                    Ok(t) => print_table(t),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("metadata", metadata)) => run_metadata_command(&o, "study", metadata),
+            Some(("label", label)) => run_label_command(&o, "study", label),
+            Some(("reconstruct", reconstruct)) => {
+                match o.reconstruct("study", reconstruct.value_of("id").unwrap()) {
+                    Ok(_) => (),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("merge", merge)) => match o.merge_study(
+                merge.value_of("id").unwrap(),
+                merge.values_of("sources").unwrap().collect(),
+                merge.is_present("keep_source"),
+            ) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("split", split)) => match o.split_study(
+                split.value_of("id").unwrap(),
+                split.values_of("series").unwrap().collect(),
+                split.values_of("replace").map(|r| r.collect()),
+                split.is_present("keep_source"),
+            ) {
                 Ok(t) => print_table(t),
                 Err(e) => exit_with_error(e),
             },
@@ -120,13 +220,32 @@ fn main() {
                 }
             }
             Some(("search", search)) => {
-                match o.search_studies(
-                    search.values_of("query").unwrap().collect(),
-                    search.values_of("columns").map(|c| c.collect()),
-                    search.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
-                    Err(e) => exit_with_error(e),
+                if search.is_present("count") || search.is_present("exists") {
+                    match o.count_search_results(
+                        "study",
+                        search
+                            .values_of("query")
+                            .map(|q| q.collect())
+                            .unwrap_or_default(),
+                        get_search_options(search),
+                    ) {
+                        Ok(c) => print_search_count(c, search.is_present("exists")),
+                        Err(e) => exit_with_search_error(e, search.is_present("exists")),
+                    }
+                } else {
+                    match o.search_studies(
+                        search
+                            .values_of("query")
+                            .map(|q| q.collect())
+                            .unwrap_or_default(),
+                        get_search_options(search),
+                        search.values_of("sort").map(|s| s.collect()),
+                        search.values_of("columns").map(|c| c.collect()),
+                        search.is_present("no_header"),
+                    ) {
+                        Ok(t) => print_table(t),
+                        Err(e) => exit_with_error(e),
+                    }
                 }
             }
             Some(("anonymize", anonymize)) => {
@@ -156,11 +275,47 @@ fn main() {
             },
             Some(("download", download)) => match o.download_study(
                 download.value_of("id").unwrap(),
-                download.value_of("output").unwrap(),
+                download.value_of("output"),
+                get_download_options(download),
             ) {
                 Ok(_) => (),
                 Err(e) => exit_with_error(e),
             },
+            Some(("download-many", download_many)) => match o.download_studies(
+                download_many.values_of("query").map(|q| q.collect()),
+                download_many.value_of("dir").unwrap(),
+                download_many
+                    .value_of("concurrency")
+                    .unwrap()
+                    .parse()
+                    .unwrap(),
+                download_many.is_present("extract"),
+                download_many.value_of("transcode"),
+                download_many.is_present("no_header"),
+            ) {
+                Ok((t, failures)) => print_report(t, failures),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("tree", tree)) => match o.show_study_tree(
+                tree.value_of("id").unwrap(),
+                tree.value_of("depth"),
+                tree.value_of("output") == Some("json"),
+            ) {
+                Ok(t) => println!("{}", t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("stats", stats)) => match o.study_stats(stats.value_of("id").unwrap()) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("verify", verify)) => match o.verify_study(
+                verify.value_of("id").unwrap(),
+                verify.value_of("concurrency").unwrap().parse().unwrap(),
+                verify.is_present("no_header"),
+            ) {
+                Ok((t, corrupted)) => print_report(t, corrupted),
+                Err(e) => exit_with_error(e),
+            },
             Some(("delete", delete)) => {
                 match o.delete_study(delete.value_of("id").unwrap()) {
                     Ok(_) => (),
@@ -180,10 +335,24 @@ fn main() {
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("show", show)) => match o.show_series(show.value_of("id").unwrap()) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
+            Some(("show", show)) => {
+                match o.show_series(
+                    show.value_of("id").unwrap(),
+                    show.is_present("metadata"),
+                    show.is_present("labels"),
+                ) {
+                    Ok(t) => print_table(t),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("metadata", metadata)) => run_metadata_command(&o, "series", metadata),
+            Some(("label", label)) => run_label_command(&o, "series", label),
+            Some(("reconstruct", reconstruct)) => {
+                match o.reconstruct("series", reconstruct.value_of("id").unwrap()) {
+                    Ok(_) => (),
+                    Err(e) => exit_with_error(e),
+                }
+            }
             Some(("list-instances", list_instances)) => {
                 match o.list_instances(
                     list_instances.value_of("id"),
@@ -195,13 +364,32 @@ fn main() {
                 }
             }
             Some(("search", search)) => {
-                match o.search_series(
-                    search.values_of("query").unwrap().collect(),
-                    search.values_of("columns").map(|c| c.collect()),
-                    search.is_present("no_header"),
-                ) {
-                    Ok(t) => print_table(t),
-                    Err(e) => exit_with_error(e),
+                if search.is_present("count") || search.is_present("exists") {
+                    match o.count_search_results(
+                        "series",
+                        search
+                            .values_of("query")
+                            .map(|q| q.collect())
+                            .unwrap_or_default(),
+                        get_search_options(search),
+                    ) {
+                        Ok(c) => print_search_count(c, search.is_present("exists")),
+                        Err(e) => exit_with_search_error(e, search.is_present("exists")),
+                    }
+                } else {
+                    match o.search_series(
+                        search
+                            .values_of("query")
+                            .map(|q| q.collect())
+                            .unwrap_or_default(),
+                        get_search_options(search),
+                        search.values_of("sort").map(|s| s.collect()),
+                        search.values_of("columns").map(|c| c.collect()),
+                        search.is_present("no_header"),
+                    ) {
+                        Ok(t) => print_table(t),
+                        Err(e) => exit_with_error(e),
+                    }
                 }
             }
             Some(("anonymize", anonymize)) => {
@@ -231,11 +419,24 @@ fn main() {
             },
             Some(("download", download)) => match o.download_series(
                 download.value_of("id").unwrap(),
-                download.value_of("output").unwrap(),
+                download.value_of("output"),
+                get_download_options(download),
             ) {
                 Ok(_) => (),
                 Err(e) => exit_with_error(e),
             },
+            Some(("tree", tree)) => match o.show_series_tree(
+                tree.value_of("id").unwrap(),
+                tree.value_of("depth"),
+                tree.value_of("output") == Some("json"),
+            ) {
+                Ok(t) => println!("{}", t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("stats", stats)) => match o.series_stats(stats.value_of("id").unwrap()) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
             Some(("delete", delete)) => {
                 match o.delete_series(delete.value_of("id").unwrap()) {
                     Ok(_) => (),
@@ -255,20 +456,53 @@ fn main() {
                     Err(e) => exit_with_error(e),
                 }
             }
-            Some(("show", show)) => match o.show_instance(show.value_of("id").unwrap()) {
-                Ok(t) => print_table(t),
-                Err(e) => exit_with_error(e),
-            },
-            Some(("search", search)) => {
-                match o.search_instances(
-                    search.values_of("query").unwrap().collect(),
-                    search.values_of("columns").map(|c| c.collect()),
-                    search.is_present("no_header"),
+            Some(("show", show)) => {
+                match o.show_instance(
+                    show.value_of("id").unwrap(),
+                    show.is_present("metadata"),
+                    show.is_present("labels"),
                 ) {
                     Ok(t) => print_table(t),
                     Err(e) => exit_with_error(e),
                 }
             }
+            Some(("metadata", metadata)) => run_metadata_command(&o, "instance", metadata),
+            Some(("label", label)) => run_label_command(&o, "instance", label),
+            Some(("reconstruct", reconstruct)) => {
+                match o.reconstruct("instance", reconstruct.value_of("id").unwrap()) {
+                    Ok(_) => (),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("search", search)) => {
+                if search.is_present("count") || search.is_present("exists") {
+                    match o.count_search_results(
+                        "instance",
+                        search
+                            .values_of("query")
+                            .map(|q| q.collect())
+                            .unwrap_or_default(),
+                        get_search_options(search),
+                    ) {
+                        Ok(c) => print_search_count(c, search.is_present("exists")),
+                        Err(e) => exit_with_search_error(e, search.is_present("exists")),
+                    }
+                } else {
+                    match o.search_instances(
+                        search
+                            .values_of("query")
+                            .map(|q| q.collect())
+                            .unwrap_or_default(),
+                        get_search_options(search),
+                        search.values_of("sort").map(|s| s.collect()),
+                        search.values_of("columns").map(|c| c.collect()),
+                        search.is_present("no_header"),
+                    ) {
+                        Ok(t) => print_table(t),
+                        Err(e) => exit_with_error(e),
+                    }
+                }
+            }
             Some(("anonymize", anonymize)) => {
                 let mut keep_private_tags = None;
                 if anonymize.is_present("keep_private_tags") {
@@ -286,6 +520,16 @@ fn main() {
                     Err(e) => exit_with_error(e),
                 }
             }
+            Some(("create", create)) => match o.create_instance(
+                create.values_of("tag").map(|t| t.collect()),
+                create.value_of("tags"),
+                create.value_of("image"),
+                create.value_of("pdf"),
+                create.value_of("parent"),
+            ) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
             Some(("modify", modify)) => match o.modify_instance(
                 modify.value_of("id").unwrap(),
                 modify.values_of("replace").map(|r| r.collect()),
@@ -305,10 +549,57 @@ fn main() {
             Some(("download", download)) => match o.download_instance(
                 download.value_of("id").unwrap(),
                 download.value_of("output").unwrap(),
+                download.value_of("transcode"),
+                download.is_present("skip_existing"),
+            ) {
+                Ok(_) => (),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("frames", frames)) => {
+                match o.list_instance_frames(frames.value_of("id").unwrap()) {
+                    Ok(t) => print_table(t),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("frame", frame)) => match o.download_instance_frame(
+                frame.value_of("id").unwrap(),
+                frame.value_of("number").unwrap().parse::<u32>().unwrap(),
+                frame.is_present("raw"),
+                frame.value_of("output").unwrap(),
+            ) {
+                Ok(_) => (),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("extract-pdf", extract_pdf)) => match o.extract_instance_pdf(
+                extract_pdf.value_of("id").unwrap(),
+                extract_pdf.value_of("output").unwrap(),
             ) {
                 Ok(_) => (),
                 Err(e) => exit_with_error(e),
             },
+            Some(("extract-document", extract_document)) => {
+                match o.extract_instance_document(
+                    extract_document.value_of("id").unwrap(),
+                    None,
+                    extract_document.value_of("output").unwrap(),
+                ) {
+                    Ok(_) => (),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            Some(("stats", stats)) => match o.instance_stats(stats.value_of("id").unwrap())
+            {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("attachments", attachments)) => match o.list_attachments(
+                attachments.value_of("id").unwrap(),
+                attachments.is_present("no_header"),
+            ) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("attachment", attachment)) => run_attachment_command(&o, attachment),
             Some(("delete", delete)) => {
                 match o.delete_instance(delete.value_of("id").unwrap()) {
                     Ok(_) => (),
@@ -349,7 +640,11 @@ fn main() {
             },
             Some(("store", store)) => {
                 let ids: Vec<&str> = store.values_of("ids").unwrap().collect();
-                match o.do_store(store.value_of("name").unwrap(), &ids) {
+                match o.do_store(
+                    store.value_of("name").unwrap(),
+                    &ids,
+                    store.value_of("transcode"),
+                ) {
                     Ok(t) => print_table(t),
                     Err(e) => exit_with_error(e),
                 }
@@ -362,6 +657,309 @@ fn main() {
             }
             _ => {}
         },
+        Some(("download", download)) => {
+            match get_ids(download.values_of("ids").map(|i| i.collect())) {
+                Ok(ids) => match o.download_resources(
+                    ids,
+                    download.value_of("output"),
+                    DownloadOptions {
+                        media: download.is_present("media"),
+                        extract: download.value_of("extract").map(String::from),
+                        layout: None,
+                        transcode: download.value_of("transcode").map(String::from),
+                        skip_existing: download.is_present("skip_existing"),
+                    },
+                ) {
+                    Ok(_) => (),
+                    Err(e) => exit_with_error(e),
+                },
+                Err(e) => exit_with_error(e),
+            }
+        }
+        Some(("changes", changes)) => {
+            let since = changes.value_of("since").map(|s| s.parse::<u64>().unwrap());
+            let types = changes.values_of("type").map(|t| t.collect());
+            if changes.is_present("follow") {
+                let handler = match (changes.value_of("exec"), changes.value_of("post")) {
+                    (Some(c), _) => Some(ChangeHandler::Exec(c.to_string())),
+                    (_, Some(u)) => Some(ChangeHandler::Post(u.to_string())),
+                    _ => None,
+                };
+                let delivery = match handler {
+                    Some(h) => {
+                        match get_dead_letter_file(
+                            changes.value_of("dead_letter"),
+                            &server_address,
+                        ) {
+                            Ok(f) => Some(ChangeDelivery {
+                                handler: h,
+                                concurrency: changes
+                                    .value_of("concurrency")
+                                    .unwrap()
+                                    .parse()
+                                    .unwrap(),
+                                retries: changes
+                                    .value_of("retries")
+                                    .unwrap()
+                                    .parse()
+                                    .unwrap(),
+                                dead_letter_file: f,
+                            }),
+                            Err(e) => return exit_with_error(e),
+                        }
+                    }
+                    None => None,
+                };
+                if let Err(e) =
+                    o.follow_changes(since, types, changes.value_of("state_file"), delivery)
+                {
+                    exit_with_error(e)
+                }
+            } else {
+                match o.list_changes(since, types, changes.is_present("no_header")) {
+                    Ok(t) => print_table(t),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+        }
+        Some(("query", query)) => match query.subcommand() {
+            Some(("save", save)) => match o.save_query(
+                save.value_of("name").unwrap(),
+                save.value_of("level").unwrap(),
+                save.values_of("query").unwrap().collect(),
+                save.is_present("ignore_case"),
+                save.values_of("columns").map(|c| c.collect()),
+                save.values_of("sort").map(|s| s.collect()),
+            ) {
+                Ok(_) => (),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("list", list)) => match o.list_queries(list.is_present("no_header")) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("run", run)) => match o.run_query(
+                run.value_of("name").unwrap(),
+                run.values_of("param").map(|p| p.collect()),
+                run.is_present("no_header"),
+            ) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("delete", delete)) => {
+                match o.delete_query(delete.value_of("name").unwrap()) {
+                    Ok(_) => (),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+            _ => {}
+        },
+        Some(("system", system)) => match system.subcommand() {
+            Some(("info", _)) => match o.system_info() {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("stats", _)) => match o.system_stats() {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("verify", verify)) => match o.verify_all(
+                verify.value_of("concurrency").unwrap().parse().unwrap(),
+                verify.is_present("no_header"),
+            ) {
+                Ok((t, corrupted)) => print_report(t, corrupted),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("reconstruct-all", reconstruct_all)) => match o.reconstruct_all(
+                reconstruct_all.values_of("query").map(|q| q.collect()),
+                reconstruct_all
+                    .value_of("concurrency")
+                    .unwrap()
+                    .parse()
+                    .unwrap(),
+                reconstruct_all.value_of("state_file"),
+                reconstruct_all.is_present("no_header"),
+            ) {
+                Ok((t, failures)) => print_report(t, failures),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("check-main-tags", check_main_tags)) => match o.check_main_dicom_tags(
+                check_main_tags.value_of("level").unwrap(),
+                check_main_tags
+                    .value_of("concurrency")
+                    .unwrap()
+                    .parse()
+                    .unwrap(),
+                check_main_tags.is_present("no_header"),
+            ) {
+                Ok((t, stale)) => print_report(t, stale),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("log-level", log_level)) => match log_level.subcommand() {
+                Some(("get", get)) => match o.get_log_level(get.value_of("category")) {
+                    Ok(t) => print_table(t),
+                    Err(e) => exit_with_error(e),
+                },
+                Some(("set", set)) => match o
+                    .set_log_level(set.value_of("level").unwrap(), set.value_of("category"))
+                {
+                    Ok(_) => (),
+                    Err(e) => exit_with_error(e),
+                },
+                _ => {}
+            },
+            _ => {}
+        },
+        Some(("plugin", plugin)) => match plugin.subcommand() {
+            Some(("list", list)) => match o.list_plugins(list.is_present("no_header")) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            Some(("show", show)) => match o.show_plugin(show.value_of("id").unwrap()) {
+                Ok(t) => print_table(t),
+                Err(e) => exit_with_error(e),
+            },
+            _ => {}
+        },
+        Some(("tools", tools)) => {
+            if let Some(("exec-lua", exec_lua)) = tools.subcommand() {
+                match o.exec_lua(
+                    exec_lua.value_of("script").unwrap(),
+                    exec_lua.values_of("var").map(|v| v.collect()),
+                ) {
+                    Ok(output) => print!("{}", output),
+                    Err(e) => exit_with_error(e),
+                }
+            }
+        }
+        Some(("lookup", lookup)) => match o.lookup(lookup.value_of("value").unwrap()) {
+            Ok(t) => print_table(t),
+            Err(e) => exit_with_error(e),
+        },
         _ => {}
     }
 }
+
+fn run_metadata_command(o: &Orthanc, level: &str, metadata: &ArgMatches) {
+    match metadata.subcommand() {
+        Some(("list", list)) => match o.list_metadata(
+            level,
+            list.value_of("id").unwrap(),
+            list.is_present("no_header"),
+        ) {
+            Ok(t) => print_table(t),
+            Err(e) => exit_with_error(e),
+        },
+        Some(("get", get)) => {
+            match o.get_metadata(
+                level,
+                get.value_of("id").unwrap(),
+                get.value_of("name").unwrap(),
+            ) {
+                Ok(v) => println!("{}", v),
+                Err(e) => exit_with_error(e),
+            }
+        }
+        Some(("set", set)) => match o.set_metadata(
+            level,
+            set.value_of("id").unwrap(),
+            set.value_of("name").unwrap(),
+            set.value_of("value").unwrap(),
+        ) {
+            Ok(_) => (),
+            Err(e) => exit_with_error(e),
+        },
+        Some(("delete", delete)) => match o.delete_metadata(
+            level,
+            delete.value_of("id").unwrap(),
+            delete.value_of("name").unwrap(),
+        ) {
+            Ok(_) => (),
+            Err(e) => exit_with_error(e),
+        },
+        _ => {}
+    }
+}
+
+fn run_label_command(o: &Orthanc, level: &str, label: &ArgMatches) {
+    let result = match label.subcommand() {
+        Some(("list", list)) => match o.list_labels(level, list.value_of("id").unwrap()) {
+            Ok(labels) => {
+                labels.iter().for_each(|l| println!("{}", l));
+                Ok(())
+            }
+            Err(e) => Err(e),
+        },
+        Some(("add", add)) => {
+            get_ids(add.values_of("ids").map(|i| i.collect())).and_then(|ids| {
+                o.label_entities(level, add.value_of("label").unwrap(), ids, true)
+            })
+        }
+        Some(("remove", remove)) => get_ids(remove.values_of("ids").map(|i| i.collect()))
+            .and_then(|ids| {
+                o.label_entities(level, remove.value_of("label").unwrap(), ids, false)
+            }),
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        exit_with_error(e)
+    }
+}
+
+fn get_download_options(download: &ArgMatches) -> DownloadOptions {
+    DownloadOptions {
+        media: download.is_present("media"),
+        extract: download.value_of("extract").map(String::from),
+        layout: download.value_of("layout").map(String::from),
+        transcode: download.value_of("transcode").map(String::from),
+        skip_existing: download.is_present("skip_existing"),
+    }
+}
+
+/// Search options of a `search` subcommand. With `--exists`, one result is enough.
+fn get_search_options(search: &ArgMatches) -> SearchOptions {
+    let labels = [
+        ("label", "All"),
+        ("label_any", "Any"),
+        ("label_none", "None"),
+    ]
+    .iter()
+    .find(|(arg, _)| search.is_present(arg));
+    SearchOptions {
+        ignore_case: search.is_present("ignore_case"),
+        limit: if search.is_present("exists") {
+            Some(1)
+        } else {
+            search.value_of("limit").map(|l| l.parse::<u64>().unwrap())
+        },
+        labels: labels
+            .map(|(arg, _)| search.values_of(arg).unwrap().map(String::from).collect()),
+        labels_constraint: labels.map(|(_, constraint)| constraint.to_string()),
+    }
+}
+
+fn run_attachment_command(o: &Orthanc, attachment: &ArgMatches) {
+    let result = match attachment.subcommand() {
+        Some(("verify", verify)) => o.verify_attachment(
+            verify.value_of("id").unwrap(),
+            verify.value_of("name").unwrap(),
+        ),
+        Some(("compress", compress)) => o.compress_attachment(
+            compress.value_of("id").unwrap(),
+            compress.value_of("name").unwrap(),
+        ),
+        Some(("uncompress", uncompress)) => o.uncompress_attachment(
+            uncompress.value_of("id").unwrap(),
+            uncompress.value_of("name").unwrap(),
+        ),
+        Some(("download", download)) => o.download_attachment(
+            download.value_of("id").unwrap(),
+            download.value_of("name").unwrap(),
+            download.value_of("output").unwrap(),
+        ),
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        exit_with_error(e)
+    }
+}
@@ -1,11 +1,18 @@
 use crate::constants::*;
 use crate::{CliError, Result};
+use chrono::{Datelike, Duration, NaiveDate};
 use comfy_table::{ColumnConstraint, ContentArrangement, Table};
+use orthanc::client::Client;
 use orthanc::entity::*;
 use orthanc::models::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use serde_yaml;
-use std::collections::HashMap;
-use std::{env, fs, process, result};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::{env, fs, io, process, result, thread, time};
 
 pub fn create_table(header: Option<&[&str]>) -> Table {
     let mut table = Table::new();
@@ -18,10 +25,13 @@ pub fn create_table(header: Option<&[&str]>) -> Table {
     table
 }
 
+/// `extra_values` holds, by entity ID, the values of the columns that don't come from the entity
+/// itself (e.g. `Protected`). These columns are always the last ones.
 pub fn create_list_table<T: Entity>(
     entities: Vec<T>,
     columns: &[&str],
     dicom_tags: &[&str],
+    extra_values: &HashMap<String, Vec<String>>,
     no_header: bool,
 ) -> Table {
     let header = if no_header { None } else { Some(columns) };
@@ -64,6 +74,9 @@ pub fn create_list_table<T: Entity>(
                 }
             }
         }
+        if let Some(values) = extra_values.get(entity.id()) {
+            row.extend(values.iter().cloned());
+        }
         table.add_row(row.iter());
     }
 
@@ -134,762 +147,3896 @@ pub fn create_new_entity_table(result: ModificationResult) -> Table {
     table
 }
 
-pub fn create_error_table(error: CliError) -> Table {
+pub fn create_new_instance_table(
+    instance_id: &str,
+    series_id: &str,
+    study_id: &str,
+    patient_id: &str,
+) -> Table {
     let mut table = create_table(None);
-    table.add_row(["Error", &error.error].iter());
-    match error.message {
-        Some(m) => {
-            table.add_row(["Message", &m].iter());
-        }
-        None => (),
-    };
-    match error.details {
-        Some(d) => {
-            table.add_row(["Details", &d].iter());
-        }
-        None => (),
-    };
+    table.add_row(["New Instance ID", instance_id].iter());
+    table.add_row(["Series ID", series_id].iter());
+    table.add_row(["Study ID", study_id].iter());
+    table.add_row(["Patient ID", patient_id].iter());
     table
 }
 
-/// Parses the command-line option value(s) TagName=TagValue into a HashMap
-pub fn parse_tag_kv_pairs(cmd_values: Vec<&str>) -> Result<HashMap<String, String>> {
-    let mut map = HashMap::new();
-    for v in cmd_values {
-        let tag_kv_pair: Vec<&str> = v.split("=").collect();
-        if tag_kv_pair.len() != 2 {
-            return Err(CliError::new(
-                "Command error",
-                Some(&format!("Wrong option value '{}'", v)),
-                Some("Must be of format 'TagName=TagValue'"),
-            ));
-        }
-        map.insert(tag_kv_pair[0].to_string(), tag_kv_pair[1].to_string());
+pub fn create_frames_table(frames: &[u32], tags: &Value) -> Table {
+    let mut table = create_table(None);
+    table.add_row(["Number of frames", &format!("{}", frames.len())].iter());
+    for t in FRAME_DICOM_TAGS.iter() {
+        table.add_row(
+            [
+                t,
+                tags.get(t)
+                    .and_then(Value::as_str)
+                    .unwrap_or(ABSENT_DICOM_TAG_PLACEHOLDER),
+            ]
+            .iter(),
+        );
     }
-    Ok(map)
+    table
 }
 
-pub fn get_anonymization_config(
-    replace: Option<Vec<&str>>,
-    keep: Option<Vec<&str>>,
-    keep_private_tags: Option<bool>,
-    config_file: Option<&str>,
-) -> Result<Option<Anonymization>> {
-    // This should never happen, but double-checking anyway
-    if (replace.is_some() || keep.is_some() || keep_private_tags.is_some())
-        && config_file.is_some()
-    {
-        return Err(CliError::new(
+/// Builds the JSON sidecar describing the pixel data of a frame out of the instance's
+/// simplified DICOM tags. Tags absent from the instance are set to null.
+pub fn create_frame_sidecar(frame: u32, tags: &Value) -> Value {
+    let mut sidecar = Map::new();
+    sidecar.insert("Frame".to_string(), Value::from(frame));
+    for t in FRAME_SIDECAR_DICOM_TAGS.iter() {
+        let val = match tags.get(t).and_then(Value::as_str).map(str::trim) {
+            Some(v) => match *t {
+                "Rows" | "Columns" | "BitsAllocated" => v
+                    .parse::<u64>()
+                    .map(Value::from)
+                    .unwrap_or_else(|_| Value::from(v)),
+                "RescaleSlope" | "RescaleIntercept" => v
+                    .parse::<f64>()
+                    .map(Value::from)
+                    .unwrap_or_else(|_| Value::from(v)),
+                _ => Value::from(v),
+            },
+            None => Value::Null,
+        };
+        sidecar.insert(t.to_string(), val);
+    }
+    Value::Object(sidecar)
+}
+
+/// Returns the type of the document encapsulated in an instance (PDF, CDA etc.), based on the
+/// instance's SOP Class UID. If `expected_type` is set, the document must be of that type.
+pub fn get_encapsulated_document_type(
+    tags: &Value,
+    expected_type: Option<&str>,
+) -> Result<&'static str> {
+    let sop_class_uid = tags
+        .get("SOPClassUID")
+        .and_then(Value::as_str)
+        .unwrap_or(ABSENT_DICOM_TAG_PLACEHOLDER);
+    let document_type = ENCAPSULATED_DOCUMENT_SOP_CLASSES
+        .iter()
+        .find(|(uid, _)| *uid == sop_class_uid)
+        .map(|(_, t)| *t);
+
+    match (document_type, expected_type) {
+        (None, _) => Err(CliError::new(
             "Command error",
-            Some("Conflicting options"),
-            None,
-        ));
+            Some("Instance holds no encapsulated document"),
+            Some(&format!("SOPClassUID: {}", sop_class_uid)),
+        )),
+        (Some(d), Some(e)) if d != e => Err(CliError::new(
+            "Command error",
+            Some(&format!(
+                "Instance holds an encapsulated {} document, not {}",
+                d, e
+            )),
+            Some(&format!("SOPClassUID: {}", sop_class_uid)),
+        )),
+        (Some(d), _) => Ok(d),
     }
+}
 
-    match config_file {
-        Some(c) => Ok(Some(get_anonymization_config_from_file(c)?)),
-        None => match (&replace, &keep, &keep_private_tags) {
-            (None, None, None) => Ok(None),
-            // TODO: This assumes that there is always either a config file
-            // or at least one of the options
-            _ => Ok(Some(get_anonymization_config_from_cmd_options(
-                replace,
-                keep,
-                keep_private_tags,
-            )?)),
-        },
+pub fn create_lookup_table(entities: &[(EntityKind, String)]) -> Table {
+    let mut table = create_table(None);
+    for (kind, id) in entities {
+        table.add_row([&format!("{:?}", kind), id].iter());
     }
+    table
 }
 
-pub fn get_modification_config(
-    replace: Option<Vec<&str>>,
-    remove: Option<Vec<&str>>,
-    config_file: Option<&str>,
-) -> Result<Modification> {
-    // This should never happen, but double-checking anyway
-    if replace.is_none() && remove.is_none() && config_file.is_none() {
-        return Err(CliError::new(
-            "Command error",
-            Some("Not enough options"),
-            None,
-        ));
+pub fn create_system_info_table(system: System, plugins: &[String]) -> Table {
+    let mut table = create_table(None);
+    table.add_row(["Name", &system.name].iter());
+    table.add_row(["Version", &system.version].iter());
+    table.add_row(["API version", &format!("{}", system.api_version)].iter());
+    table.add_row(["DICOM AET", &system.dicom_aet].iter());
+    table.add_row(["DICOM port", &format!("{}", system.dicom_port)].iter());
+    table.add_row(["HTTP port", &format!("{}", system.http_port)].iter());
+    table.add_row(["HTTPS", &format!("{}", system.is_http_server_secure)].iter());
+    table.add_row(
+        [
+            "Database backend",
+            system
+                .database_backend_plugin
+                .as_deref()
+                .unwrap_or(BUILTIN_DATABASE_BACKEND),
+        ]
+        .iter(),
+    );
+    table.add_row(["Database version", &format!("{}", system.database_version)].iter());
+    table.add_row(
+        [
+            "Storage area",
+            system
+                .storage_area_plugin
+                .as_deref()
+                .unwrap_or(BUILTIN_STORAGE_AREA),
+        ]
+        .iter(),
+    );
+    let plugins = if system.plugins_enabled {
+        plugins.join(", ")
+    } else {
+        "disabled".to_string()
+    };
+    table.add_row(["Plugins", &plugins].iter());
+    table
+}
+
+pub fn create_system_stats_table(stats: Statistics) -> Table {
+    let mut table = create_table(None);
+    table.add_row(["Patients", &format!("{}", stats.count_patients)].iter());
+    table.add_row(["Studies", &format!("{}", stats.count_studies)].iter());
+    table.add_row(["Series", &format!("{}", stats.count_series)].iter());
+    table.add_row(["Instances", &format!("{}", stats.count_instances)].iter());
+    table.add_row(["Disk size", &format_size(stats.total_disk_size)].iter());
+    table.add_row(
+        [
+            "Uncompressed size",
+            &format_size(stats.total_uncompressed_size),
+        ]
+        .iter(),
+    );
+    table
+}
+
+pub fn create_entity_stats_table(stats: EntityStatistics) -> Table {
+    let mut table = create_table(None);
+    if let Some(c) = stats.count_studies {
+        table.add_row(["Studies", &format!("{}", c)].iter());
     }
-    if (replace.is_some() || remove.is_some()) && config_file.is_some() {
-        return Err(CliError::new(
-            "Command error",
-            Some("Conflicting options"),
-            None,
-        ));
+    if let Some(c) = stats.count_series {
+        table.add_row(["Series", &format!("{}", c)].iter());
     }
+    table.add_row(["Instances", &format!("{}", stats.count_instances)].iter());
+    table.add_row(["Disk size", &format_size(stats.disk_size)].iter());
+    table.add_row(["Uncompressed size", &format_size(stats.uncompressed_size)].iter());
+    table
+}
 
-    match config_file {
-        Some(c) => Ok(get_modification_config_from_file(c)?),
-        None => match (&replace, &remove) {
-            // TODO: This assumes that there is always either a config file
-            // or at least one of the options
-            _ => Ok(get_modification_config_from_cmd_options(replace, remove)?),
-        },
+pub fn create_plugins_table(plugins: &[Plugin], no_header: bool) -> Table {
+    let header = if no_header {
+        None
+    } else {
+        Some(PLUGINS_LIST_HEADER)
+    };
+    let mut table = create_table(header);
+    for p in plugins {
+        table.add_row(
+            [
+                &p.id,
+                &p.version,
+                &format!("{}", p.extended_explorer),
+                p.description.as_deref().unwrap_or_default(),
+            ]
+            .iter(),
+        );
     }
+    table
 }
 
-fn get_anonymization_config_from_file(config_file: &str) -> Result<Anonymization> {
-    let yaml = fs::read(config_file)?;
-    let mut a: Anonymization = serde_yaml::from_slice(&yaml)?;
-    a.force = Some(true);
-    Ok(a)
+pub fn create_plugin_table(plugin: Plugin) -> Table {
+    let mut table = create_table(None);
+    table.add_row(["ID", &plugin.id].iter());
+    table.add_row(["Version", &plugin.version].iter());
+    table.add_row(
+        [
+            "Description",
+            plugin.description.as_deref().unwrap_or_default(),
+        ]
+        .iter(),
+    );
+    table.add_row(
+        [
+            "Explorer extension",
+            &format!("{}", plugin.extended_explorer),
+        ]
+        .iter(),
+    );
+    if let Some(uri) = &plugin.root_uri {
+        table.add_row(["Root URI", uri].iter());
+    }
+    if let Some((_, features)) = KNOWN_PLUGIN_FEATURES
+        .iter()
+        .find(|(id, _)| *id == plugin.id)
+    {
+        table.add_row(["CLI features", features].iter());
+    }
+    table
 }
 
-fn get_modification_config_from_file(config_file: &str) -> Result<Modification> {
-    let yaml = fs::read(config_file)?;
-    let mut a: Modification = serde_yaml::from_slice(&yaml)?;
-    a.force = Some(true);
-    Ok(a)
+pub fn create_metadata_table(metadata: HashMap<String, String>, no_header: bool) -> Table {
+    let header = if no_header {
+        None
+    } else {
+        Some(METADATA_LIST_HEADER)
+    };
+    let mut table = create_table(header);
+    let metadata: BTreeMap<String, String> = metadata.into_iter().collect();
+    for (name, value) in metadata {
+        table.add_row([name, value].iter());
+    }
+    table
 }
 
-fn get_anonymization_config_from_cmd_options(
-    replace: Option<Vec<&str>>,
-    keep: Option<Vec<&str>>,
-    keep_private_tags: Option<bool>,
-) -> Result<Anonymization> {
-    Ok(Anonymization {
-        replace: match replace {
-            Some(r) => Some(parse_tag_kv_pairs(r)?),
-            None => None,
-        },
-        keep: keep.map(|vec| vec.iter().map(ToString::to_string).collect()),
-        keep_private_tags,
-        dicom_version: None,
-        force: Some(true),
-    })
+pub fn get_entity_kind(level: &str) -> EntityKind {
+    match level {
+        "patient" => EntityKind::Patient,
+        "study" => EntityKind::Study,
+        "series" => EntityKind::Series,
+        _ => EntityKind::Instance,
+    }
 }
 
-fn get_modification_config_from_cmd_options(
-    replace: Option<Vec<&str>>,
-    remove: Option<Vec<&str>>,
-) -> Result<Modification> {
-    Ok(Modification {
-        replace: match replace {
-            Some(r) => Some(parse_tag_kv_pairs(r)?),
-            None => None,
-        },
-        remove: remove.map(|vec| vec.iter().map(ToString::to_string).collect()),
-        force: Some(true),
-    })
+pub fn create_attachments_table(
+    attachments: &[(String, AttachmentInfo)],
+    no_header: bool,
+) -> Table {
+    let header = if no_header {
+        None
+    } else {
+        Some(ATTACHMENTS_LIST_HEADER)
+    };
+    let mut table = create_table(header);
+    for (name, info) in attachments {
+        table.add_row(
+            [
+                name,
+                &format!("{}", info.uncompressed_size),
+                &format!("{}", info.compressed_size),
+                &info.uncompressed_md5,
+                &info.uuid,
+            ]
+            .iter(),
+        );
+    }
+    table
 }
 
-pub fn get_server_address(cmd_option: Option<&str>) -> result::Result<String, CliError> {
-    match cmd_option {
-        Some(s) => Ok(s.to_string()),
-        None => match env::var("ORC_ORTHANC_SERVER") {
-            Ok(s) => Ok(s),
-            Err(e) => Err(CliError::new(
-                "Command error",
-                Some("Neither --server nor ORC_ORTHANC_SERVER are set"),
-                Some(&format!("{}", e)),
-            )),
-        },
+/// `failures` are (instance ID, attachment name, error) tuples
+pub fn create_verification_table(
+    failures: &[(String, String, String)],
+    no_header: bool,
+) -> Table {
+    let header = if no_header {
+        None
+    } else {
+        Some(VERIFICATION_REPORT_HEADER)
+    };
+    let mut table = create_table(header);
+    for (id, name, error) in failures {
+        table.add_row([id, name, error].iter());
     }
+    table
 }
 
-pub fn get_username(cmd_option: Option<&str>) -> Option<String> {
-    match cmd_option {
-        Some(s) => Some(s.to_string()),
-        None => match env::var("ORC_ORTHANC_USERNAME") {
-            Ok(s) => Some(s),
-            Err(_) => None, // TODO: This will hide the error
-        },
+/// `rows` are (entity ID, issue) pairs
+pub fn create_report_table(
+    header: &[&str],
+    rows: &[(String, String)],
+    no_header: bool,
+) -> Table {
+    let mut table = create_table(if no_header { None } else { Some(header) });
+    for (id, issue) in rows {
+        table.add_row([id, issue].iter());
     }
+    table
 }
 
-pub fn get_password(cmd_option: Option<&str>) -> Option<String> {
-    match cmd_option {
-        Some(s) => Some(s.to_string()),
-        None => match env::var("ORC_ORTHANC_PASSWORD") {
-            Ok(s) => Some(s),
-            Err(_) => None, // TODO: This will hide the error
-        },
+/// Prints the issues a command found (corrupted files, failures...), and exits with 1 if there
+/// are any
+pub fn print_report(table: Table, issues: usize) {
+    print_table(table);
+    if issues > 0 {
+        process::exit(1);
     }
 }
 
-pub fn get_iap_client_id(cmd_option: Option<&str>) -> Option<String> {
-    match cmd_option {
-        Some(s) => Some(s.to_string()),
-        None => match env::var("IAP_CLIENT_ID") {
-            Ok(s) => Some(s),
-            Err(_) => None, // TODO: This will hide the error
-        },
+/// Main DICOM tags of the server configuration missing from the signature an entity was
+/// indexed with, or `None` if there are none. The signatures are `;`-separated lists of tag
+/// numbers, e.g. `0010,0010;0010,0020`. Entities stored before Orthanc 1.11.0 have no
+/// signature, so which tags they are missing is unknown.
+pub fn get_missing_main_dicom_tags(
+    signature: Option<&str>,
+    expected: &str,
+) -> Option<String> {
+    let signature = match signature {
+        Some(s) => s,
+        None => return Some("Unknown, stored before Orthanc 1.11.0".to_string()),
+    };
+    let indexed: HashSet<&str> = signature.split(';').collect();
+    let missing: Vec<&str> = expected
+        .split(';')
+        .filter(|t| !t.is_empty() && !indexed.contains(t))
+        .collect();
+    if missing.is_empty() {
+        None
+    } else {
+        Some(missing.join(", "))
     }
 }
 
-pub fn get_google_application_credentials(cmd_option: Option<&str>) -> Option<String> {
-    match cmd_option {
-        Some(s) => Some(s.to_string()),
-        None => match env::var("GOOGLE_APPLICATION_CREDENTIALS") {
-            Ok(s) => Some(s),
-            Err(_) => None, // TODO: This will hide the error
-        },
+/// IDs of the entities already reconstructed by an interrupted `reconstruct-all`
+pub fn read_reconstruction_state(path: &Path) -> Result<HashSet<String>> {
+    if !path.exists() {
+        return Ok(HashSet::new());
     }
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .map(String::from)
+        .collect())
 }
 
-pub fn check_columns_option(
-    original_header: &[&str],
-    requested_columns: &[&str],
-) -> Result<()> {
-    for c in requested_columns {
-        if !original_header.contains(c) {
-            return Err(CliError::new(
-                "Command error",
-                Some(&format!(
-                    "Invalid column name: {}. Available columns: {}",
-                    c,
-                    original_header.join(", ")
-                )),
-                None,
-            ));
-        }
+pub fn append_reconstruction_state(path: &Path, id: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
     }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", id)?;
     Ok(())
 }
 
-pub fn get_header_and_dicom_tags(
-    header: &mut Vec<&str>,
-    dicom_tags: &mut Vec<&str>,
-    columns: Option<Vec<&str>>,
+pub fn create_log_levels_table(levels: &[(&str, String)]) -> Table {
+    let mut table = create_table(None);
+    for (category, level) in levels {
+        table.add_row([category, level.as_str()].iter());
+    }
+    table
+}
+
+/// Fails if the server version is older than the version a feature requires. Development
+/// builds ("mainline") are considered recent enough.
+pub fn check_server_version(
+    server_version: &str,
+    required: &str,
+    feature: &str,
 ) -> Result<()> {
-    if let Some(c) = columns {
-        check_columns_option(header, &c)?;
-        header.retain(|v| c.contains(v));
-        dicom_tags.retain(|v| c.contains(v));
-    };
-    Ok(())
+    let parse =
+        |v: &str| -> Vec<u32> { v.split('.').map(|p| p.parse().unwrap_or(0)).collect() };
+    if server_version == "mainline" || parse(server_version) >= parse(required) {
+        Ok(())
+    } else {
+        Err(CliError::new(
+            "Command error",
+            Some(&format!(
+                "{} requires Orthanc {} or later",
+                feature, required
+            )),
+            Some(&format!("Server version: {}", server_version)),
+        ))
+    }
 }
 
-pub fn print_table(table: Table) {
-    println!("{}", table);
+/// Formats a number of bytes along with its value in MB, the way Orthanc reports it
+pub fn format_size(bytes: u64) -> String {
+    format!("{} ({} MB)", bytes, bytes / 1024 / 1024)
 }
 
-pub fn exit_with_error(error: CliError) {
-    let output = create_error_table(error);
-    eprintln!("{}", output);
-    process::exit(1);
+pub fn create_tree_node<T: Entity>(entity: &T, children: Vec<Value>) -> Value {
+    let mut node = create_tree_node_tags(entity);
+    match T::kind() {
+        EntityKind::Instance => {
+            node.insert(TREE_SIZE_KEY.to_string(), Value::from(entity.size()));
+        }
+        _ => {
+            let children_kind_name = entity.children_kind_name().unwrap();
+            // Counts of the lower levels are summed up from the children
+            let mut counts: Map<String, Value> = Map::new();
+            counts.insert(
+                format!("Count{}", children_kind_name),
+                Value::from(children.len()),
+            );
+            let mut size = 0;
+            for c in children.iter() {
+                size += c[TREE_SIZE_KEY].as_u64().unwrap_or(0);
+                for k in TREE_CHILDREN_KEYS.iter() {
+                    let count_key = format!("Count{}", k);
+                    if let Some(n) = c[&count_key].as_u64() {
+                        let total =
+                            counts.get(&count_key).and_then(Value::as_u64).unwrap_or(0);
+                        counts.insert(count_key, Value::from(total + n));
+                    }
+                }
+            }
+            node.extend(counts);
+            node.insert(TREE_SIZE_KEY.to_string(), Value::from(size));
+            node.insert(children_kind_name.to_string(), Value::Array(children));
+        }
+    }
+    Value::Object(node)
+}
+
+/// Tree node without children, for the entities at the depth the tree stops at. Counts and size
+/// come from the statistics of the entity, instead of being summed up from its children.
+pub fn create_tree_leaf<T: Entity>(entity: &T, statistics: EntityStatistics) -> Value {
+    let mut node = create_tree_node_tags(entity);
+    let counts = [
+        ("Studies", statistics.count_studies),
+        ("Series", statistics.count_series),
+        ("Instances", Some(statistics.count_instances)),
+    ];
+    for (k, count) in counts.iter() {
+        if let Some(c) = count {
+            node.insert(format!("Count{}", k), Value::from(*c));
+        }
+    }
+    node.insert(
+        TREE_SIZE_KEY.to_string(),
+        Value::from(statistics.uncompressed_size),
+    );
+    Value::Object(node)
+}
+
+/// Whether the tree stops at the level of an entity, i.e. its children aren't fetched
+pub fn is_tree_leaf(kind: EntityKind, depth: Option<&str>) -> bool {
+    match depth {
+        Some(d) => get_level_index(&format!("{:?}", kind)) >= get_level_index(d),
+        None => false,
+    }
+}
+
+fn create_tree_node_tags<T: Entity>(entity: &T) -> Map<String, Value> {
+    let level = format!("{:?}", T::kind());
+    let mut node = Map::new();
+    let mut tags = Map::new();
+    if let Some((_, dicom_tags)) = TREE_DICOM_TAGS.iter().find(|(l, _)| *l == level) {
+        for t in dicom_tags.iter() {
+            tags.insert(
+                t.to_string(),
+                entity.main_dicom_tag(t).map_or(Value::Null, Value::from),
+            );
+        }
+    }
+    node.insert("ID".to_string(), Value::from(entity.id()));
+    node.insert("Level".to_string(), Value::from(level));
+    node.insert("MainDicomTags".to_string(), Value::Object(tags));
+    node
+}
+
+pub fn render_tree(tree: Value, json: bool) -> Result<String> {
+    if json {
+        Ok(serde_json::to_string_pretty(&tree)?)
+    } else {
+        Ok(format_tree(&tree))
+    }
+}
+
+pub fn format_tree(node: &Value) -> String {
+    let mut lines = vec![];
+    format_tree_node(node, 0, &mut lines);
+    lines.join("\n")
+}
+
+fn format_tree_node(node: &Value, indent: usize, lines: &mut Vec<String>) {
+    let level = node["Level"].as_str().unwrap_or_default();
+    let mut fields = vec![format!(
+        "{}{} {}",
+        "  ".repeat(indent),
+        level,
+        node["ID"].as_str().unwrap_or_default()
+    )];
+    if let Some((_, dicom_tags)) = TREE_DICOM_TAGS.iter().find(|(l, _)| *l == level) {
+        for t in dicom_tags.iter() {
+            fields.push(
+                node["MainDicomTags"][*t]
+                    .as_str()
+                    .unwrap_or(ABSENT_DICOM_TAG_PLACEHOLDER)
+                    .to_string(),
+            );
+        }
+    }
+    let mut summary = vec![];
+    for k in TREE_CHILDREN_KEYS.iter() {
+        if let Some(n) = node[&format!("Count{}", k)].as_u64() {
+            summary.push(format!("{}: {}", k, n));
+        }
+    }
+    summary.push(format!(
+        "Size: {}",
+        node[TREE_SIZE_KEY].as_u64().unwrap_or(0)
+    ));
+    fields.push(format!("({})", summary.join(", ")));
+    lines.push(fields.join("   "));
+
+    for k in TREE_CHILDREN_KEYS.iter() {
+        if let Value::Array(children) = &node[*k] {
+            for c in children.iter() {
+                format_tree_node(c, indent + 1, lines);
+            }
+        }
+    }
+}
+
+fn get_level_index(level: &str) -> usize {
+    ENTITY_LEVELS
+        .iter()
+        .position(|l| l.eq_ignore_ascii_case(level))
+        .unwrap_or(ENTITY_LEVELS.len())
+}
+
+pub fn create_saved_queries_table(
+    queries: BTreeMap<String, SavedQuery>,
+    no_header: bool,
+) -> Table {
+    let header = if no_header {
+        None
+    } else {
+        Some(SAVED_QUERIES_LIST_HEADER)
+    };
+    let mut table = create_table(header);
+    for (name, q) in queries {
+        table.add_row(
+            [
+                name,
+                q.level,
+                q.query.join(" "),
+                q.columns.map(|c| c.join(" ")).unwrap_or_default(),
+                q.sort.map(|s| s.join(" ")).unwrap_or_default(),
+            ]
+            .iter(),
+        );
+    }
+    table
+}
+
+pub fn create_changes_table(changes: &[Change], no_header: bool) -> Table {
+    let header = if no_header {
+        None
+    } else {
+        Some(CHANGES_LIST_HEADER)
+    };
+    let mut table = create_table(header);
+    for c in changes {
+        table.add_row(get_change_row(c).iter());
+    }
+    table
+}
+
+/// Single change formatted as a table row, for printing changes as they come
+pub fn format_change(change: &Change) -> String {
+    let mut table = create_table(None);
+    table.add_row(get_change_row(change).iter());
+    table.to_string()
+}
+
+fn get_change_row(change: &Change) -> Vec<String> {
+    vec![
+        change.seq.to_string(),
+        change.change_type.clone(),
+        change.resource_type.clone(),
+        change.id.clone(),
+        change.date.clone(),
+    ]
+}
+
+pub fn create_error_table(error: CliError) -> Table {
+    let mut table = create_table(None);
+    table.add_row(["Error", &error.error].iter());
+    match error.message {
+        Some(m) => {
+            table.add_row(["Message", &m].iter());
+        }
+        None => (),
+    };
+    match error.details {
+        Some(d) => {
+            table.add_row(["Details", &d].iter());
+        }
+        None => (),
+    };
+    table
+}
+
+/// Parses the command-line option value(s) TagName=TagValue into a HashMap
+pub fn parse_tag_kv_pairs(cmd_values: Vec<&str>) -> Result<HashMap<String, String>> {
+    let mut map = HashMap::new();
+    for v in cmd_values {
+        // Values may contain "=" themselves
+        let tag_kv_pair: Vec<&str> = v.splitn(2, '=').collect();
+        if tag_kv_pair.len() != 2 || tag_kv_pair[0].is_empty() {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!("Wrong option value '{}'", v)),
+                Some("Must be of format 'TagName=TagValue'"),
+            ));
+        }
+        map.insert(tag_kv_pair[0].to_string(), tag_kv_pair[1].to_string());
+    }
+    Ok(map)
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub enum EntityId<'a> {
+    Plain(&'a str),
+    DicomUid(&'a str),
+    AccessionNumber(&'a str),
+    PatientId(&'a str),
+}
+
+pub fn parse_entity_id(id: &str) -> EntityId<'_> {
+    if let Some(uid) = id.strip_prefix(ID_PREFIX_DICOM_UID) {
+        EntityId::DicomUid(uid)
+    } else if let Some(accession_number) = id.strip_prefix(ID_PREFIX_ACCESSION_NUMBER) {
+        EntityId::AccessionNumber(accession_number)
+    } else if let Some(patient_id) = id.strip_prefix(ID_PREFIX_PATIENT_ID) {
+        EntityId::PatientId(patient_id)
+    } else {
+        EntityId::Plain(id)
+    }
+}
+
+/// Builds a find query out of structured search terms. Besides `TagName=TagValue` (exact match,
+/// wildcards or DICOM date ranges), `TagName>=TagValue` and `TagName<=TagValue` define open date
+/// ranges (with dates possibly relative to `today`, e.g. `-7d`) and `TagName in (A,B)` matches any
+/// of the listed values. Tag names can be prefixed with the search level or one of its parents
+/// (e.g. `Study.StudyDate`). A lower and an upper bound on the same tag make a closed range.
+pub fn parse_search_query(
+    terms: Vec<&str>,
+    level: EntityKind,
+    today: NaiveDate,
+) -> Result<HashMap<String, String>> {
+    let mut map: HashMap<String, String> = HashMap::new();
+    for t in terms {
+        let (tag, mut value) = parse_search_term(t, &level, today)?;
+        if let Some(previous) = map.get(&tag) {
+            value = merge_range_bounds(previous, &value).ok_or_else(|| {
+                CliError::new(
+                    "Command error",
+                    Some(&format!("Duplicate search term on tag '{}'", tag)),
+                    Some("A tag can only be searched on once, or with one >= and one <= bound"),
+                )
+            })?;
+            if is_date_tag(&tag) {
+                check_date_value(&value)?;
+            }
+        }
+        map.insert(tag, value);
+    }
+    Ok(map)
+}
+
+/// Range `X-Y` made of an `X-` lower bound and a `-Y` upper bound, given in any order
+fn merge_range_bounds(a: &str, b: &str) -> Option<String> {
+    fn lower(v: &str) -> Option<&str> {
+        v.strip_suffix('-')
+            .filter(|l| !l.is_empty() && !l.contains('-'))
+    }
+    fn upper(v: &str) -> Option<&str> {
+        v.strip_prefix('-')
+            .filter(|u| !u.is_empty() && !u.contains('-'))
+    }
+    match (lower(a), upper(b), lower(b), upper(a)) {
+        (Some(l), Some(u), _, _) | (_, _, Some(l), Some(u)) => Some(format!("{}-{}", l, u)),
+        _ => None,
+    }
+}
+
+fn parse_search_term(
+    term: &str,
+    level: &EntityKind,
+    today: NaiveDate,
+) -> Result<(String, String)> {
+    if let Some((tag, values)) = term.split_once(" in ").filter(|(t, _)| !t.contains('=')) {
+        let values: Vec<&str> = values
+            .trim()
+            .strip_prefix('(')
+            .and_then(|v| v.strip_suffix(')'))
+            .ok_or_else(|| wrong_search_term(term))?
+            .split(',')
+            .map(str::trim)
+            .collect();
+        if values.iter().any(|v| v.is_empty()) {
+            return Err(wrong_search_term(term));
+        }
+        return Ok((
+            parse_search_tag(tag.trim(), term, level)?,
+            values.join("\\"),
+        ));
+    }
+
+    let (tag, value) = term
+        .split_once('=')
+        .ok_or_else(|| wrong_search_term(term))?;
+    let (tag, value) = if let Some(t) = tag.strip_suffix('>') {
+        (t, format!("{}-", get_range_bound(t, value, today)?))
+    } else if let Some(t) = tag.strip_suffix('<') {
+        (t, format!("-{}", get_range_bound(t, value, today)?))
+    } else {
+        if is_date_tag(tag) {
+            check_date_value(value)?;
+        }
+        (tag, value.to_string())
+    };
+    Ok((parse_search_tag(tag, term, level)?, value))
+}
+
+fn wrong_search_term(term: &str) -> CliError {
+    CliError::new(
+        "Command error",
+        Some(&format!("Wrong option value '{}'", term)),
+        Some(SEARCH_TERM_FORMAT),
+    )
+}
+
+/// Strips the level prefix off the tag name, checking that the level is the search level or one
+/// of its parents
+fn parse_search_tag(tag: &str, term: &str, level: &EntityKind) -> Result<String> {
+    let (tag_level, tag_name) = tag.split_once('.').unwrap_or(("", tag));
+    if tag_name.is_empty() {
+        return Err(wrong_search_term(term));
+    }
+    let search_level = format!("{:?}", level);
+    if !tag_level.is_empty() && get_level_index(tag_level) > get_level_index(&search_level)
+    {
+        return Err(CliError::new(
+            "Command error",
+            Some(&format!("Invalid level in search term '{}'", term)),
+            Some(&format!(
+                "Only {} and its parent levels can be searched on",
+                search_level
+            )),
+        ));
+    }
+    Ok(tag_name.to_string())
+}
+
+fn get_range_bound(tag: &str, value: &str, today: NaiveDate) -> Result<String> {
+    if is_date_tag(tag) {
+        Ok(get_date(value, today)?
+            .format(DICOM_DATE_FORMAT)
+            .to_string())
+    } else if tag.ends_with("Time")
+        && !value.is_empty()
+        && value.chars().all(|c| c.is_ascii_digit())
+    {
+        Ok(value.to_string())
+    } else {
+        Err(CliError::new(
+            "Command error",
+            Some(&format!("Invalid range value for {}: {}", tag, value)),
+            Some("Ranges are only supported on dates (YYYYMMDD or relative, e.g. -7d) and times (HHMMSS)"),
+        ))
+    }
+}
+
+fn is_date_tag(tag: &str) -> bool {
+    tag.ends_with("Date")
+}
+
+/// Checks DICOM date values and date ranges (YYYYMMDD, YYYYMMDD-, -YYYYMMDD, YYYYMMDD-YYYYMMDD).
+/// Values with wildcards are left alone.
+fn check_date_value(value: &str) -> Result<()> {
+    if value.contains('*') || value.contains('?') {
+        return Ok(());
+    }
+    let invalid_date = || {
+        CliError::new(
+            "Command error",
+            Some(&format!("Invalid date or date range: {}", value)),
+            Some("Dates must be of format YYYYMMDD, ranges of format YYYYMMDD-YYYYMMDD"),
+        )
+    };
+    let parse = |d: &str| -> Result<Option<NaiveDate>> {
+        if d.is_empty() {
+            Ok(None)
+        } else {
+            parse_dicom_date(d).map(Some).ok_or_else(invalid_date)
+        }
+    };
+    match value.split_once('-') {
+        Some((start, end)) => match (parse(start)?, parse(end)?) {
+            (None, None) => Err(invalid_date()),
+            (Some(s), Some(e)) if s > e => Err(invalid_date()),
+            _ => Ok(()),
+        },
+        None => parse(value).and_then(|d| d.map(|_| ()).ok_or_else(invalid_date)),
+    }
+}
+
+fn parse_dicom_date(value: &str) -> Option<NaiveDate> {
+    if value.len() != 8 {
+        return None;
+    }
+    NaiveDate::parse_from_str(value, DICOM_DATE_FORMAT).ok()
+}
+
+/// Parses an absolute (YYYYMMDD) or relative date. Relative dates are either `today` or a number
+/// of days, weeks, months or years before `today` (`-7d`, `-2w`, `-1m`, `-1y`).
+pub fn get_date(value: &str, today: NaiveDate) -> Result<NaiveDate> {
+    if value == "today" {
+        return Ok(today);
+    }
+    if let Some(d) = parse_dicom_date(value) {
+        return Ok(d);
+    }
+    let invalid_date = || {
+        CliError::new(
+            "Command error",
+            Some(&format!("Invalid date: {}", value)),
+            Some("Must be of format YYYYMMDD, or relative to today, e.g. -7d, -2w, -1m or -1y"),
+        )
+    };
+    let relative = value.strip_prefix('-').ok_or_else(invalid_date)?;
+    if relative.len() < 2 {
+        return Err(invalid_date());
+    }
+    let (number, unit) = relative.split_at(relative.len() - 1);
+    let number: i64 = number.parse().map_err(|_| invalid_date())?;
+    let date = match unit {
+        "d" => today.checked_sub_signed(Duration::days(number)),
+        "w" => today.checked_sub_signed(Duration::weeks(number)),
+        "m" => sub_months(today, number),
+        "y" => sub_months(today, number * 12),
+        _ => None,
+    };
+    date.ok_or_else(invalid_date)
+}
+
+fn sub_months(date: NaiveDate, months: i64) -> Option<NaiveDate> {
+    let total_months = date.year() as i64 * 12 + date.month0() as i64 - months;
+    let year = total_months.div_euclid(12) as i32;
+    let month = total_months.rem_euclid(12) as u32 + 1;
+    // Clamp the day to the end of the resulting month (e.g. March 31st - 1m = February 28th)
+    (1..=date.day())
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(year, month, day))
+}
+
+/// Sorts entities by ID or main DICOM tags. Keys prefixed with `-` sort in descending order.
+/// Numeric values (e.g. SeriesNumber) are compared as numbers.
+pub fn sort_entities<T: Entity>(entities: &mut [T], sort: &[&str]) {
+    entities.sort_by(|a, b| {
+        for s in sort {
+            let (key, descending) = match s.strip_prefix('-') {
+                Some(k) => (k, true),
+                None => (*s, false),
+            };
+            let value = |e: &'_ T| -> Option<String> {
+                if key == "ID" {
+                    Some(e.id().to_string())
+                } else {
+                    e.main_dicom_tag(key).map(String::from)
+                }
+            };
+            let (va, vb) = (value(a), value(b));
+            let ordering = match (
+                va.as_deref().and_then(|v| v.trim().parse::<f64>().ok()),
+                vb.as_deref().and_then(|v| v.trim().parse::<f64>().ok()),
+            ) {
+                (Some(na), Some(nb)) => na.partial_cmp(&nb).unwrap_or(Ordering::Equal),
+                _ => va.cmp(&vb),
+            };
+            let ordering = if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    });
+}
+
+/// Search options besides the query terms
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SearchOptions {
+    pub ignore_case: bool,
+    pub limit: Option<u64>,
+    pub labels: Option<Vec<String>>,
+    /// `All`, `Any` or `None` of the labels
+    pub labels_constraint: Option<String>,
+}
+
+/// Fails if the label contains characters other than the ones Orthanc allows
+pub fn check_label(label: &str) -> Result<()> {
+    if !label.is_empty()
+        && label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        Ok(())
+    } else {
+        Err(CliError::new(
+            "Command error",
+            Some(&format!("Wrong label '{}'", label)),
+            Some("Labels can only contain alphanumeric characters, '_' and '-'"),
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Eq, PartialEq, Clone)]
+pub struct SavedQuery {
+    pub level: String,
+    pub query: Vec<String>,
+    #[serde(default)]
+    pub ignore_case: bool,
+    pub columns: Option<Vec<String>>,
+    pub sort: Option<Vec<String>>,
+}
+
+pub fn get_saved_queries_file() -> Result<PathBuf> {
+    Ok(get_config_dir()?.join(SAVED_QUERIES_FILE))
+}
+
+pub fn load_saved_queries(path: &Path) -> Result<BTreeMap<String, SavedQuery>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    Ok(serde_yaml::from_str(&fs::read_to_string(path)?)?)
+}
+
+pub fn store_saved_queries(
+    path: &Path,
+    queries: &BTreeMap<String, SavedQuery>,
+) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_yaml::to_string(queries)?)?;
+    Ok(())
+}
+
+/// Replaces `{{date:<date>}}` placeholders with DICOM dates (see `get_date`) and `{{<name>}}`
+/// placeholders with the values of the matching parameters
+pub fn expand_query_placeholders(
+    term: &str,
+    params: &HashMap<String, String>,
+    today: NaiveDate,
+) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = term;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..].find("}}").ok_or_else(|| {
+            CliError::new(
+                "Command error",
+                Some(&format!(
+                    "Unterminated placeholder in query term '{}'",
+                    term
+                )),
+                None,
+            )
+        })? + start;
+        let placeholder = rest[start + 2..end].trim();
+        let value = match placeholder.strip_prefix("date:") {
+            Some(d) => get_date(d.trim(), today)?
+                .format(DICOM_DATE_FORMAT)
+                .to_string(),
+            None => params.get(placeholder).cloned().ok_or_else(|| {
+                CliError::new(
+                    "Command error",
+                    Some(&format!("Missing query parameter: {}", placeholder)),
+                    Some("Pass it with --param name=value"),
+                )
+            })?,
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&value);
+        rest = &rest[end + 2..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+pub fn filter_changes(changes: Vec<Change>, types: &Option<Vec<&str>>) -> Vec<Change> {
+    match types {
+        Some(t) => changes
+            .into_iter()
+            .filter(|c| t.contains(&c.change_type.as_str()))
+            .collect(),
+        None => changes,
+    }
+}
+
+pub fn read_sequence_state(path: &Path) -> Result<Option<u64>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let state = fs::read_to_string(path)?;
+    match state.trim().parse::<u64>() {
+        Ok(s) => Ok(Some(s)),
+        Err(e) => Err(CliError::new(
+            "Command error",
+            Some(&format!("Invalid state file: {}", path.display())),
+            Some(&e.to_string()),
+        )),
+    }
+}
+
+pub fn write_sequence_state(path: &Path, seq: u64) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Write to a temporary file first, so that an interruption doesn't leave a corrupted state
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, seq.to_string())?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// What to do with each change when following the changes feed
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangeHandler {
+    /// Shell command template, with `{{id}}`, `{{type}}`, `{{level}}` and `{{seq}}` placeholders
+    Exec(String),
+    /// Webhook URL the change is POSTed to as JSON
+    Post(String),
+}
+
+pub struct ChangeDelivery {
+    pub handler: ChangeHandler,
+    pub concurrency: usize,
+    pub retries: u32,
+    pub dead_letter_file: PathBuf,
+}
+
+pub fn get_dead_letter_file(path: Option<&str>, server_address: &str) -> Result<PathBuf> {
+    match path {
+        Some(p) => Ok(PathBuf::from(p)),
+        None => get_server_state_file(server_address, CHANGES_DEAD_LETTER_FILE),
+    }
+}
+
+pub fn create_change_payload(
+    change: &Change,
+    main_dicom_tags: HashMap<String, String>,
+) -> Value {
+    json!({
+        "Seq": change.seq,
+        "ChangeType": change.change_type,
+        "ResourceType": change.resource_type,
+        "ID": change.id,
+        "Path": change.path,
+        "Date": change.date,
+        "MainDicomTags": main_dicom_tags,
+    })
+}
+
+/// Hands the changes over to the handler, `delivery.concurrency` at a time. Each change is tried
+/// `delivery.retries` more times after a failure, and is then written to the dead-letter file.
+/// Failing to write to it is reported, but doesn't stop the delivery of the next changes.
+pub fn deliver_changes(payloads: Vec<Value>, delivery: &ChangeDelivery) -> Result<()> {
+    for chunk in payloads.chunks(delivery.concurrency.max(1)) {
+        let workers: Vec<_> = chunk
+            .iter()
+            .cloned()
+            .map(|payload| {
+                let handler = delivery.handler.clone();
+                let retries = delivery.retries;
+                thread::spawn(move || {
+                    let result = deliver_change(&payload, &handler, retries);
+                    (payload, result)
+                })
+            })
+            .collect();
+        for worker in workers {
+            let (payload, result) = worker.join().map_err(|_| {
+                CliError::new(
+                    "Command error",
+                    Some("Change delivery worker panicked"),
+                    None,
+                )
+            })?;
+            if let Err(e) = result {
+                eprintln!(
+                    "{}",
+                    create_error_table(CliError::new(
+                        "Command error",
+                        Some(&format!("Failed to deliver change {}", payload["Seq"])),
+                        Some(&e),
+                    ))
+                );
+                if let Err(e) = append_dead_letter(&delivery.dead_letter_file, payload, &e)
+                {
+                    eprintln!("{}", create_error_table(e));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn deliver_change(
+    payload: &Value,
+    handler: &ChangeHandler,
+    retries: u32,
+) -> result::Result<(), String> {
+    let mut interval = CHANGE_DELIVERY_RETRY_INTERVAL;
+    let mut attempt = 0;
+    loop {
+        let result = match handler {
+            ChangeHandler::Exec(command) => exec_change_command(command, payload),
+            ChangeHandler::Post(url) => post_change(url, payload),
+        };
+        match result {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt >= retries => return Err(e),
+            Err(_) => {
+                attempt += 1;
+                thread::sleep(time::Duration::from_secs(interval));
+                interval *= 2;
+            }
+        }
+    }
+}
+
+fn exec_change_command(command: &str, payload: &Value) -> result::Result<(), String> {
+    let mut cmd = process::Command::new("sh");
+    cmd.arg("-c")
+        .arg(expand_change_placeholders(command, payload));
+    for (name, value) in get_change_env(payload) {
+        cmd.env(name, value);
+    }
+    let status = cmd.status().map_err(|e| e.to_string())?;
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(c) => Err(format!("Command exited with code {}", c)),
+        None => Err("Command was killed by a signal".to_string()),
+    }
+}
+
+fn post_change(url: &str, payload: &Value) -> result::Result<(), String> {
+    let resp = reqwest::blocking::Client::new()
+        .post(url)
+        .json(payload)
+        .send()
+        .map_err(|e| e.to_string())?;
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("Webhook responded with {}", resp.status()))
+    }
+}
+
+fn get_payload_field(payload: &Value, field: &str) -> String {
+    match &payload[field] {
+        Value::String(s) => s.to_string(),
+        Value::Null => "".to_string(),
+        v => v.to_string(),
+    }
+}
+
+/// Replaces the placeholders of a shell command with the values of a change, each quoted so
+/// that the shell takes it as a single word, whatever it contains
+pub fn expand_change_placeholders(command: &str, payload: &Value) -> String {
+    let value = |field| shell_quote(&get_payload_field(payload, field));
+    command
+        .replace("{{id}}", &value("ID"))
+        .replace("{{type}}", &value("ChangeType"))
+        .replace("{{level}}", &value("ResourceType"))
+        .replace("{{seq}}", &value("Seq"))
+}
+
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::NaiveDate;
-    use maplit::hashmap;
-    use regex::RegexBuilder;
-    use std::env::{remove_var, set_var};
-    use std::io::Write;
-    use tempfile::NamedTempFile;
+pub fn get_change_env(payload: &Value) -> Vec<(String, String)> {
+    let mut env = vec![
+        (
+            "ORC_CHANGE_SEQ".to_string(),
+            get_payload_field(payload, "Seq"),
+        ),
+        (
+            "ORC_CHANGE_TYPE".to_string(),
+            get_payload_field(payload, "ChangeType"),
+        ),
+        (
+            "ORC_CHANGE_LEVEL".to_string(),
+            get_payload_field(payload, "ResourceType"),
+        ),
+        (
+            "ORC_CHANGE_ID".to_string(),
+            get_payload_field(payload, "ID"),
+        ),
+        (
+            "ORC_CHANGE_PATH".to_string(),
+            get_payload_field(payload, "Path"),
+        ),
+        (
+            "ORC_CHANGE_DATE".to_string(),
+            get_payload_field(payload, "Date"),
+        ),
+    ];
+    if let Some(tags) = payload["MainDicomTags"].as_object() {
+        for (tag, value) in tags {
+            env.push((
+                format!("ORC_TAG_{}", tag),
+                value.as_str().unwrap_or_default().to_string(),
+            ));
+        }
+    }
+    env
+}
+
+fn append_dead_letter(path: &Path, payload: Value, error: &str) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", json!({"Change": payload, "Error": error}))?;
+    Ok(())
+}
+
+/// IDs given on the command line, or read from the standard input (whitespace-separated) if
+/// there are none, or only `-`
+pub fn get_ids(ids: Option<Vec<&str>>) -> Result<Vec<String>> {
+    match ids {
+        Some(i) if i != ["-"] => Ok(i.iter().map(|s| s.to_string()).collect()),
+        _ => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input)?;
+            parse_ids(&input)
+        }
+    }
+}
+
+fn parse_ids(input: &str) -> Result<Vec<String>> {
+    let ids: Vec<String> = input.split_whitespace().map(String::from).collect();
+    if ids.is_empty() {
+        return Err(CliError::new("Command error", Some("No IDs given"), None));
+    }
+    Ok(ids)
+}
+
+/// Reads a script from a file, or from the standard input if the file is `-`
+pub fn read_script(file: &str) -> Result<String> {
+    if file == "-" {
+        let mut script = String::new();
+        io::stdin().read_to_string(&mut script)?;
+        Ok(script)
+    } else {
+        Ok(fs::read_to_string(file)?)
+    }
+}
+
+/// Prepends the script with the Lua assignments of the `name=value` variables
+pub fn create_lua_script(script: &str, vars: Option<Vec<&str>>) -> Result<String> {
+    let mut lua = String::new();
+    for v in vars.unwrap_or_default() {
+        let var: Vec<&str> = v.splitn(2, '=').collect();
+        if var.len() != 2 || !is_lua_identifier(var[0]) {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!("Wrong option value '{}'", v)),
+                Some("Must be of format 'name=value', name being a valid Lua identifier"),
+            ));
+        }
+        lua.push_str(&format!("{} = {}\n", var[0], quote_lua_string(var[1])));
+    }
+    lua.push_str(script);
+    Ok(lua)
+}
+
+fn is_lua_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+fn quote_lua_string(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+pub fn get_anonymization_config(
+    replace: Option<Vec<&str>>,
+    keep: Option<Vec<&str>>,
+    keep_private_tags: Option<bool>,
+    config_file: Option<&str>,
+) -> Result<Option<Anonymization>> {
+    // This should never happen, but double-checking anyway
+    if (replace.is_some() || keep.is_some() || keep_private_tags.is_some())
+        && config_file.is_some()
+    {
+        return Err(CliError::new(
+            "Command error",
+            Some("Conflicting options"),
+            None,
+        ));
+    }
+
+    match config_file {
+        Some(c) => Ok(Some(get_anonymization_config_from_file(c)?)),
+        None => match (&replace, &keep, &keep_private_tags) {
+            (None, None, None) => Ok(None),
+            // TODO: This assumes that there is always either a config file
+            // or at least one of the options
+            _ => Ok(Some(get_anonymization_config_from_cmd_options(
+                replace,
+                keep,
+                keep_private_tags,
+            )?)),
+        },
+    }
+}
+
+pub fn get_modification_config(
+    replace: Option<Vec<&str>>,
+    remove: Option<Vec<&str>>,
+    config_file: Option<&str>,
+) -> Result<Modification> {
+    // This should never happen, but double-checking anyway
+    if replace.is_none() && remove.is_none() && config_file.is_none() {
+        return Err(CliError::new(
+            "Command error",
+            Some("Not enough options"),
+            None,
+        ));
+    }
+    if (replace.is_some() || remove.is_some()) && config_file.is_some() {
+        return Err(CliError::new(
+            "Command error",
+            Some("Conflicting options"),
+            None,
+        ));
+    }
+
+    match config_file {
+        Some(c) => Ok(get_modification_config_from_file(c)?),
+        None => match (&replace, &remove) {
+            // TODO: This assumes that there is always either a config file
+            // or at least one of the options
+            _ => Ok(get_modification_config_from_cmd_options(replace, remove)?),
+        },
+    }
+}
+
+fn get_anonymization_config_from_file(config_file: &str) -> Result<Anonymization> {
+    let yaml = fs::read(config_file)?;
+    let mut a: Anonymization = serde_yaml::from_slice(&yaml)?;
+    a.force = Some(true);
+    Ok(a)
+}
+
+fn get_modification_config_from_file(config_file: &str) -> Result<Modification> {
+    let yaml = fs::read(config_file)?;
+    let mut a: Modification = serde_yaml::from_slice(&yaml)?;
+    a.force = Some(true);
+    Ok(a)
+}
+
+fn get_anonymization_config_from_cmd_options(
+    replace: Option<Vec<&str>>,
+    keep: Option<Vec<&str>>,
+    keep_private_tags: Option<bool>,
+) -> Result<Anonymization> {
+    Ok(Anonymization {
+        replace: match replace {
+            Some(r) => Some(parse_tag_kv_pairs(r)?),
+            None => None,
+        },
+        keep: keep.map(|vec| vec.iter().map(ToString::to_string).collect()),
+        keep_private_tags,
+        dicom_version: None,
+        force: Some(true),
+    })
+}
+
+fn get_modification_config_from_cmd_options(
+    replace: Option<Vec<&str>>,
+    remove: Option<Vec<&str>>,
+) -> Result<Modification> {
+    Ok(Modification {
+        replace: match replace {
+            Some(r) => Some(parse_tag_kv_pairs(r)?),
+            None => None,
+        },
+        remove: remove.map(|vec| vec.iter().map(ToString::to_string).collect()),
+        force: Some(true),
+    })
+}
+
+/// How an entity is downloaded, besides the output file
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DownloadOptions {
+    /// Media archive, with a DICOMDIR, instead of a plain archive
+    pub media: bool,
+    /// Directory the archive is extracted to
+    pub extract: Option<String>,
+    /// Path template of the extracted files, with `{TagName}` placeholders
+    pub layout: Option<String>,
+    /// Transfer syntax (UID or alias) the instances are transcoded to
+    pub transcode: Option<String>,
+    /// Keep what a previous download already wrote completely
+    pub skip_existing: bool,
+}
+
+/// UID of a transfer syntax given as a UID or as one of the aliases of `TRANSFER_SYNTAX_ALIASES`
+pub fn get_transfer_syntax(value: &str) -> Result<String> {
+    if let Some((_, uid)) = TRANSFER_SYNTAX_ALIASES.iter().find(|(a, _)| *a == value) {
+        return Ok(uid.to_string());
+    }
+    let is_uid = value
+        .split('.')
+        .all(|c| !c.is_empty() && c.chars().all(|d| d.is_ascii_digit()));
+    if is_uid
+        && (value == TRANSFER_SYNTAX_UID_ROOT
+            || value.starts_with(&format!("{}.", TRANSFER_SYNTAX_UID_ROOT)))
+    {
+        Ok(value.to_string())
+    } else {
+        Err(CliError::new(
+            "Command error",
+            Some(&format!("Unknown transfer syntax '{}'", value)),
+            Some(&format!(
+                "Must be a transfer syntax UID, or one of: {}",
+                TRANSFER_SYNTAX_ALIASES
+                    .iter()
+                    .map(|(a, _)| *a)
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            )),
+        ))
+    }
+}
+
+/// Path of an instance downloaded with a layout. The placeholders are replaced with the first
+/// value found in `tags` (instance, series, study, then patient main DICOM tags).
+pub fn expand_layout(layout: &str, tags: &[&HashMap<String, String>]) -> Result<PathBuf> {
+    let wrong_layout = || {
+        CliError::new(
+            "Command error",
+            Some(&format!("Wrong layout '{}'", layout)),
+            Some("Placeholders must be of format {TagName}, e.g. {PatientID}/{StudyDate}/{SOPInstanceUID}.dcm"),
+        )
+    };
+    let mut path = String::new();
+    let mut rest = layout;
+    while let Some(start) = rest.find('{') {
+        let end = rest.find('}').ok_or_else(wrong_layout)?;
+        let tag = rest.get(start + 1..end).ok_or_else(wrong_layout)?;
+        if tag.is_empty() || tag.contains('{') {
+            return Err(wrong_layout());
+        }
+        path.push_str(&rest[..start]);
+        path.push_str(&match tags.iter().find_map(|t| t.get(tag)) {
+            Some(v) => sanitize_path_component(v),
+            None => ABSENT_DICOM_TAG_PLACEHOLDER.to_string(),
+        });
+        rest = &rest[end + 1..];
+    }
+    if rest.contains('}') {
+        return Err(wrong_layout());
+    }
+    path.push_str(rest);
+    Ok(PathBuf::from(path))
+}
+
+/// Makes a DICOM value usable as a file or directory name, replacing path separators and the
+/// characters some filesystems don't allow
+fn sanitize_path_component(value: &str) -> String {
+    let value: String = value
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_control() || "/\\:*?\"<>|".contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    match value.as_str() {
+        "" => ABSENT_DICOM_TAG_PLACEHOLDER.to_string(),
+        "." | ".." => value.replace('.', "_"),
+        _ => value,
+    }
+}
+
+pub fn extract_archive(file: fs::File, dir: &Path) -> Result<()> {
+    zip::ZipArchive::new(file)?.extract(dir)?;
+    Ok(())
+}
+
+/// Reads all files of a ZIP archive, which checks their CRC
+pub fn verify_archive<R: Read + io::Seek>(reader: R) -> Result<()> {
+    let corrupted = |e: &dyn std::error::Error| {
+        CliError::new(
+            "Command error",
+            Some("Corrupted archive"),
+            Some(&e.to_string()),
+        )
+    };
+    let mut archive = zip::ZipArchive::new(reader).map_err(|e| corrupted(&e))?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| corrupted(&e))?;
+        io::copy(&mut file, &mut io::sink()).map_err(|e| corrupted(&e))?;
+    }
+    Ok(())
+}
+
+/// Writes a file through a temporary file next to it, which only replaces the file once `write`
+/// succeeded. This way an interrupted download never leaves a truncated file behind.
+pub fn write_file_atomically<F: FnOnce(&mut fs::File) -> Result<()>>(
+    path: &Path,
+    write: F,
+) -> Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(DOWNLOAD_PARTIAL_SUFFIX);
+    let tmp_path = path.with_file_name(tmp_name);
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&tmp_path)?;
+    let result = write(&mut file);
+    drop(file);
+    match result {
+        Ok(_) => fs::rename(&tmp_path, path).map_err(Into::into),
+        Err(e) => {
+            // The error of the write matters more than a failed cleanup
+            fs::remove_file(&tmp_path).ok();
+            Err(e)
+        }
+    }
+}
+
+/// Streams a download to a file, showing its progress if stderr is a terminal
+pub fn download_to<F: FnOnce(&mut dyn Write) -> Result<()>>(
+    file: &mut fs::File,
+    download: F,
+) -> Result<()> {
+    let mut progress = Progress::new(atty::is(atty::Stream::Stderr));
+    download(&mut progress.writer(file))
+}
+
+/// Downloads the archive of a study to a file, or extracts it to a directory, and returns the
+/// size of what was written
+pub fn save_study(
+    client: &Client,
+    id: &str,
+    path: &Path,
+    extract: bool,
+    transcode: Option<&str>,
+) -> Result<u64> {
+    if extract {
+        fs::create_dir_all(path)?;
+        let archive_path = path.join(DOWNLOAD_ARCHIVE_FILE);
+        let mut file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&archive_path)?;
+        let result = client
+            .study_dicom(id, transcode, &mut file)
+            .map_err(Into::into)
+            .and_then(|_| verify_archive(&mut file))
+            .and_then(|_| extract_archive(file, path));
+        // A failed cleanup mustn't hide the error of the download
+        let removed = fs::remove_file(&archive_path);
+        result?;
+        removed?;
+    } else {
+        write_file_atomically(path, |file| {
+            client.study_dicom(id, transcode, &mut *file)?;
+            verify_archive(file)
+        })?;
+    }
+    get_path_size(path)
+}
+
+/// Path of a study downloaded by `study download-many`, relative to the output directory
+pub fn get_study_download_path(id: &str, extract: bool) -> String {
+    if extract {
+        id.to_string()
+    } else {
+        format!("{}.zip", id)
+    }
+}
+
+/// Size of a file, or of all the files of a directory
+pub fn get_path_size(path: &Path) -> Result<u64> {
+    let metadata = fs::metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += get_path_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
+/// Row of a download manifest without its path and size: the study ID and main DICOM tags
+pub fn get_manifest_row(study: &Study) -> Vec<String> {
+    let mut row = vec![study.id.clone()];
+    for tag in DOWNLOAD_MANIFEST_TAGS {
+        let value = study
+            .main_dicom_tags
+            .get(*tag)
+            .or_else(|| study.patient_main_dicom_tags.get(*tag));
+        row.push(value.cloned().unwrap_or_default());
+    }
+    row
+}
+
+/// Whether a manifest row says the study was downloaded to this path, and the path still has
+/// the same size, i.e. the download needn't be done again
+pub fn is_downloaded(dir: &Path, row: &[String], path: &str) -> bool {
+    match row {
+        [.., p, size] if p == path => {
+            get_path_size(&dir.join(p)).ok() == size.parse::<u64>().ok()
+        }
+        _ => false,
+    }
+}
+
+/// Whether a file is a complete, readable ZIP archive
+pub fn is_archive_complete(path: &Path) -> bool {
+    fs::File::open(path)
+        .map_err(Into::into)
+        .and_then(verify_archive)
+        .is_ok()
+}
+
+/// Rows of a download manifest, by study ID. There are none if the manifest doesn't exist yet.
+pub fn read_manifest(path: &Path) -> Result<BTreeMap<String, Vec<String>>> {
+    let mut rows = BTreeMap::new();
+    if !path.exists() {
+        return Ok(rows);
+    }
+    for line in fs::read_to_string(path)?.lines().skip(1) {
+        let row = parse_csv_row(line);
+        if let Some(id) = row.first() {
+            rows.insert(id.clone(), row);
+        }
+    }
+    Ok(rows)
+}
+
+pub fn write_manifest(path: &Path, rows: &BTreeMap<String, Vec<String>>) -> Result<()> {
+    let mut header = vec!["ID".to_string()];
+    header.extend(DOWNLOAD_MANIFEST_TAGS.iter().map(|t| t.to_string()));
+    header.extend(vec!["Path".to_string(), "Size".to_string()]);
+    write_file_atomically(path, |file| {
+        writeln!(file, "{}", format_csv_row(&header))?;
+        for row in rows.values() {
+            writeln!(file, "{}", format_csv_row(row))?;
+        }
+        Ok(())
+    })
+}
+
+/// CSV row of the values, quoted when needed. Line breaks are replaced with spaces, so that each
+/// row is a single line.
+pub fn format_csv_row(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|v| {
+            let v = v.replace(&['\r', '\n'][..], " ");
+            if v.contains(&[',', '"'][..]) {
+                format!("\"{}\"", v.replace('"', "\"\""))
+            } else {
+                v
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+pub fn parse_csv_row(line: &str) -> Vec<String> {
+    let mut values = vec![];
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                value.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => values.push(std::mem::take(&mut value)),
+            _ => value.push(c),
+        }
+    }
+    values.push(value);
+    values
+}
+
+/// Number of bytes downloaded and transfer rate, shown on stderr until dropped
+#[derive(Debug)]
+pub struct Progress {
+    bytes: u64,
+    start: time::Instant,
+    last_shown: time::Instant,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(enabled: bool) -> Progress {
+        let now = time::Instant::now();
+        Progress {
+            bytes: 0,
+            start: now,
+            last_shown: now,
+            enabled,
+        }
+    }
+
+    /// Writer that adds what goes through it to the progress
+    pub fn writer<W: Write>(&mut self, inner: W) -> ProgressWriter<'_, W> {
+        ProgressWriter {
+            inner,
+            progress: self,
+        }
+    }
+
+    fn add(&mut self, bytes: usize) {
+        self.bytes += bytes as u64;
+        if self.last_shown.elapsed()
+            >= time::Duration::from_millis(DOWNLOAD_PROGRESS_INTERVAL)
+        {
+            self.show();
+        }
+    }
+
+    fn show(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let seconds = self.start.elapsed().as_secs_f64().max(0.001);
+        eprint!(
+            "\r{} downloaded, {}/s    ",
+            format_bytes(self.bytes),
+            format_bytes((self.bytes as f64 / seconds) as u64)
+        );
+        self.last_shown = time::Instant::now();
+    }
+}
+
+impl Drop for Progress {
+    /// Shows the final progress, and leaves the line to whatever is printed next
+    fn drop(&mut self) {
+        if self.enabled && self.bytes > 0 {
+            self.show();
+            eprintln!();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ProgressWriter<'a, W: Write> {
+    inner: W,
+    progress: &'a mut Progress,
+}
+
+impl<'a, W: Write> Write for ProgressWriter<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.progress.add(written);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Formats a number of bytes with the largest unit that keeps it above 1, e.g. 1.5 MB
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for u in units.iter() {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = u;
+    }
+    match unit {
+        "B" => format!("{} B", bytes),
+        _ => format!("{:.1} {}", value, unit),
+    }
+}
+
+/// Reads tags from a YAML file. Scalar values are taken as strings, so that e.g.
+/// `SeriesNumber: 1` needn't be quoted.
+fn read_tags_file(path: &str) -> Result<HashMap<String, String>> {
+    let tags: BTreeMap<String, serde_yaml::Value> =
+        serde_yaml::from_slice(&fs::read(path)?)?;
+    let mut strings = HashMap::new();
+    for (tag, value) in tags {
+        let value = match value {
+            serde_yaml::Value::String(s) => s,
+            serde_yaml::Value::Number(n) => n.to_string(),
+            serde_yaml::Value::Bool(b) => b.to_string(),
+            serde_yaml::Value::Null => "".to_string(),
+            _ => {
+                return Err(CliError::new(
+                    "Command error",
+                    Some(&format!("Invalid value of tag {}", tag)),
+                    Some("Tag values must be strings, numbers or booleans"),
+                ))
+            }
+        };
+        strings.insert(tag, value);
+    }
+    Ok(strings)
+}
+
+pub fn get_instance_creation(
+    tags: Option<Vec<&str>>,
+    tags_file: Option<&str>,
+    image: Option<&str>,
+    pdf: Option<&str>,
+    parent: Option<&str>,
+) -> Result<InstanceCreation> {
+    // This should never happen, but double-checking anyway
+    if image.is_some() && pdf.is_some() {
+        return Err(CliError::new(
+            "Command error",
+            Some("Conflicting options"),
+            None,
+        ));
+    }
+
+    // Tags given on the command line take precedence over those in the file
+    let mut creation_tags = match tags_file {
+        Some(f) => read_tags_file(f)?,
+        None => HashMap::new(),
+    };
+    if let Some(t) = tags {
+        creation_tags.extend(parse_tag_kv_pairs(t)?);
+    }
+
+    let content = match (image, pdf) {
+        (Some(i), None) => {
+            let data = fs::read(i)?;
+            Some(get_data_uri(get_image_mime_type(&data)?, &data))
+        }
+        (None, Some(p)) => Some(get_data_uri("application/pdf", &fs::read(p)?)),
+        _ => None,
+    };
+
+    Ok(InstanceCreation {
+        tags: creation_tags,
+        content,
+        parent: parent.map(String::from),
+        force: Some(true),
+    })
+}
+
+fn get_image_mime_type(data: &[u8]) -> Result<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Ok("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Ok("image/jpeg")
+    } else {
+        Err(CliError::new(
+            "Command error",
+            Some("Unsupported image format"),
+            Some("Only PNG and JPEG images are supported"),
+        ))
+    }
+}
+
+fn get_data_uri(mime_type: &str, data: &[u8]) -> String {
+    format!("data:{};base64,{}", mime_type, base64::encode(data))
+}
+
+pub fn get_server_address(cmd_option: Option<&str>) -> result::Result<String, CliError> {
+    match cmd_option {
+        Some(s) => Ok(s.to_string()),
+        None => match env::var("ORC_ORTHANC_SERVER") {
+            Ok(s) => Ok(s),
+            Err(e) => Err(CliError::new(
+                "Command error",
+                Some("Neither --server nor ORC_ORTHANC_SERVER are set"),
+                Some(&format!("{}", e)),
+            )),
+        },
+    }
+}
+
+pub fn get_username(cmd_option: Option<&str>) -> Option<String> {
+    match cmd_option {
+        Some(s) => Some(s.to_string()),
+        None => match env::var("ORC_ORTHANC_USERNAME") {
+            Ok(s) => Some(s),
+            Err(_) => None, // TODO: This will hide the error
+        },
+    }
+}
+
+pub fn get_password(cmd_option: Option<&str>) -> Option<String> {
+    match cmd_option {
+        Some(s) => Some(s.to_string()),
+        None => match env::var("ORC_ORTHANC_PASSWORD") {
+            Ok(s) => Some(s),
+            Err(_) => None, // TODO: This will hide the error
+        },
+    }
+}
+
+pub fn get_iap_client_id(cmd_option: Option<&str>) -> Option<String> {
+    match cmd_option {
+        Some(s) => Some(s.to_string()),
+        None => match env::var("IAP_CLIENT_ID") {
+            Ok(s) => Some(s),
+            Err(_) => None, // TODO: This will hide the error
+        },
+    }
+}
+
+pub fn get_google_application_credentials(cmd_option: Option<&str>) -> Option<String> {
+    match cmd_option {
+        Some(s) => Some(s.to_string()),
+        None => match env::var("GOOGLE_APPLICATION_CREDENTIALS") {
+            Ok(s) => Some(s),
+            Err(_) => None, // TODO: This will hide the error
+        },
+    }
+}
+
+/// Directory holding the CLI configuration (e.g. saved queries): `ORC_CONFIG_DIR` if set,
+/// otherwise `orthanc-cli` in `XDG_CONFIG_HOME` or `~/.config`
+pub fn get_config_dir() -> Result<PathBuf> {
+    if let Ok(d) = env::var("ORC_CONFIG_DIR") {
+        return Ok(PathBuf::from(d));
+    }
+    match (env::var("XDG_CONFIG_HOME"), env::var("HOME")) {
+        (Ok(d), _) => Ok(PathBuf::from(d).join(CONFIG_DIR_NAME)),
+        (_, Ok(h)) => Ok(PathBuf::from(h).join(".config").join(CONFIG_DIR_NAME)),
+        (_, Err(e)) => Err(CliError::new(
+            "Command error",
+            Some("Could not determine the configuration directory"),
+            Some(&format!("Neither ORC_CONFIG_DIR nor HOME are set: {}", e)),
+        )),
+    }
+}
+
+/// State file of one server (e.g. the last change seen), in a directory of the configuration
+/// directory named after the server address, so that several servers don't share their state
+pub fn get_server_state_file(server_address: &str, name: &str) -> Result<PathBuf> {
+    Ok(get_config_dir()?
+        .join(SERVERS_STATE_DIR)
+        .join(get_server_key(server_address))
+        .join(name))
+}
+
+/// FNV-1a hash of the server address, which unlike `DefaultHasher` is stable across Rust
+/// versions and doesn't put the credentials an address may contain in a path
+fn get_server_key(server_address: &str) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in server_address.trim_end_matches('/').bytes() {
+        hash ^= u64::from(b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+pub fn check_columns_option(
+    original_header: &[&str],
+    requested_columns: &[&str],
+) -> Result<()> {
+    for c in requested_columns {
+        if !original_header.contains(c) {
+            return Err(CliError::new(
+                "Command error",
+                Some(&format!(
+                    "Invalid column name: {}. Available columns: {}",
+                    c,
+                    original_header.join(", ")
+                )),
+                None,
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Restricts the header and the DICOM tags to the requested columns. Extra columns, as well as
+/// metadata columns (`meta:<Name>`), are only displayed when requested, and are added at the end
+/// of the header.
+pub fn get_header_and_dicom_tags<'a>(
+    header: &mut Vec<&'a str>,
+    dicom_tags: &mut Vec<&'a str>,
+    extra_columns: &[&str],
+    columns: Option<Vec<&'a str>>,
+) -> Result<()> {
+    if let Some(c) = columns {
+        let available: Vec<&str> = header.iter().chain(extra_columns).cloned().collect();
+        let not_metadata: Vec<&str> = c
+            .iter()
+            .filter(|v| get_metadata_name(v).is_none())
+            .cloned()
+            .collect();
+        check_columns_option(&available, &not_metadata)?;
+        header.retain(|v| c.contains(v));
+        dicom_tags.retain(|v| c.contains(v));
+        header.extend(
+            c.iter()
+                .filter(|v| extra_columns.contains(v) || get_metadata_name(v).is_some()),
+        );
+    };
+    Ok(())
+}
+
+/// Name of the metadata displayed in a `meta:<Name>` column
+pub fn get_metadata_name(column: &str) -> Option<&str> {
+    match column.strip_prefix(METADATA_COLUMN_PREFIX) {
+        Some(name) if !name.is_empty() => Some(name),
+        _ => None,
+    }
+}
+
+pub fn print_table(table: Table) {
+    println!("{}", table);
+}
+
+/// Prints the number of search results, or exits with 0 (found) or 1 (nothing found) when only
+/// checking for existence
+pub fn print_search_count(count: usize, exists: bool) {
+    if exists {
+        process::exit(if count > 0 { 0 } else { 1 });
+    }
+    println!("{}", count);
+}
+
+/// Exits with the error of a `--count` or `--exists` search. With `--exists`, the exit code
+/// can't be 1, which means nothing was found.
+pub fn exit_with_search_error(error: CliError, exists: bool) {
+    if exists {
+        eprintln!("{}", create_error_table(error));
+        process::exit(SEARCH_ERROR_EXIT_CODE);
+    }
+    exit_with_error(error);
+}
+
+pub fn exit_with_error(error: CliError) {
+    let output = create_error_table(error);
+    eprintln!("{}", output);
+    process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use maplit::{btreemap, hashmap};
+    use regex::RegexBuilder;
+    use serde_json::json;
+    use std::env::{remove_var, set_var};
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn format_table(table: Table) -> String {
+        let trailing_whitespace_regex = RegexBuilder::new(r"([ ]+$)")
+            .multi_line(true)
+            .build()
+            .unwrap();
+        trailing_whitespace_regex
+            .replace_all(&format!("{}", table), "")
+            .to_string()
+    }
+
+    #[test]
+    fn test_create_table_with_header() {
+        let table = create_table(Some(&["Foo", "bar"]));
+        assert_eq!(&format!("{}", table), " Foo   bar \n-----------");
+    }
+
+    #[test]
+    fn test_create_table_no_header() {
+        let table = create_table(None);
+        assert_eq!(&format!("{}", table), "");
+    }
+
+    fn test_list_table_patient(
+        columns: &[&str],
+        dicom_tags: &[&str],
+        no_header: bool,
+        expected_output: &str,
+    ) {
+        let patient_1 = Patient {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            main_dicom_tags: hashmap! {
+                "PatientID".to_string() => "foo_id".to_string(),
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            studies: ["study_1".to_string()].to_vec(),
+            entity: EntityKind::Patient,
+            anonymized_from: None,
+        };
+        let patient_2 = Patient {
+            id: "bar".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Morty Smith".to_string(),
+            },
+            studies: ["study_1".to_string(), "study_2".to_string()].to_vec(),
+            entity: EntityKind::Patient,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                vec![patient_1, patient_2],
+                &columns,
+                &dicom_tags,
+                &HashMap::new(),
+                no_header,
+            )),
+            expected_output
+        );
+    }
+
+    fn test_list_table_study(
+        columns: &[&str],
+        dicom_tags: &[&str],
+        no_header: bool,
+        expected_output: &str,
+    ) {
+        let study_1 = Study {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
+            main_dicom_tags: hashmap! {
+                "AccessionNumber".to_string() => "foo_an".to_string(),
+                "StudyInstanceUID".to_string() => "foo_suid".to_string(),
+                "StudyDescription".to_string() => "foo_sd".to_string(),
+            },
+            parent_patient: "patient_foo".to_string(),
+            patient_main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            series: ["foo_series_1".to_string(), "foo_series_2".to_string()].to_vec(),
+            entity: EntityKind::Study,
+            anonymized_from: None,
+        };
+        let study_2 = Study {
+            id: "bar".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 12, 30).and_hms(10, 10, 0),
+            main_dicom_tags: hashmap! {
+                "PatientID".to_string() => "bar_pid".to_string(),
+                "StudyDate".to_string() => "20200101".to_string(),
+                "StudyTime".to_string() => "190001".to_string(),
+            },
+            parent_patient: "patient_bar".to_string(),
+            patient_main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            series: ["bar_series_1".to_string()].to_vec(),
+            entity: EntityKind::Study,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                vec![study_1, study_2],
+                &columns,
+                &dicom_tags,
+                &HashMap::new(),
+                no_header,
+            )),
+            expected_output
+        );
+    }
+
+    fn test_list_table_series(
+        columns: &[&str],
+        dicom_tags: &[&str],
+        no_header: bool,
+        expected_output: &str,
+    ) {
+        let series_1 = Series {
+            id: "foo".to_string(),
+            status: "Unknown".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
+            main_dicom_tags: hashmap! {
+                "BodyPartExamined".to_string() => "ABDOMEN".to_string(),
+                "SeriesDescription".to_string() => "Foo".to_string(),
+            },
+            parent_study: "study_1".to_string(),
+            expected_number_of_instances: Some(17),
+            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
+            entity: EntityKind::Series,
+            anonymized_from: None,
+        };
+        let series_2 = Series {
+            id: "bar".to_string(),
+            status: "Known".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2021, 8, 30).and_hms(19, 12, 09),
+            main_dicom_tags: hashmap! {
+                "BodyPartExamined".to_string() => "KNEE".to_string(),
+                "Modality".to_string() => "CT".to_string(),
+            },
+            parent_study: "study_2".to_string(),
+            expected_number_of_instances: Some(17),
+            instances: ["i3".to_string(), "i4".to_string()].to_vec(),
+            entity: EntityKind::Series,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                vec![series_1, series_2],
+                &columns,
+                &dicom_tags,
+                &HashMap::new(),
+                no_header,
+            )),
+            expected_output
+        );
+    }
+    fn test_list_table_instance(
+        columns: &[&str],
+        dicom_tags: &[&str],
+        no_header: bool,
+        expected_output: &str,
+    ) {
+        let instance_1 = Instance {
+            id: "foo".to_string(),
+            main_dicom_tags: hashmap! {
+                "SOPInstanceUID".to_string() => "suid_1".to_string(),
+                "InstanceNumber".to_string() => "in_1".to_string(),
+            },
+            parent_series: "foo_series".to_string(),
+            index_in_series: Some(13),
+            file_uuid: "file_uuid".to_string(),
+            file_size: 139402,
+            modified_from: None,
+            entity: EntityKind::Instance,
+            anonymized_from: None,
+        };
+        let instance_2 = Instance {
+            id: "bar".to_string(),
+            main_dicom_tags: hashmap! {
+                "SOPInstanceUID".to_string() => "suid_2".to_string(),
+                "InstanceCreationDate".to_string() => "19000101".to_string(),
+                "InstanceCreationTime".to_string() => "000000".to_string(),
+            },
+            parent_series: "foo_series".to_string(),
+            index_in_series: Some(13),
+            file_uuid: "file_uuid".to_string(),
+            file_size: 139402,
+            modified_from: None,
+            entity: EntityKind::Instance,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                vec![instance_1, instance_2],
+                &columns,
+                &dicom_tags,
+                &HashMap::new(),
+                no_header,
+            )),
+            expected_output
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_patient() {
+        test_list_table_patient(
+            PATIENTS_LIST_HEADER,
+            PATIENTS_LIST_DICOM_TAGS,
+            false,
+            include_str!("../tests/data/unit/list_patients").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_patient_no_header() {
+        test_list_table_patient(
+            PATIENTS_LIST_HEADER,
+            PATIENTS_LIST_DICOM_TAGS,
+            true,
+            include_str!("../tests/data/unit/list_patients_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_patient_columns() {
+        test_list_table_patient(
+            &["PatientID", "Number of Studies"],
+            &["PatientID"],
+            false,
+            include_str!("../tests/data/unit/list_patients_columns").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_patient_columns_no_header() {
+        test_list_table_patient(
+            &["PatientID", "Number of Studies"],
+            &["PatientID"],
+            true,
+            include_str!("../tests/data/unit/list_patients_columns_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_study() {
+        test_list_table_study(
+            STUDIES_LIST_HEADER,
+            STUDIES_LIST_DICOM_TAGS,
+            false,
+            include_str!("../tests/data/unit/list_studies").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_study_no_header() {
+        test_list_table_study(
+            STUDIES_LIST_HEADER,
+            STUDIES_LIST_DICOM_TAGS,
+            true,
+            include_str!("../tests/data/unit/list_studies_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_series() {
+        test_list_table_series(
+            SERIES_LIST_HEADER,
+            SERIES_LIST_DICOM_TAGS,
+            false,
+            include_str!("../tests/data/unit/list_series").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_series_no_header() {
+        test_list_table_series(
+            SERIES_LIST_HEADER,
+            SERIES_LIST_DICOM_TAGS,
+            true,
+            include_str!("../tests/data/unit/list_series_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_instance() {
+        test_list_table_instance(
+            INSTANCES_LIST_HEADER,
+            INSTANCES_LIST_DICOM_TAGS,
+            false,
+            include_str!("../tests/data/unit/list_instances").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_instance_no_header() {
+        test_list_table_instance(
+            INSTANCES_LIST_HEADER,
+            INSTANCES_LIST_DICOM_TAGS,
+            true,
+            include_str!("../tests/data/unit/list_instances_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_instance_columns() {
+        test_list_table_instance(
+            &["SOPInstanceUID", "Index in series", "File size"],
+            &["SOPInstanceUID"],
+            false,
+            include_str!("../tests/data/unit/list_instances_columns").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_instance_columns_no_header() {
+        test_list_table_instance(
+            &["SOPInstanceUID", "Index in series", "File size"],
+            &["SOPInstanceUID"],
+            true,
+            include_str!("../tests/data/unit/list_instances_columns_no_header").trim_end(),
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_extra_values() {
+        let patient = Patient {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            main_dicom_tags: hashmap! {
+                "PatientID".to_string() => "foo_id".to_string(),
+            },
+            studies: vec![],
+            entity: EntityKind::Patient,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_list_table(
+                vec![patient],
+                &["PatientID", "Protected"],
+                &["PatientID"],
+                &hashmap! {"foo".to_string() => vec!["true".to_string()]},
+                false,
+            )),
+            " PatientID   Protected\n-----------------------\n foo_id      true"
+        );
+    }
+
+    #[test]
+    fn test_create_list_table_no_header_no_data() {
+        let data: Vec<Patient> = vec![];
+        assert_eq!(
+            format_table(create_list_table(
+                data,
+                PATIENTS_LIST_HEADER,
+                PATIENTS_LIST_DICOM_TAGS,
+                &HashMap::new(),
+                true,
+            )),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_create_show_table_patient() {
+        let patient = Patient {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            main_dicom_tags: hashmap! {
+                "PatientID".to_string() => "foo_id".to_string(),
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            studies: ["study_1".to_string()].to_vec(),
+            entity: EntityKind::Patient,
+            anonymized_from: None,
+        };
+
+        assert_eq!(
+            format_table(create_show_table(patient, &PATIENT_DICOM_TAGS)),
+            include_str!("../tests/data/unit/show_patient").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_create_show_table_study() {
+        let study = Study {
+            id: "foo".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
+            main_dicom_tags: hashmap! {
+                "AccessionNumber".to_string() => "foo_an".to_string(),
+                "StudyInstanceUID".to_string() => "foo_suid".to_string(),
+                "StudyDescription".to_string() => "foo_sd".to_string(),
+            },
+            parent_patient: "patient_foo".to_string(),
+            patient_main_dicom_tags: hashmap! {
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            },
+            series: ["foo_series_1".to_string(), "foo_series_2".to_string()].to_vec(),
+            entity: EntityKind::Study,
+            anonymized_from: None,
+        };
+
+        assert_eq!(
+            format_table(create_show_table(study, &STUDY_DICOM_TAGS)),
+            include_str!("../tests/data/unit/show_study").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_create_show_table_series() {
+        let series = Series {
+            id: "foo".to_string(),
+            status: "Unknown".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
+            main_dicom_tags: hashmap! {
+                "BodyPartExamined".to_string() => "ABDOMEN".to_string(),
+                "SeriesDescription".to_string() => "Foo".to_string(),
+            },
+            parent_study: "study_1".to_string(),
+            expected_number_of_instances: Some(17),
+            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
+            entity: EntityKind::Series,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_show_table(series, &SERIES_DICOM_TAGS)),
+            include_str!("../tests/data/unit/show_series").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_create_show_table_instance() {
+        let instance = Instance {
+            id: "foo".to_string(),
+            main_dicom_tags: hashmap! {
+                "SOPInstanceUID".to_string() => "suid_1".to_string(),
+                "InstanceNumber".to_string() => "in_1".to_string(),
+            },
+            parent_series: "foo_series".to_string(),
+            index_in_series: Some(13),
+            file_uuid: "file_uuid".to_string(),
+            file_size: 139402,
+            modified_from: None,
+            entity: EntityKind::Instance,
+            anonymized_from: None,
+        };
+        assert_eq!(
+            format_table(create_show_table(instance, &INSTANCE_DICOM_TAGS)),
+            include_str!("../tests/data/unit/show_instance").trim_end()
+        );
+    }
+
+    #[test]
+    fn test_create_new_entity_table() {
+        let res = ModificationResult {
+            id: "foobar".to_string(),
+            patient_id: "bazqux".to_string(),
+            path: "long_and_rocky".to_string(),
+            entity: EntityKind::Study,
+        };
+        let expected_table = " New Study ID   foobar \n Patient ID     bazqux ";
+        assert_eq!(format!("{}", create_new_entity_table(res)), expected_table)
+    }
+
+    #[test]
+    fn test_create_new_entity_table_patient() {
+        let res = ModificationResult {
+            id: "foobar".to_string(),
+            patient_id: "bazqux".to_string(),
+            path: "long_and_rocky".to_string(),
+            entity: EntityKind::Patient,
+        };
+        let expected_table = " New Patient ID   foobar ";
+        assert_eq!(format!("{}", create_new_entity_table(res)), expected_table)
+    }
+
+    #[test]
+    fn test_create_frames_table() {
+        let tags = json!({"Rows": "512", "NumberOfFrames": "3"});
+        assert_eq!(
+            format_table(create_frames_table(&[0, 1, 2], &tags)),
+            " Number of frames   3\n Rows               512\n Columns            undefined"
+        );
+    }
+
+    #[test]
+    fn test_create_frame_sidecar() {
+        let tags = json!({
+            "Rows": "512",
+            "Columns": "256",
+            "BitsAllocated": "16",
+            "PhotometricInterpretation": "MONOCHROME2",
+            "RescaleSlope": "1.5 ",
+            "SOPInstanceUID": "1.2.3",
+        });
+        assert_eq!(
+            create_frame_sidecar(2, &tags),
+            json!({
+                "Frame": 2,
+                "Rows": 512,
+                "Columns": 256,
+                "BitsAllocated": 16,
+                "PhotometricInterpretation": "MONOCHROME2",
+                "RescaleSlope": 1.5,
+                "RescaleIntercept": null,
+            })
+        );
+    }
+
+    #[test]
+    fn test_get_encapsulated_document_type() {
+        let pdf = json!({"SOPClassUID": "1.2.840.10008.5.1.4.1.1.104.1"});
+        let stl = json!({"SOPClassUID": "1.2.840.10008.5.1.4.1.1.104.3"});
+        assert_eq!(get_encapsulated_document_type(&pdf, None).unwrap(), "PDF");
+        assert_eq!(
+            get_encapsulated_document_type(&pdf, Some("PDF")).unwrap(),
+            "PDF"
+        );
+        assert_eq!(get_encapsulated_document_type(&stl, None).unwrap(), "STL");
+        assert_eq!(
+            get_encapsulated_document_type(&stl, Some("PDF")).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Instance holds an encapsulated STL document, not PDF"),
+                Some("SOPClassUID: 1.2.840.10008.5.1.4.1.1.104.3"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_encapsulated_document_type_no_document() {
+        assert_eq!(
+            get_encapsulated_document_type(
+                &json!({"SOPClassUID": "1.2.840.10008.5.1.4.1.1.4"}),
+                None
+            )
+            .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Instance holds no encapsulated document"),
+                Some("SOPClassUID: 1.2.840.10008.5.1.4.1.1.4"),
+            )
+        );
+        assert_eq!(
+            get_encapsulated_document_type(&json!({}), Some("PDF")).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Instance holds no encapsulated document"),
+                Some("SOPClassUID: undefined"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_create_error_table() {
+        assert_eq!(
+            format!(
+                "{}",
+                create_error_table(CliError::new(
+                    "error",
+                    Some("message"),
+                    Some("details")
+                ))
+            ),
+            " Error     error   \n Message   message \n Details   details "
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                create_error_table(CliError::new("error", None, Some("details")))
+            ),
+            " Error     error   \n Details   details "
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                create_error_table(CliError::new("error", Some("message"), None))
+            ),
+            " Error     error   \n Message   message "
+        );
+        assert_eq!(
+            format!("{}", create_error_table(CliError::new("error", None, None))),
+            " Error   error "
+        );
+    }
+
+    #[test]
+    fn test_parse_tag_kv_pairs() {
+        assert_eq!(
+            parse_tag_kv_pairs(vec!["Foo=Bar", "Baz=42"]).unwrap(),
+            hashmap! {"Foo".to_string() => "Bar".to_string(), "Baz".to_string() => "42".to_string()}
+        )
+    }
 
-    fn format_table(table: Table) -> String {
-        let trailing_whitespace_regex = RegexBuilder::new(r"([ ]+$)")
-            .multi_line(true)
-            .build()
-            .unwrap();
-        trailing_whitespace_regex
-            .replace_all(&format!("{}", table), "")
-            .to_string()
+    #[test]
+    fn test_parse_tag_kv_pairs_error() {
+        assert_eq!(
+            parse_tag_kv_pairs(vec!["Foo=Bar", "Baz"]).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Wrong option value 'Baz'"),
+                Some("Must be of format 'TagName=TagValue'"),
+            )
+        )
     }
 
     #[test]
-    fn test_create_table_with_header() {
-        let table = create_table(Some(&["Foo", "bar"]));
-        assert_eq!(&format!("{}", table), " Foo   bar \n-----------");
+    fn test_parse_entity_id() {
+        assert_eq!(
+            parse_entity_id("00000000-00000000-00000000-00000000-00000000"),
+            EntityId::Plain("00000000-00000000-00000000-00000000-00000000")
+        );
+        assert_eq!(
+            parse_entity_id("uid:1.2.3.4"),
+            EntityId::DicomUid("1.2.3.4")
+        );
+        assert_eq!(
+            parse_entity_id("accession:XYZ"),
+            EntityId::AccessionNumber("XYZ")
+        );
+        assert_eq!(
+            parse_entity_id("pid:patient_2"),
+            EntityId::PatientId("patient_2")
+        );
     }
 
     #[test]
-    fn test_create_table_no_header() {
-        let table = create_table(None);
-        assert_eq!(&format!("{}", table), "");
+    fn test_create_lookup_table() {
+        assert_eq!(
+            format!(
+                "{}",
+                create_lookup_table(&[
+                    (EntityKind::Study, "foo".to_string()),
+                    (EntityKind::Series, "bar".to_string()),
+                ])
+            ),
+            " Study    foo \n Series   bar "
+        );
     }
 
-    fn test_list_table_patient(
-        columns: &[&str],
-        dicom_tags: &[&str],
-        no_header: bool,
-        expected_output: &str,
-    ) {
-        let patient_1 = Patient {
-            id: "foo".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
-            main_dicom_tags: hashmap! {
-                "PatientID".to_string() => "foo_id".to_string(),
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
-            },
-            studies: ["study_1".to_string()].to_vec(),
-            entity: EntityKind::Patient,
-            anonymized_from: None,
-        };
-        let patient_2 = Patient {
-            id: "bar".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
-            main_dicom_tags: hashmap! {
-                "PatientName".to_string() => "Morty Smith".to_string(),
-            },
-            studies: ["study_1".to_string(), "study_2".to_string()].to_vec(),
-            entity: EntityKind::Patient,
-            anonymized_from: None,
+    #[test]
+    fn test_create_system_info_table() {
+        let system = System {
+            name: "MyOrthanc".to_string(),
+            version: "1.9.1".to_string(),
+            api_version: 11,
+            database_version: 6,
+            database_backend_plugin: Some("postgresql-index".to_string()),
+            dicom_aet: "ORTHANC".to_string(),
+            dicom_port: 4242,
+            http_port: 8042,
+            is_http_server_secure: false,
+            plugins_enabled: true,
+            storage_area_plugin: None,
+            main_dicom_tags: None,
         };
         assert_eq!(
-            format_table(create_list_table(
-                vec![patient_1, patient_2],
-                &columns,
-                &dicom_tags,
-                no_header,
+            format_table(create_system_info_table(
+                system.clone(),
+                &["dicom-web".to_string(), "postgresql-index".to_string()]
             )),
-            expected_output
+            concat!(
+                " Name               MyOrthanc\n",
+                " Version            1.9.1\n",
+                " API version        11\n",
+                " DICOM AET          ORTHANC\n",
+                " DICOM port         4242\n",
+                " HTTP port          8042\n",
+                " HTTPS              false\n",
+                " Database backend   postgresql-index\n",
+                " Database version   6\n",
+                " Storage area       Filesystem (built-in)\n",
+                " Plugins            dicom-web, postgresql-index",
+            )
         );
+        let system = System {
+            plugins_enabled: false,
+            ..system
+        };
+        assert!(format_table(create_system_info_table(system, &[]))
+            .ends_with(" Plugins            disabled"));
     }
 
-    fn test_list_table_study(
-        columns: &[&str],
-        dicom_tags: &[&str],
-        no_header: bool,
-        expected_output: &str,
-    ) {
-        let study_1 = Study {
-            id: "foo".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
-            main_dicom_tags: hashmap! {
-                "AccessionNumber".to_string() => "foo_an".to_string(),
-                "StudyInstanceUID".to_string() => "foo_suid".to_string(),
-                "StudyDescription".to_string() => "foo_sd".to_string(),
+    #[test]
+    fn test_create_plugin_tables() {
+        let plugins = vec![
+            Plugin {
+                id: "dicom-web".to_string(),
+                version: "1.5".to_string(),
+                description: Some("DICOMweb support".to_string()),
+                extended_explorer: true,
+                root_uri: Some("../dicom-web/app/client/index.html".to_string()),
             },
-            parent_patient: "patient_foo".to_string(),
-            patient_main_dicom_tags: hashmap! {
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
+            Plugin {
+                id: "foo".to_string(),
+                version: "0.1".to_string(),
+                description: None,
+                extended_explorer: false,
+                root_uri: None,
             },
-            series: ["foo_series_1".to_string(), "foo_series_2".to_string()].to_vec(),
-            entity: EntityKind::Study,
-            anonymized_from: None,
+        ];
+        assert_eq!(
+            format_table(create_plugins_table(&plugins, false)),
+            concat!(
+                " ID          Version   Explorer   Description\n",
+                "---------------------------------------------------\n",
+                " dicom-web   1.5       true       DICOMweb support\n",
+                " foo         0.1       false",
+            )
+        );
+        assert_eq!(
+            format_table(create_plugin_table(plugins[0].clone())),
+            concat!(
+                " ID                   dicom-web\n",
+                " Version              1.5\n",
+                " Description          DICOMweb support\n",
+                " Explorer extension   true\n",
+                " Root URI             ../dicom-web/app/client/index.html\n",
+                " CLI features         None, DICOMweb is only served to DICOMweb clients under ",
+                "/dicom-web",
+            )
+        );
+        assert_eq!(
+            format_table(create_plugin_table(plugins[1].clone())),
+            concat!(
+                " ID                   foo\n",
+                " Version              0.1\n",
+                " Description\n",
+                " Explorer extension   false",
+            )
+        );
+    }
+
+    #[test]
+    fn test_create_log_levels_table() {
+        assert_eq!(
+            format_table(create_log_levels_table(&[
+                ("default", "verbose".to_string()),
+                ("http", "trace".to_string()),
+            ])),
+            " default   verbose\n http      trace"
+        );
+    }
+
+    #[test]
+    fn test_check_label() {
+        assert_eq!(check_label("cohort_1-a"), Ok(()));
+        for l in &["", "foo bar", "foo/bar", "é"] {
+            assert_eq!(
+                check_label(l).unwrap_err(),
+                CliError::new(
+                    "Command error",
+                    Some(&format!("Wrong label '{}'", l)),
+                    Some("Labels can only contain alphanumeric characters, '_' and '-'"),
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_transfer_syntax() {
+        assert_eq!(
+            get_transfer_syntax("jpeg-lossless"),
+            Ok("1.2.840.10008.1.2.4.70".to_string())
+        );
+        assert_eq!(
+            get_transfer_syntax("explicit-little"),
+            Ok("1.2.840.10008.1.2.1".to_string())
+        );
+        for uid in &["1.2.840.10008.1.2", "1.2.840.10008.1.2.4.91"] {
+            assert_eq!(get_transfer_syntax(uid), Ok(uid.to_string()));
+        }
+        for ts in &[
+            "foo",
+            "JPEG-LOSSLESS",
+            "1.2.840.10008.1.20",
+            "1.2.840.10008.1.2..1",
+            "1.2.3",
+        ] {
+            assert_eq!(
+                get_transfer_syntax(ts).unwrap_err().message,
+                Some(format!("Unknown transfer syntax '{}'", ts))
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_server_version() {
+        for v in &["1.8.1", "1.8.2", "1.9.1", "1.10.0", "2.0", "mainline"] {
+            assert_eq!(
+                check_server_version(v, "1.8.1", "Per-category logging"),
+                Ok(())
+            );
+        }
+        for v in &["1.8.0", "1.8", "1.7.4", "0.9.6"] {
+            assert_eq!(
+                check_server_version(v, "1.8.1", "Per-category logging").unwrap_err(),
+                CliError::new(
+                    "Command error",
+                    Some("Per-category logging requires Orthanc 1.8.1 or later"),
+                    Some(&format!("Server version: {}", v)),
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_stats_tables() {
+        let stats = Statistics {
+            count_patients: 2,
+            count_studies: 3,
+            count_series: 5,
+            count_instances: 42,
+            total_disk_size: 12_582_912,
+            total_uncompressed_size: 25_165_824,
         };
-        let study_2 = Study {
-            id: "bar".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 12, 30).and_hms(10, 10, 0),
-            main_dicom_tags: hashmap! {
-                "PatientID".to_string() => "bar_pid".to_string(),
-                "StudyDate".to_string() => "20200101".to_string(),
-                "StudyTime".to_string() => "190001".to_string(),
-            },
-            parent_patient: "patient_bar".to_string(),
-            patient_main_dicom_tags: hashmap! {
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
-            },
-            series: ["bar_series_1".to_string()].to_vec(),
-            entity: EntityKind::Study,
-            anonymized_from: None,
+        assert_eq!(
+            format_table(create_system_stats_table(stats)),
+            concat!(
+                " Patients            2\n",
+                " Studies             3\n",
+                " Series              5\n",
+                " Instances           42\n",
+                " Disk size           12582912 (12 MB)\n",
+                " Uncompressed size   25165824 (24 MB)",
+            )
+        );
+        let stats = EntityStatistics {
+            count_studies: None,
+            count_series: Some(2),
+            count_instances: 10,
+            disk_size: 1000,
+            uncompressed_size: 1000,
         };
         assert_eq!(
-            format_table(create_list_table(
-                vec![study_1, study_2],
-                &columns,
-                &dicom_tags,
-                no_header,
-            )),
-            expected_output
+            format_table(create_entity_stats_table(stats)),
+            concat!(
+                " Series              2\n",
+                " Instances           10\n",
+                " Disk size           1000 (0 MB)\n",
+                " Uncompressed size   1000 (0 MB)",
+            )
         );
     }
 
-    fn test_list_table_series(
-        columns: &[&str],
-        dicom_tags: &[&str],
-        no_header: bool,
-        expected_output: &str,
-    ) {
-        let series_1 = Series {
-            id: "foo".to_string(),
-            status: "Unknown".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
+    #[test]
+    fn test_create_tree_node() {
+        let instance = Instance {
+            id: "i1".to_string(),
             main_dicom_tags: hashmap! {
-                "BodyPartExamined".to_string() => "ABDOMEN".to_string(),
-                "SeriesDescription".to_string() => "Foo".to_string(),
+                "InstanceNumber".to_string() => "1".to_string(),
             },
-            parent_study: "study_1".to_string(),
-            expected_number_of_instances: Some(17),
-            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
-            entity: EntityKind::Series,
+            parent_series: "s1".to_string(),
+            index_in_series: Some(1),
+            file_uuid: "file_uuid".to_string(),
+            file_size: 1000,
+            modified_from: None,
+            entity: EntityKind::Instance,
             anonymized_from: None,
         };
-        let series_2 = Series {
-            id: "bar".to_string(),
+        let series = Series {
+            id: "s1".to_string(),
             status: "Known".to_string(),
             is_stable: true,
-            last_update: NaiveDate::from_ymd(2021, 8, 30).and_hms(19, 12, 09),
+            last_update: NaiveDate::from_ymd(2021, 8, 30).and_hms(19, 12, 9),
             main_dicom_tags: hashmap! {
-                "BodyPartExamined".to_string() => "KNEE".to_string(),
                 "Modality".to_string() => "CT".to_string(),
             },
-            parent_study: "study_2".to_string(),
-            expected_number_of_instances: Some(17),
-            instances: ["i3".to_string(), "i4".to_string()].to_vec(),
+            parent_study: "st1".to_string(),
+            expected_number_of_instances: None,
+            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
             entity: EntityKind::Series,
             anonymized_from: None,
         };
+        let instance_node = create_tree_node(&instance, vec![]);
         assert_eq!(
-            format_table(create_list_table(
-                vec![series_1, series_2],
-                &columns,
-                &dicom_tags,
-                no_header,
-            )),
-            expected_output
+            instance_node,
+            json!({
+                "ID": "i1",
+                "Level": "Instance",
+                "MainDicomTags": {"InstanceNumber": "1"},
+                "FileSize": 1000,
+            })
+        );
+        assert_eq!(
+            create_tree_node(&series, vec![instance_node.clone(), instance_node.clone()]),
+            json!({
+                "ID": "s1",
+                "Level": "Series",
+                "MainDicomTags": {
+                    "SeriesNumber": null,
+                    "Modality": "CT",
+                    "SeriesDescription": null,
+                },
+                "CountInstances": 2,
+                "FileSize": 2000,
+                "Instances": [instance_node.clone(), instance_node],
+            })
         );
     }
-    fn test_list_table_instance(
-        columns: &[&str],
-        dicom_tags: &[&str],
-        no_header: bool,
-        expected_output: &str,
-    ) {
-        let instance_1 = Instance {
-            id: "foo".to_string(),
-            main_dicom_tags: hashmap! {
-                "SOPInstanceUID".to_string() => "suid_1".to_string(),
-                "InstanceNumber".to_string() => "in_1".to_string(),
-            },
-            parent_series: "foo_series".to_string(),
-            index_in_series: Some(13),
-            file_uuid: "file_uuid".to_string(),
-            file_size: 139402,
-            modified_from: None,
-            entity: EntityKind::Instance,
-            anonymized_from: None,
-        };
-        let instance_2 = Instance {
-            id: "bar".to_string(),
+
+    #[test]
+    fn test_create_tree_leaf() {
+        let series = Series {
+            id: "s1".to_string(),
+            status: "Known".to_string(),
+            is_stable: true,
+            last_update: NaiveDate::from_ymd(2021, 8, 30).and_hms(19, 12, 9),
             main_dicom_tags: hashmap! {
-                "SOPInstanceUID".to_string() => "suid_2".to_string(),
-                "InstanceCreationDate".to_string() => "19000101".to_string(),
-                "InstanceCreationTime".to_string() => "000000".to_string(),
+                "Modality".to_string() => "CT".to_string(),
             },
-            parent_series: "foo_series".to_string(),
-            index_in_series: Some(13),
-            file_uuid: "file_uuid".to_string(),
-            file_size: 139402,
-            modified_from: None,
-            entity: EntityKind::Instance,
+            parent_study: "st1".to_string(),
+            expected_number_of_instances: None,
+            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
+            entity: EntityKind::Series,
             anonymized_from: None,
         };
+        let statistics = EntityStatistics {
+            count_studies: None,
+            count_series: None,
+            count_instances: 2,
+            disk_size: 1500,
+            uncompressed_size: 2000,
+        };
         assert_eq!(
-            format_table(create_list_table(
-                vec![instance_1, instance_2],
-                &columns,
-                &dicom_tags,
-                no_header,
-            )),
-            expected_output
+            create_tree_leaf(&series, statistics),
+            json!({
+                "ID": "s1",
+                "Level": "Series",
+                "MainDicomTags": {
+                    "SeriesNumber": null,
+                    "Modality": "CT",
+                    "SeriesDescription": null,
+                },
+                "CountInstances": 2,
+                "FileSize": 2000,
+            })
         );
     }
 
     #[test]
-    fn test_create_list_table_patient() {
-        test_list_table_patient(
-            PATIENTS_LIST_HEADER,
-            PATIENTS_LIST_DICOM_TAGS,
-            false,
-            include_str!("../tests/data/unit/list_patients").trim_end(),
-        );
+    fn test_is_tree_leaf() {
+        assert!(!is_tree_leaf(EntityKind::Study, None));
+        assert!(!is_tree_leaf(EntityKind::Study, Some("series")));
+        assert!(is_tree_leaf(EntityKind::Series, Some("series")));
+        assert!(is_tree_leaf(EntityKind::Instance, Some("series")));
     }
 
     #[test]
-    fn test_create_list_table_patient_no_header() {
-        test_list_table_patient(
-            PATIENTS_LIST_HEADER,
-            PATIENTS_LIST_DICOM_TAGS,
-            true,
-            include_str!("../tests/data/unit/list_patients_no_header").trim_end(),
+    fn test_format_tree() {
+        let tree = json!({
+            "ID": "st1",
+            "Level": "Study",
+            "MainDicomTags": {"StudyDate": "20110101", "StudyDescription": "Study 1"},
+            "CountSeries": 1,
+            "CountInstances": 1,
+            "FileSize": 1000,
+            "Series": [{
+                "ID": "s1",
+                "Level": "Series",
+                "MainDicomTags": {"SeriesNumber": "1", "Modality": "CT"},
+                "CountInstances": 1,
+                "FileSize": 1000,
+            }],
+        });
+        assert_eq!(
+            format_tree(&tree),
+            concat!(
+                "Study st1   20110101   Study 1   (Series: 1, Instances: 1, Size: 1000)\n",
+                "  Series s1   1   CT   undefined   (Instances: 1, Size: 1000)",
+            )
         );
     }
 
     #[test]
-    fn test_create_list_table_patient_columns() {
-        test_list_table_patient(
-            &["PatientID", "Number of Studies"],
-            &["PatientID"],
-            false,
-            include_str!("../tests/data/unit/list_patients_columns").trim_end(),
-        );
+    fn test_parse_tag_kv_pairs_value_with_equals_sign() {
+        assert_eq!(
+            parse_tag_kv_pairs(vec!["Foo=Bar=Baz"]).unwrap(),
+            hashmap! {"Foo".to_string() => "Bar=Baz".to_string()}
+        )
     }
 
     #[test]
-    fn test_create_list_table_patient_columns_no_header() {
-        test_list_table_patient(
-            &["PatientID", "Number of Studies"],
-            &["PatientID"],
-            true,
-            include_str!("../tests/data/unit/list_patients_columns_no_header").trim_end(),
+    fn test_parse_search_query() {
+        let today = NaiveDate::from_ymd(2021, 3, 31);
+        assert_eq!(
+            parse_search_query(
+                vec![
+                    "PatientName=*Sanchez*",
+                    "StudyDate=20200101-20201231",
+                    "Study.StudyDescription=a=b",
+                    "Modality in (CT, MR)",
+                ],
+                EntityKind::Series,
+                today
+            )
+            .unwrap(),
+            hashmap! {
+                "PatientName".to_string() => "*Sanchez*".to_string(),
+                "StudyDate".to_string() => "20200101-20201231".to_string(),
+                "StudyDescription".to_string() => "a=b".to_string(),
+                "Modality".to_string() => "CT\\MR".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_search_query(
+                vec![
+                    "StudyDate>=-7d",
+                    "SeriesDate<=20210101",
+                    "StudyTime>=120000"
+                ],
+                EntityKind::Series,
+                today
+            )
+            .unwrap(),
+            hashmap! {
+                "StudyDate".to_string() => "20210324-".to_string(),
+                "SeriesDate".to_string() => "-20210101".to_string(),
+                "StudyTime".to_string() => "120000-".to_string(),
+            }
+        );
+        assert_eq!(
+            parse_search_query(
+                vec![
+                    "StudyDate<=20201231",
+                    "StudyDate>=20200101",
+                    "StudyTime>=080000"
+                ],
+                EntityKind::Study,
+                today
+            )
+            .unwrap(),
+            hashmap! {
+                "StudyDate".to_string() => "20200101-20201231".to_string(),
+                "StudyTime".to_string() => "080000-".to_string(),
+            }
         );
     }
 
     #[test]
-    fn test_create_list_table_study() {
-        test_list_table_study(
-            STUDIES_LIST_HEADER,
-            STUDIES_LIST_DICOM_TAGS,
-            false,
-            include_str!("../tests/data/unit/list_studies").trim_end(),
+    fn test_parse_search_query_error() {
+        let today = NaiveDate::from_ymd(2021, 3, 31);
+        assert_eq!(
+            parse_search_query(vec!["Modality in CT,MR"], EntityKind::Series, today)
+                .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Wrong option value 'Modality in CT,MR'"),
+                Some(SEARCH_TERM_FORMAT),
+            )
+        );
+        assert_eq!(
+            parse_search_query(
+                vec!["StudyDate=20201231-20200101"],
+                EntityKind::Study,
+                today
+            )
+            .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid date or date range: 20201231-20200101"),
+                Some(
+                    "Dates must be of format YYYYMMDD, ranges of format YYYYMMDD-YYYYMMDD"
+                ),
+            )
+        );
+        for terms in &[
+            vec!["Modality=CT", "Modality=MR"],
+            vec!["StudyDate>=20200101", "StudyDate>=20200601"],
+            vec!["StudyDate>=20200101", "StudyDate=20200601"],
+        ] {
+            assert_eq!(
+                parse_search_query(terms.clone(), EntityKind::Study, today).unwrap_err(),
+                CliError::new(
+                    "Command error",
+                    Some(&format!(
+                        "Duplicate search term on tag '{}'",
+                        terms[0].split(&['=', '>'][..]).next().unwrap()
+                    )),
+                    Some("A tag can only be searched on once, or with one >= and one <= bound"),
+                )
+            );
+        }
+        assert_eq!(
+            parse_search_query(
+                vec!["StudyDate>=20201231", "StudyDate<=20200101"],
+                EntityKind::Study,
+                today
+            )
+            .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid date or date range: 20201231-20200101"),
+                Some(
+                    "Dates must be of format YYYYMMDD, ranges of format YYYYMMDD-YYYYMMDD"
+                ),
+            )
+        );
+        assert_eq!(
+            parse_search_query(vec!["SeriesNumber>=3"], EntityKind::Series, today)
+                .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid range value for SeriesNumber: 3"),
+                Some(concat!(
+                    "Ranges are only supported on dates (YYYYMMDD or relative, e.g. -7d) ",
+                    "and times (HHMMSS)"
+                )),
+            )
+        );
+        assert_eq!(
+            parse_search_query(vec!["Series.Modality=CT"], EntityKind::Study, today)
+                .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid level in search term 'Series.Modality=CT'"),
+                Some("Only Study and its parent levels can be searched on"),
+            )
         );
     }
 
     #[test]
-    fn test_create_list_table_study_no_header() {
-        test_list_table_study(
-            STUDIES_LIST_HEADER,
-            STUDIES_LIST_DICOM_TAGS,
-            true,
-            include_str!("../tests/data/unit/list_studies_no_header").trim_end(),
+    fn test_get_date() {
+        let today = NaiveDate::from_ymd(2021, 3, 31);
+        assert_eq!(get_date("today", today).unwrap(), today);
+        assert_eq!(
+            get_date("20200229", today).unwrap(),
+            NaiveDate::from_ymd(2020, 2, 29)
+        );
+        assert_eq!(
+            get_date("-1d", today).unwrap(),
+            NaiveDate::from_ymd(2021, 3, 30)
         );
+        assert_eq!(
+            get_date("-2w", today).unwrap(),
+            NaiveDate::from_ymd(2021, 3, 17)
+        );
+        assert_eq!(
+            get_date("-1m", today).unwrap(),
+            NaiveDate::from_ymd(2021, 2, 28)
+        );
+        assert_eq!(
+            get_date("-13m", today).unwrap(),
+            NaiveDate::from_ymd(2020, 2, 29)
+        );
+        assert_eq!(
+            get_date("-1y", today).unwrap(),
+            NaiveDate::from_ymd(2020, 3, 31)
+        );
+        assert!(get_date("-1x", today).is_err());
+        assert!(get_date("20201301", today).is_err());
     }
 
     #[test]
-    fn test_create_list_table_series() {
-        test_list_table_series(
-            SERIES_LIST_HEADER,
-            SERIES_LIST_DICOM_TAGS,
-            false,
-            include_str!("../tests/data/unit/list_series").trim_end(),
+    fn test_expand_query_placeholders() {
+        let today = NaiveDate::from_ymd(2021, 3, 31);
+        let params = hashmap! {"accession".to_string() => "XYZ".to_string()};
+        assert_eq!(
+            expand_query_placeholders(
+                "StudyDate={{date:-1d}}-{{ date:today }}",
+                &params,
+                today
+            )
+            .unwrap(),
+            "StudyDate=20210330-20210331"
+        );
+        assert_eq!(
+            expand_query_placeholders("AccessionNumber={{accession}}", &params, today)
+                .unwrap(),
+            "AccessionNumber=XYZ"
+        );
+        assert_eq!(
+            expand_query_placeholders("PatientID={{pid}}", &params, today).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Missing query parameter: pid"),
+                Some("Pass it with --param name=value"),
+            )
+        );
+        assert_eq!(
+            expand_query_placeholders("PatientID={{pid", &params, today).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Unterminated placeholder in query term 'PatientID={{pid'"),
+                None,
+            )
         );
     }
 
     #[test]
-    fn test_create_list_table_series_no_header() {
-        test_list_table_series(
-            SERIES_LIST_HEADER,
-            SERIES_LIST_DICOM_TAGS,
-            true,
-            include_str!("../tests/data/unit/list_series_no_header").trim_end(),
+    fn test_sort_entities() {
+        let instance = |id: &str, number: &str| Instance {
+            id: id.to_string(),
+            main_dicom_tags: hashmap! {
+                "InstanceNumber".to_string() => number.to_string(),
+            },
+            parent_series: "s1".to_string(),
+            index_in_series: None,
+            file_uuid: "file_uuid".to_string(),
+            file_size: 1000,
+            modified_from: None,
+            entity: EntityKind::Instance,
+            anonymized_from: None,
+        };
+        let mut instances =
+            vec![instance("b", "10"), instance("a", "9"), instance("c", "10")];
+        sort_entities(&mut instances, &["InstanceNumber"]);
+        assert_eq!(
+            instances
+                .iter()
+                .map(|i| i.id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["a", "b", "c"]
+        );
+        sort_entities(&mut instances, &["-InstanceNumber", "-ID"]);
+        assert_eq!(
+            instances
+                .iter()
+                .map(|i| i.id.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["c", "b", "a"]
         );
     }
 
     #[test]
-    fn test_create_list_table_instance() {
-        test_list_table_instance(
-            INSTANCES_LIST_HEADER,
-            INSTANCES_LIST_DICOM_TAGS,
-            false,
-            include_str!("../tests/data/unit/list_instances").trim_end(),
+    fn test_get_missing_main_dicom_tags() {
+        let expected = "0008,0018;0020,0013;0020,4000;0020,0037";
+        assert_eq!(get_missing_main_dicom_tags(Some(expected), expected), None);
+        assert_eq!(
+            get_missing_main_dicom_tags(Some("0020,0013;0008,0018;0008,0020"), expected),
+            Some("0020,4000, 0020,0037".to_string())
+        );
+        assert_eq!(
+            get_missing_main_dicom_tags(None, expected),
+            Some("Unknown, stored before Orthanc 1.11.0".to_string())
+        );
+        let stale = vec![("b".to_string(), "0020,4000, 0020,0037".to_string())];
+        assert_eq!(
+            format_table(create_report_table(
+                STALE_MAIN_DICOM_TAGS_HEADER,
+                &stale,
+                false
+            )),
+            concat!(
+                " ID   Missing tags\n",
+                "---------------------------\n",
+                " b    0020,4000, 0020,0037",
+            )
         );
     }
 
     #[test]
-    fn test_create_list_table_instance_no_header() {
-        test_list_table_instance(
-            INSTANCES_LIST_HEADER,
-            INSTANCES_LIST_DICOM_TAGS,
-            true,
-            include_str!("../tests/data/unit/list_instances_no_header").trim_end(),
+    fn test_reconstruction_state() {
+        let path = PathBuf::from("/tmp/orthanc-cli-test-reconstruct/reconstruct.state");
+        fs::remove_dir_all("/tmp/orthanc-cli-test-reconstruct").ok();
+        assert!(read_reconstruction_state(&path).unwrap().is_empty());
+        append_reconstruction_state(&path, "foo").unwrap();
+        append_reconstruction_state(&path, "bar").unwrap();
+        assert_eq!(
+            read_reconstruction_state(&path).unwrap(),
+            ["foo", "bar"].iter().map(|i| i.to_string()).collect()
         );
     }
 
     #[test]
-    fn test_create_list_table_instance_columns() {
-        test_list_table_instance(
-            &["SOPInstanceUID", "Index in series", "File size"],
-            &["SOPInstanceUID"],
-            false,
-            include_str!("../tests/data/unit/list_instances_columns").trim_end(),
+    fn test_expand_layout() {
+        let instance_tags = hashmap! {
+            "InstanceNumber".to_string() => "1".to_string(),
+        };
+        let study_tags = hashmap! {
+            "StudyDate".to_string() => "20210330".to_string(),
+            "StudyDescription".to_string() => " CT/Thorax ".to_string(),
+        };
+        let tags = [&instance_tags, &study_tags];
+        assert_eq!(
+            expand_layout(
+                "{StudyDate}_{StudyDescription}/{Modality}/{InstanceNumber}.dcm",
+                &tags
+            ),
+            Ok(PathBuf::from("20210330_CT_Thorax/undefined/1.dcm"))
         );
+        assert_eq!(
+            expand_layout("study.zip", &tags),
+            Ok(PathBuf::from("study.zip"))
+        );
+        for l in &[
+            "{StudyDate",
+            "StudyDate}",
+            "{}",
+            "{Study{Date}",
+            "}{StudyDate}",
+        ] {
+            assert_eq!(
+                expand_layout(l, &tags).unwrap_err(),
+                CliError::new(
+                    "Command error",
+                    Some(&format!("Wrong layout '{}'", l)),
+                    Some("Placeholders must be of format {TagName}, e.g. {PatientID}/{StudyDate}/{SOPInstanceUID}.dcm"),
+                )
+            );
+        }
     }
 
     #[test]
-    fn test_create_list_table_instance_columns_no_header() {
-        test_list_table_instance(
-            &["SOPInstanceUID", "Index in series", "File size"],
-            &["SOPInstanceUID"],
-            true,
-            include_str!("../tests/data/unit/list_instances_columns_no_header").trim_end(),
+    fn test_verify_archive() {
+        let mut zip = zip::ZipWriter::new(io::Cursor::new(vec![]));
+        let options = zip::write::FileOptions::default()
+            .compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("foo.dcm", options).unwrap();
+        zip.write_all(b"foobarbaz").unwrap();
+        let mut archive = zip.finish().unwrap().into_inner();
+        assert_eq!(verify_archive(io::Cursor::new(&archive)), Ok(()));
+
+        let pos = archive.windows(9).position(|w| w == b"foobarbaz").unwrap();
+        archive[pos] = b'F';
+        assert_eq!(
+            verify_archive(io::Cursor::new(&archive))
+                .unwrap_err()
+                .message,
+            Some("Corrupted archive".to_string())
         );
+        assert_eq!(
+            verify_archive(io::Cursor::new(&archive[..pos]))
+                .unwrap_err()
+                .message,
+            Some("Corrupted archive".to_string())
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo.zip");
+        assert!(!is_archive_complete(&path));
+        fs::write(&path, &archive[..pos]).unwrap();
+        assert!(!is_archive_complete(&path));
+        archive[pos] = b'f';
+        fs::write(&path, &archive).unwrap();
+        assert!(is_archive_complete(&path));
     }
 
     #[test]
-    fn test_create_list_table_no_header_no_data() {
-        let data: Vec<Patient> = vec![];
+    fn test_write_file_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("study.zip");
+        let partial_path = dir.path().join("study.zip.part");
+
+        let result = write_file_atomically(&path, |file| {
+            file.write_all(b"foo")?;
+            assert!(partial_path.exists());
+            Err(CliError::new("Command error", Some("Interrupted"), None))
+        });
         assert_eq!(
-            format_table(create_list_table(
-                data,
-                PATIENTS_LIST_HEADER,
-                PATIENTS_LIST_DICOM_TAGS,
-                true,
-            )),
-            ""
+            result,
+            Err(CliError::new("Command error", Some("Interrupted"), None))
         );
+        assert!(!path.exists());
+        assert!(!partial_path.exists());
+
+        write_file_atomically(&path, |file| file.write_all(b"bar").map_err(Into::into))
+            .unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"bar");
+        assert!(!partial_path.exists());
     }
 
     #[test]
-    fn test_create_show_table_patient() {
-        let patient = Patient {
+    fn test_csv_row() {
+        let values = vec![
+            "foo".to_string(),
+            "Doe, John".to_string(),
+            "".to_string(),
+            "say \"cheese\"".to_string(),
+        ];
+        let row = format_csv_row(&values);
+        assert_eq!(row, r#"foo,"Doe, John",,"say ""cheese""""#);
+        assert_eq!(parse_csv_row(&row), values);
+        assert_eq!(format_csv_row(&["foo\r\nbar".to_string()]), "foo  bar");
+    }
+
+    #[test]
+    fn test_manifest() {
+        let study = Study {
             id: "foo".to_string(),
             is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 1, 1).and_hms(15, 46, 17),
+            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 9),
             main_dicom_tags: hashmap! {
-                "PatientID".to_string() => "foo_id".to_string(),
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
+                "StudyInstanceUID".to_string() => "1.2.3".to_string(),
+                "StudyDescription".to_string() => "CT, Thorax".to_string(),
             },
-            studies: ["study_1".to_string()].to_vec(),
-            entity: EntityKind::Patient,
+            parent_patient: "patient_foo".to_string(),
+            patient_main_dicom_tags: hashmap! {
+                "PatientID".to_string() => "pid".to_string(),
+            },
+            series: vec![],
+            entity: EntityKind::Study,
             anonymized_from: None,
         };
+        let mut row = get_manifest_row(&study);
+        assert_eq!(row, vec!["foo", "pid", "", "1.2.3", "", "", "CT, Thorax"]);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("foo.zip"), b"foobar").unwrap();
+        row.push(get_study_download_path("foo", false));
+        row.push(
+            get_path_size(&dir.path().join("foo.zip"))
+                .unwrap()
+                .to_string(),
+        );
+        assert!(is_downloaded(dir.path(), &row, "foo.zip"));
+        assert!(!is_downloaded(dir.path(), &row, "foo"));
+        fs::write(dir.path().join("foo.zip"), b"foo").unwrap();
+        assert!(!is_downloaded(dir.path(), &row, "foo.zip"));
+
+        fs::create_dir_all(dir.path().join("bar/baz")).unwrap();
+        fs::write(dir.path().join("bar/1.dcm"), b"foo").unwrap();
+        fs::write(dir.path().join("bar/baz/2.dcm"), b"bar").unwrap();
+        assert_eq!(get_path_size(&dir.path().join("bar")), Ok(6));
+
+        let manifest_path = dir.path().join(DOWNLOAD_MANIFEST_FILE);
+        assert_eq!(read_manifest(&manifest_path), Ok(BTreeMap::new()));
+        let manifest = btreemap! {"foo".to_string() => row};
+        write_manifest(&manifest_path, &manifest).unwrap();
+        assert_eq!(
+            fs::read_to_string(&manifest_path).unwrap(),
+            concat!(
+                "ID,PatientID,PatientName,StudyInstanceUID,AccessionNumber,StudyDate,StudyDescription,Path,Size\n",
+                "foo,pid,,1.2.3,,,\"CT, Thorax\",foo.zip,6\n",
+            )
+        );
+        assert_eq!(read_manifest(&manifest_path), Ok(manifest));
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1536), "1.5 KB");
+        assert_eq!(format_bytes(10 * 1024 * 1024), "10.0 MB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
+
+    #[test]
+    fn test_get_server_key() {
+        assert_eq!(get_server_key(""), "cbf29ce484222325");
+        assert_eq!(
+            get_server_key("http://localhost:8042"),
+            get_server_key("http://localhost:8042/")
+        );
+        assert_ne!(
+            get_server_key("http://localhost:8042"),
+            get_server_key("http://localhost:8043")
+        );
+    }
+
+    #[test]
+    fn test_saved_queries() {
+        let path = PathBuf::from("/tmp/orthanc-cli-test/queries.yml");
+        fs::remove_dir_all("/tmp/orthanc-cli-test").ok();
+        assert_eq!(load_saved_queries(&path).unwrap(), BTreeMap::new());
+
+        let queries = btreemap! {
+            "ct".to_string() => SavedQuery {
+                level: "series".to_string(),
+                query: vec!["Modality=CT".to_string()],
+                ignore_case: false,
+                columns: Some(vec!["ID".to_string(), "Modality".to_string()]),
+                sort: Some(vec!["-ID".to_string()]),
+            },
+        };
+        store_saved_queries(&path, &queries).unwrap();
+        assert_eq!(load_saved_queries(&path).unwrap(), queries);
+        assert_eq!(
+            format!("{}", create_saved_queries_table(queries, true)),
+            " ct   series   Modality=CT   ID Modality   -ID "
+        );
+    }
+
+    fn change(seq: u64, change_type: &str) -> Change {
+        Change {
+            seq,
+            change_type: change_type.to_string(),
+            resource_type: "Study".to_string(),
+            id: "foo".to_string(),
+            path: "/studies/foo".to_string(),
+            date: "20210330T120000".to_string(),
+        }
+    }
 
+    #[test]
+    fn test_create_changes_table() {
+        let changes = vec![change(1, "NewStudy"), change(12, "StableStudy")];
         assert_eq!(
-            format_table(create_show_table(patient, &PATIENT_DICOM_TAGS)),
-            include_str!("../tests/data/unit/show_patient").trim_end()
+            format!("{}", create_changes_table(&changes, true)),
+            concat!(
+                " 1    NewStudy      Study   foo   20210330T120000 \n",
+                " 12   StableStudy   Study   foo   20210330T120000 "
+            )
+        );
+        assert_eq!(
+            format_change(&changes[0]),
+            " 1   NewStudy   Study   foo   20210330T120000 "
         );
     }
 
     #[test]
-    fn test_create_show_table_study() {
-        let study = Study {
-            id: "foo".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
-            main_dicom_tags: hashmap! {
-                "AccessionNumber".to_string() => "foo_an".to_string(),
-                "StudyInstanceUID".to_string() => "foo_suid".to_string(),
-                "StudyDescription".to_string() => "foo_sd".to_string(),
-            },
-            parent_patient: "patient_foo".to_string(),
-            patient_main_dicom_tags: hashmap! {
-                "PatientName".to_string() => "Rick Sanchez".to_string(),
-            },
-            series: ["foo_series_1".to_string(), "foo_series_2".to_string()].to_vec(),
-            entity: EntityKind::Study,
-            anonymized_from: None,
-        };
-
+    fn test_filter_changes() {
+        let changes = vec![
+            change(1, "NewStudy"),
+            change(2, "NewSeries"),
+            change(3, "StableStudy"),
+        ];
+        assert_eq!(filter_changes(changes.clone(), &None), changes);
         assert_eq!(
-            format_table(create_show_table(study, &STUDY_DICOM_TAGS)),
-            include_str!("../tests/data/unit/show_study").trim_end()
+            filter_changes(changes, &Some(vec!["NewStudy", "StableStudy"])),
+            vec![change(1, "NewStudy"), change(3, "StableStudy")]
         );
     }
 
     #[test]
-    fn test_create_show_table_series() {
-        let series = Series {
-            id: "foo".to_string(),
-            status: "Unknown".to_string(),
-            is_stable: true,
-            last_update: NaiveDate::from_ymd(2020, 8, 30).and_hms(19, 11, 09),
-            main_dicom_tags: hashmap! {
-                "BodyPartExamined".to_string() => "ABDOMEN".to_string(),
-                "SeriesDescription".to_string() => "Foo".to_string(),
-            },
-            parent_study: "study_1".to_string(),
-            expected_number_of_instances: Some(17),
-            instances: ["i1".to_string(), "i2".to_string()].to_vec(),
-            entity: EntityKind::Series,
-            anonymized_from: None,
-        };
+    fn test_sequence_state() {
+        let path = PathBuf::from("/tmp/orthanc-cli-test-state/changes.seq");
+        fs::remove_dir_all("/tmp/orthanc-cli-test-state").ok();
+        assert_eq!(read_sequence_state(&path).unwrap(), None);
+        write_sequence_state(&path, 42).unwrap();
+        assert_eq!(read_sequence_state(&path).unwrap(), Some(42));
+        fs::write(&path, "foo").unwrap();
         assert_eq!(
-            format_table(create_show_table(series, &SERIES_DICOM_TAGS)),
-            include_str!("../tests/data/unit/show_series").trim_end()
+            read_sequence_state(&path).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid state file: /tmp/orthanc-cli-test-state/changes.seq"),
+                Some("invalid digit found in string"),
+            )
         );
     }
 
     #[test]
-    fn test_create_show_table_instance() {
-        let instance = Instance {
-            id: "foo".to_string(),
-            main_dicom_tags: hashmap! {
-                "SOPInstanceUID".to_string() => "suid_1".to_string(),
-                "InstanceNumber".to_string() => "in_1".to_string(),
-            },
-            parent_series: "foo_series".to_string(),
-            index_in_series: Some(13),
-            file_uuid: "file_uuid".to_string(),
-            file_size: 139402,
-            modified_from: None,
-            entity: EntityKind::Instance,
-            anonymized_from: None,
-        };
+    fn test_change_payload() {
+        let payload = create_change_payload(
+            &change(7, "StableStudy"),
+            hashmap! {"StudyDescription".to_string() => "Brain".to_string()},
+        );
         assert_eq!(
-            format_table(create_show_table(instance, &INSTANCE_DICOM_TAGS)),
-            include_str!("../tests/data/unit/show_instance").trim_end()
+            payload,
+            json!({
+                "Seq": 7,
+                "ChangeType": "StableStudy",
+                "ResourceType": "Study",
+                "ID": "foo",
+                "Path": "/studies/foo",
+                "Date": "20210330T120000",
+                "MainDicomTags": {"StudyDescription": "Brain"},
+            })
+        );
+        assert_eq!(
+            expand_change_placeholders(
+                "script.sh {{id}} {{type}} {{level}} {{seq}}",
+                &payload
+            ),
+            "script.sh 'foo' 'StableStudy' 'Study' '7'"
+        );
+        let mut payload_with_quote = payload.clone();
+        payload_with_quote["ID"] = json!("foo'; echo $(id)");
+        assert_eq!(
+            expand_change_placeholders("script.sh {{id}}", &payload_with_quote),
+            "script.sh 'foo'\\''; echo $(id)'"
+        );
+        assert_eq!(
+            get_change_env(&payload),
+            vec![
+                ("ORC_CHANGE_SEQ".to_string(), "7".to_string()),
+                ("ORC_CHANGE_TYPE".to_string(), "StableStudy".to_string()),
+                ("ORC_CHANGE_LEVEL".to_string(), "Study".to_string()),
+                ("ORC_CHANGE_ID".to_string(), "foo".to_string()),
+                ("ORC_CHANGE_PATH".to_string(), "/studies/foo".to_string()),
+                ("ORC_CHANGE_DATE".to_string(), "20210330T120000".to_string()),
+                ("ORC_TAG_StudyDescription".to_string(), "Brain".to_string()),
+            ]
         );
     }
 
     #[test]
-    fn test_create_new_entity_table() {
-        let res = ModificationResult {
-            id: "foobar".to_string(),
-            patient_id: "bazqux".to_string(),
-            path: "long_and_rocky".to_string(),
-            entity: EntityKind::Study,
+    fn test_deliver_changes() {
+        let dir = PathBuf::from("/tmp/orthanc-cli-test-delivery");
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        let payloads = vec![
+            create_change_payload(&change(1, "NewStudy"), HashMap::new()),
+            create_change_payload(&change(2, "StableStudy"), HashMap::new()),
+        ];
+
+        let delivery = ChangeDelivery {
+            handler: ChangeHandler::Exec(format!(
+                "echo {{{{seq}}}} $ORC_CHANGE_TYPE >> {}",
+                dir.join("out").display()
+            )),
+            concurrency: 1,
+            retries: 0,
+            dead_letter_file: dir.join("dead-letter.jsonl"),
         };
-        let expected_table = " New Study ID   foobar \n Patient ID     bazqux ";
-        assert_eq!(format!("{}", create_new_entity_table(res)), expected_table)
+        deliver_changes(payloads.clone(), &delivery).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("out")).unwrap(),
+            "1 NewStudy\n2 StableStudy\n"
+        );
+        assert!(!delivery.dead_letter_file.exists());
+
+        let delivery = ChangeDelivery {
+            handler: ChangeHandler::Exec("exit 3".to_string()),
+            concurrency: 2,
+            retries: 0,
+            dead_letter_file: dir.join("dead-letter.jsonl"),
+        };
+        deliver_changes(payloads.clone(), &delivery).unwrap();
+        let dead_letters: Vec<Value> = fs::read_to_string(&delivery.dead_letter_file)
+            .unwrap()
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(
+            dead_letters,
+            vec![
+                json!({"Change": payloads[0], "Error": "Command exited with code 3"}),
+                json!({"Change": payloads[1], "Error": "Command exited with code 3"}),
+            ]
+        );
+
+        // A dead-letter file that can't be written doesn't stop the delivery
+        let delivery = ChangeDelivery {
+            dead_letter_file: dir.join("out/dead-letter.jsonl"),
+            ..delivery
+        };
+        assert_eq!(deliver_changes(payloads, &delivery), Ok(()));
     }
 
     #[test]
-    fn test_create_new_entity_table_patient() {
-        let res = ModificationResult {
-            id: "foobar".to_string(),
-            patient_id: "bazqux".to_string(),
-            path: "long_and_rocky".to_string(),
-            entity: EntityKind::Patient,
-        };
-        let expected_table = " New Patient ID   foobar ";
-        assert_eq!(format!("{}", create_new_entity_table(res)), expected_table)
+    fn test_get_metadata_name() {
+        assert_eq!(get_metadata_name("meta:RemoteAET"), Some("RemoteAET"));
+        assert_eq!(get_metadata_name("meta:"), None);
+        assert_eq!(get_metadata_name("RemoteAET"), None);
     }
 
     #[test]
-    fn test_create_error_table() {
+    fn test_get_header_and_dicom_tags_metadata_columns() {
+        let mut header = vec!["foo", "bar", "baz"];
+        let mut dicom_tags = vec!["bar", "baz"];
+
+        get_header_and_dicom_tags(
+            &mut header,
+            &mut dicom_tags,
+            &["qux"],
+            Some(vec!["meta:RemoteAET", "bar", "qux", "meta:ReceptionDate"]),
+        )
+        .unwrap();
+
         assert_eq!(
-            format!(
-                "{}",
-                create_error_table(CliError::new(
-                    "error",
-                    Some("message"),
-                    Some("details")
-                ))
-            ),
-            " Error     error   \n Message   message \n Details   details "
+            header,
+            vec!["bar", "meta:RemoteAET", "qux", "meta:ReceptionDate"]
         );
+        assert_eq!(dicom_tags, vec!["bar"]);
+    }
+
+    #[test]
+    fn test_create_metadata_table() {
         assert_eq!(
-            format!(
-                "{}",
-                create_error_table(CliError::new("error", None, Some("details")))
-            ),
-            " Error     error   \n Details   details "
+            format_table(create_metadata_table(
+                hashmap! {
+                    "RemoteAET".to_string() => "MODALITY".to_string(),
+                    "Origin".to_string() => "DicomProtocol".to_string(),
+                },
+                false
+            )),
+            concat!(
+                " Name        Value\n",
+                "---------------------------\n",
+                " Origin      DicomProtocol\n",
+                " RemoteAET   MODALITY",
+            )
         );
+    }
+
+    #[test]
+    fn test_create_attachments_table() {
+        let info = AttachmentInfo {
+            compressed_md5: "aaa".to_string(),
+            compressed_size: 100,
+            content_type: 1,
+            uncompressed_md5: "bbb".to_string(),
+            uncompressed_size: 300,
+            uuid: "foo".to_string(),
+        };
         assert_eq!(
-            format!(
-                "{}",
-                create_error_table(CliError::new("error", Some("message"), None))
-            ),
-            " Error     error   \n Message   message "
+            format_table(create_attachments_table(
+                &[("dicom".to_string(), info)],
+                false
+            )),
+            concat!(
+                " Name    Uncompressed size   Compressed size   MD5   UUID\n",
+                "----------------------------------------------------------\n",
+                " dicom   300                 100               bbb   foo",
+            )
         );
+    }
+
+    #[test]
+    fn test_create_verification_table() {
+        let failures = vec![(
+            "foo".to_string(),
+            "dicom".to_string(),
+            "Corrupted file".to_string(),
+        )];
         assert_eq!(
-            format!("{}", create_error_table(CliError::new("error", None, None))),
-            " Error   error "
+            format_table(create_verification_table(&failures, true)),
+            " foo   dicom   Corrupted file"
         );
     }
 
     #[test]
-    fn test_parse_tag_kv_pairs() {
+    fn test_parse_ids() {
         assert_eq!(
-            parse_tag_kv_pairs(vec!["Foo=Bar", "Baz=42"]).unwrap(),
-            hashmap! {"Foo".to_string() => "Bar".to_string(), "Baz".to_string() => "42".to_string()}
-        )
+            parse_ids("foo\nbar baz\n\n").unwrap(),
+            vec!["foo".to_string(), "bar".to_string(), "baz".to_string()]
+        );
+        assert_eq!(
+            parse_ids(" \n").unwrap_err(),
+            CliError::new("Command error", Some("No IDs given"), None)
+        );
     }
 
     #[test]
-    fn test_parse_tag_kv_pairs_error() {
+    fn test_get_header_and_dicom_tags_extra_columns() {
+        let mut header = vec!["foo", "bar", "baz"];
+        let mut dicom_tags = vec!["bar", "baz"];
+
+        get_header_and_dicom_tags(
+            &mut header,
+            &mut dicom_tags,
+            &["qux", "quux"],
+            Some(vec!["quux", "foo", "baz"]),
+        )
+        .unwrap();
+
+        assert_eq!(header, vec!["foo", "baz", "quux"]);
+        assert_eq!(dicom_tags, vec!["baz"]);
+
+        let mut header = vec!["foo", "bar", "baz"];
         assert_eq!(
-            parse_tag_kv_pairs(vec!["Foo=Bar", "Baz"]).unwrap_err(),
+            get_header_and_dicom_tags(
+                &mut header,
+                &mut vec![],
+                &["qux"],
+                Some(vec!["quux"])
+            )
+            .unwrap_err(),
             CliError::new(
                 "Command error",
-                Some("Wrong option value 'Baz'"),
-                Some("Must be of format 'TagName=TagValue'"),
+                Some("Invalid column name: quux. Available columns: foo, bar, baz, qux"),
+                None
             )
-        )
+        );
+    }
+
+    #[test]
+    fn test_create_lua_script() {
+        assert_eq!(
+            create_lua_script("print(name)", None).unwrap(),
+            "print(name)"
+        );
+        assert_eq!(
+            create_lua_script("print(name)", Some(vec!["name=foo", "_x1=a=\"b\"\\\n"]))
+                .unwrap(),
+            "name = \"foo\"\n_x1 = \"a=\\\"b\\\"\\\\\\n\"\nprint(name)"
+        );
+        for v in &["name", "=foo", "1name=foo", "my-name=foo"] {
+            assert_eq!(
+                create_lua_script("", Some(vec![v])).unwrap_err(),
+                CliError::new(
+                    "Command error",
+                    Some(&format!("Wrong option value '{}'", v)),
+                    Some(
+                        "Must be of format 'name=value', name being a valid Lua identifier"
+                    ),
+                )
+            );
+        }
     }
 
     #[test]
@@ -1068,6 +4215,137 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_create_new_instance_table() {
+        assert_eq!(
+            format!(
+                "{}",
+                create_new_instance_table("foo", "bar", "baz", "qux")
+            ),
+            " New Instance ID   foo \n Series ID         bar \n Study ID          baz \n Patient ID        qux "
+        )
+    }
+
+    #[test]
+    fn test_get_instance_creation() {
+        let mut tags_file = NamedTempFile::new().unwrap();
+        writeln!(tags_file, "PatientName: Rick Sanchez\nModality: OT").unwrap();
+        let mut pdf = NamedTempFile::new().unwrap();
+        pdf.write_all(b"%PDF-1.4").unwrap();
+
+        assert_eq!(
+            get_instance_creation(
+                Some(vec!["Modality=DOC", "SeriesDescription=Report"]),
+                Some(tags_file.path().to_str().unwrap()),
+                None,
+                Some(pdf.path().to_str().unwrap()),
+                Some("foo"),
+            )
+            .unwrap(),
+            InstanceCreation {
+                tags: hashmap! {
+                    "PatientName".to_string() => "Rick Sanchez".to_string(),
+                    "Modality".to_string() => "DOC".to_string(),
+                    "SeriesDescription".to_string() => "Report".to_string(),
+                },
+                content: Some("data:application/pdf;base64,JVBERi0xLjQ=".to_string()),
+                parent: Some("foo".to_string()),
+                force: Some(true),
+            }
+        );
+
+        assert_eq!(
+            get_instance_creation(None, None, None, None, None).unwrap(),
+            InstanceCreation {
+                tags: HashMap::new(),
+                content: None,
+                parent: None,
+                force: Some(true),
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_tags_file() {
+        let mut tags_file = NamedTempFile::new().unwrap();
+        writeln!(
+            tags_file,
+            "PatientName: Rick Sanchez\nSeriesNumber: 1\nSliceThickness: 2.5\nPatientComments:"
+        )
+        .unwrap();
+        assert_eq!(
+            read_tags_file(tags_file.path().to_str().unwrap()).unwrap(),
+            hashmap! {
+                "PatientName".to_string() => "Rick Sanchez".to_string(),
+                "SeriesNumber".to_string() => "1".to_string(),
+                "SliceThickness".to_string() => "2.5".to_string(),
+                "PatientComments".to_string() => "".to_string(),
+            }
+        );
+
+        let mut tags_file = NamedTempFile::new().unwrap();
+        writeln!(tags_file, "ImageType:\n  - ORIGINAL\n  - PRIMARY").unwrap();
+        assert_eq!(
+            read_tags_file(tags_file.path().to_str().unwrap()).unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Invalid value of tag ImageType"),
+                Some("Tag values must be strings, numbers or booleans"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_instance_creation_image() {
+        let mut png = NamedTempFile::new().unwrap();
+        png.write_all(b"\x89PNG\r\n\x1a\n").unwrap();
+        assert_eq!(
+            get_instance_creation(
+                None,
+                None,
+                Some(png.path().to_str().unwrap()),
+                None,
+                None
+            )
+            .unwrap()
+            .content,
+            Some("data:image/png;base64,iVBORw0KGgo=".to_string())
+        );
+
+        let mut gif = NamedTempFile::new().unwrap();
+        gif.write_all(b"GIF89a").unwrap();
+        assert_eq!(
+            get_instance_creation(
+                None,
+                None,
+                Some(gif.path().to_str().unwrap()),
+                None,
+                None
+            )
+            .unwrap_err(),
+            CliError::new(
+                "Command error",
+                Some("Unsupported image format"),
+                Some("Only PNG and JPEG images are supported"),
+            )
+        );
+    }
+
+    #[test]
+    fn test_get_instance_creation_conflicting_options() {
+        assert_eq!(
+            get_instance_creation(
+                None,
+                None,
+                Some("/tmp/foo.png"),
+                Some("/tmp/foo.pdf"),
+                None
+            )
+            .unwrap_err(),
+            CliError::new("Command error", Some("Conflicting options"), None)
+        )
+    }
+
     #[test]
     fn test_get_server() {
         remove_var("ORC_ORTHANC_SERVER");
@@ -1130,6 +4408,7 @@ mod tests {
         get_header_and_dicom_tags(
             &mut header,
             &mut dicom_tags,
+            &[],
             Some(vec!["bar", "quux", "quuz"]),
         )
         .unwrap();
@@ -1143,7 +4422,7 @@ mod tests {
         let mut header = vec!["foo", "bar", "baz"];
         let mut dicom_tags = vec!["qux", "quux", "quuz"];
 
-        get_header_and_dicom_tags(&mut header, &mut dicom_tags, None).unwrap();
+        get_header_and_dicom_tags(&mut header, &mut dicom_tags, &[], None).unwrap();
 
         assert_eq!(header, vec!["foo", "bar", "baz"]);
         assert_eq!(dicom_tags, vec!["qux", "quux", "quuz"]);
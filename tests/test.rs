@@ -109,6 +109,13 @@ fn client() -> Client {
     )
 }
 
+fn server_version_at_least(required: &str) -> bool {
+    let version = client().system().unwrap().version;
+    let parse =
+        |v: &str| -> Vec<u32> { v.split('.').map(|p| p.parse().unwrap_or(0)).collect() };
+    version == "mainline" || parse(&version) >= parse(required)
+}
+
 fn find_instance_by_sop_instance_uid(sop_instance_uid: &str) -> Option<Instance> {
     let instances = client().instances_expanded().unwrap();
     for i in instances {
@@ -492,6 +499,45 @@ fn _test_show_patient() {
     );
 }
 
+#[test]
+fn _test_instance_metadata_get() {
+    let instance = find_instance_by_sop_instance_uid(SOP_INSTANCE_UID).unwrap();
+    assert_result(
+        vec!["instance", "metadata", "get", &instance.id, "Origin"],
+        CommandResult::new(0, "RestApi\n".to_string(), "".to_string()),
+    );
+}
+
+#[test]
+fn _test_instance_attachment_verify() {
+    let instance = find_instance_by_sop_instance_uid(SOP_INSTANCE_UID).unwrap();
+    assert_result(
+        vec!["instance", "attachment", "verify", &instance.id, "dicom"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+}
+
+#[test]
+fn test_protect_patient() {
+    let patient = find_patient_by_patient_id(PATIENT_ID).unwrap();
+    assert_result(
+        vec!["patient", "protect", &patient.id],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec!["patient", "list", "-c", "PatientID", "Protected"],
+        CommandResult::new(
+            0,
+            include_str!("data/patient_list_protected.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+    assert_result(
+        vec!["patient", "unprotect", &patient.id],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+}
+
 #[test]
 fn _test_show_patient_error() {
     assert_result(
@@ -517,6 +563,18 @@ fn _test_show_study() {
     );
 }
 
+#[test]
+fn _test_show_study_by_dicom_uid() {
+    assert_result(
+        vec!["study", "show", &format!("uid:{}", STUDY_INSTANCE_UID)],
+        CommandResult::new(
+            0,
+            include_str!("data/study_show.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
 #[test]
 fn _test_show_study_error() {
     assert_result(
@@ -592,6 +650,18 @@ fn _test_list_patient_studies() {
     );
 }
 
+#[test]
+fn _test_list_patient_studies_by_patient_id() {
+    assert_result(
+        vec!["patient", "list-studies", &format!("pid:{}", PATIENT_ID)],
+        CommandResult::new(
+            0,
+            include_str!("data/patient_list_studies.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
 #[test]
 fn _test_list_patient_studies_no_header() {
     let patient = find_patient_by_patient_id(PATIENT_ID).unwrap();
@@ -668,6 +738,19 @@ fn _test_list_series_instances() {
     );
 }
 
+#[test]
+fn _test_series_tree() {
+    let series = find_series_by_series_instance_uid(SERIES_INSTANCE_UID).unwrap();
+    assert_result(
+        vec!["series", "tree", &series.id],
+        CommandResult::new(
+            0,
+            include_str!("data/series_tree.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
 #[test]
 fn _test_list_series_instances_no_header() {
     let series = find_series_by_series_instance_uid(SERIES_INSTANCE_UID).unwrap();
@@ -749,6 +832,199 @@ fn test_download_study() {
     );
 }
 
+#[test]
+fn test_download_study_skip_existing() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let args = vec![
+        "study",
+        "download",
+        &study.id,
+        "-o",
+        "/tmp/study_skip_existing.zip",
+        "--skip-existing",
+    ];
+
+    // An incomplete archive is downloaded again
+    fs::write("/tmp/study_skip_existing.zip", b"PK").unwrap();
+    assert_result(
+        args.clone(),
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    let file = fs::File::open("/tmp/study_skip_existing.zip").unwrap();
+    let zip = zip::ZipArchive::new(BufReader::new(file)).unwrap();
+    assert_eq!(zip.len(), 2);
+    assert!(!Path::new("/tmp/study_skip_existing.zip.part").exists());
+
+    // A complete one is kept
+    let modified = fs::metadata("/tmp/study_skip_existing.zip")
+        .unwrap()
+        .modified()
+        .unwrap();
+    assert_result(args, CommandResult::new(0, "".to_string(), "".to_string()));
+    assert_eq!(
+        fs::metadata("/tmp/study_skip_existing.zip")
+            .unwrap()
+            .modified()
+            .unwrap(),
+        modified
+    );
+}
+
+#[test]
+fn test_download_study_layout() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    assert_result(
+        vec![
+            "study",
+            "download",
+            &study.id,
+            "--extract",
+            dir.path().to_str().unwrap(),
+            "--layout",
+            "{PatientID}/{StudyDescription}/{Modality}.dcm",
+        ],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert!(dir.path().join("patient_2/Study 1/MR.dcm").is_file());
+    assert!(dir.path().join("patient_2/Study 1/PR.dcm").is_file());
+}
+
+#[test]
+fn test_download_study_layout_error() {
+    assert_result(
+        vec![
+            "study",
+            "download",
+            "foobar",
+            "--extract",
+            "/tmp/study",
+            "--layout",
+            "{PatientID/{StudyDate}.dcm",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/layout_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_download_study_transcode_error() {
+    assert_result(
+        vec![
+            "study",
+            "download",
+            "foobar",
+            "-o",
+            "/tmp/study.zip",
+            "--transcode",
+            "jpeg2000",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/transcode_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_download_study_transcode() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let args = vec![
+        "study",
+        "download",
+        &study.id,
+        "-o",
+        "/tmp/study_transcoded.zip",
+        "--transcode",
+        "explicit-little",
+    ];
+    if server_version_at_least("1.7.0") {
+        assert_result(args, CommandResult::new(0, "".to_string(), "".to_string()));
+    } else {
+        assert_result(
+            args,
+            CommandResult::new(
+                1,
+                "".to_string(),
+                format!(
+                    concat!(
+                        " Error     Command error\n",
+                        " Message   Transcoding requires Orthanc 1.7.0 or later\n",
+                        " Details   Server version: {}\n",
+                    ),
+                    client().system().unwrap().version
+                ),
+            ),
+        );
+    }
+}
+
+#[test]
+fn test_download_studies() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let query = format!("StudyInstanceUID={}", STUDY_INSTANCE_UID);
+    let args = vec![
+        "study",
+        "download-many",
+        "-q",
+        &query,
+        "-d",
+        dir.path().to_str().unwrap(),
+        "-n",
+    ];
+    let res = run_command(args.clone());
+    assert_eq!(res.exit_code, 0);
+    assert!(res.stderr.starts_with(&format!("[1/1] {} (", study.id)));
+
+    let archive_path = dir.path().join(format!("{}.zip", study.id));
+    let file = fs::File::open(&archive_path).unwrap();
+    let zip = zip::ZipArchive::new(BufReader::new(file)).unwrap();
+    assert_eq!(zip.len(), 2);
+    let manifest = fs::read_to_string(dir.path().join("manifest.csv")).unwrap();
+    let rows: Vec<&str> = manifest.lines().collect();
+    assert_eq!(
+        rows[0],
+        "ID,PatientID,PatientName,StudyInstanceUID,AccessionNumber,StudyDate,StudyDescription,Path,Size"
+    );
+    assert!(rows[1].starts_with(&format!("{},{},", study.id, PATIENT_ID)));
+    assert!(rows[1].ends_with(&format!(
+        ",{}.zip,{}",
+        study.id,
+        fs::metadata(&archive_path).unwrap().len()
+    )));
+
+    // Studies already downloaded are skipped
+    let res = run_command(args);
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(res.stderr, "Resuming, 1/1 studies already downloaded\n");
+}
+
+#[test]
+fn test_download_resources() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    let mut args = vec!["download", "-o", "/tmp/resources.zip", "--ids"];
+    args.extend(study.series.iter().map(|s| s.as_str()));
+    assert_result(args, CommandResult::new(0, "".to_string(), "".to_string()));
+    let file = fs::File::open("/tmp/resources.zip").unwrap();
+    let reader = BufReader::new(file);
+    let zip = zip::ZipArchive::new(reader).unwrap();
+    let mut files: Vec<&str> = zip.file_names().collect();
+    files.sort();
+
+    assert_eq!(
+        files,
+        vec![
+            "patient_2 Patient 2/REMOVED Study 1/MR Series 1/MR000000.dcm",
+            "patient_2 Patient 2/REMOVED Study 1/PR/PR000000.dcm",
+        ]
+    );
+}
+
 #[test]
 fn test_download_study_error() {
     assert_result(
@@ -1481,9 +1757,10 @@ fn test_anonymize_instance_with_config() {
             .unwrap(),
         "Patient 2 " // TODO: Why is there a trailing space?
     );
-    assert!(
-        matches!(obj.element(Tag::from((0x1235, 0x0042))).unwrap_err(), DicomError::NoSuchDataElementTag{..})
-    );
+    assert!(matches!(
+        obj.element(Tag::from((0x1235, 0x0042))).unwrap_err(),
+        DicomError::NoSuchDataElementTag { .. }
+    ));
 }
 
 #[test]
@@ -2523,41 +2800,766 @@ fn test_instance_tags_error() {
 }
 
 #[test]
-fn test_search_patients() {
+fn test_instance_frames() {
     assert_result(
-        vec!["patient", "search", "-q", "PatientID=patient*"],
+        vec![
+            "instance",
+            "frames",
+            &find_instance_by_sop_instance_uid(SOP_INSTANCE_UID)
+                .unwrap()
+                .id,
+        ],
         CommandResult::new(
             0,
-            include_str!("data/patients_search.stdout").to_string(),
+            include_str!("data/instance_frames.stdout").to_string(),
             "".to_string(),
         ),
     );
 }
 
 #[test]
-fn test_search_patients_no_header() {
+fn test_instance_frames_error() {
     assert_result(
-        vec!["patient", "search", "-q", "PatientID=patient*", "-n"],
+        vec!["instance", "frames", "foobar"],
         CommandResult::new(
-            0,
-            include_str!("data/patients_search_no_header.stdout").to_string(),
+            1,
             "".to_string(),
+            include_str!("data/not_found_with_message_error.stderr").to_string(),
         ),
     );
 }
 
 #[test]
-fn test_search_studies() {
+fn test_instance_frame_raw() {
+    let instance = find_instance_by_sop_instance_uid(SOP_INSTANCE_UID).unwrap();
     assert_result(
-        vec!["study", "search", "-q", "StudyDescription=Study*"],
+        vec![
+            "instance",
+            "frame",
+            &instance.id,
+            "0",
+            "--raw",
+            "-o",
+            "/tmp/frame.bin",
+        ],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert!(Path::new("/tmp/frame.bin").exists());
+    let sidecar: serde_json::Value =
+        serde_json::from_slice(&fs::read("/tmp/frame.bin.json").unwrap()).unwrap();
+    assert_eq!(
+        sidecar,
+        serde_json::json!({
+            "Frame": 0,
+            "Rows": 432,
+            "Columns": 432,
+            "BitsAllocated": 16,
+            "PhotometricInterpretation": "MONOCHROME2",
+            "RescaleSlope": 10.3455433455433,
+            "RescaleIntercept": 0.0,
+        })
+    );
+}
+
+#[test]
+fn test_instance_frame_error() {
+    let instance = find_instance_by_sop_instance_uid(SOP_INSTANCE_UID).unwrap();
+    fs::remove_file("/tmp/frame_error.png").ok();
+    assert_result(
+        vec![
+            "instance",
+            "frame",
+            &instance.id,
+            "1",
+            "-o",
+            "/tmp/frame_error.png",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            concat!(
+                " Error     Command error\n",
+                " Message   Invalid frame number: 1. Number of frames: 1\n",
+            )
+            .to_string(),
+        ),
+    );
+    assert!(!Path::new("/tmp/frame_error.png").exists());
+    assert!(!Path::new("/tmp/frame_error.png.part").exists());
+}
+
+#[test]
+fn test_instance_extract_pdf_error() {
+    assert_result(
+        vec![
+            "instance",
+            "extract-pdf",
+            &find_instance_by_sop_instance_uid(SOP_INSTANCE_UID)
+                .unwrap()
+                .id,
+            "-o",
+            "/tmp/report.pdf",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/no_encapsulated_document_error.stderr").to_string(),
+        ),
+    );
+    assert_result(
+        vec!["instance", "extract-pdf", "foobar", "-o", "/tmp/report.pdf"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/not_found_with_message_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_instance_extract_document_error() {
+    assert_result(
+        vec![
+            "instance",
+            "extract-document",
+            &find_instance_by_sop_instance_uid(SOP_INSTANCE_UID)
+                .unwrap()
+                .id,
+            "-o",
+            "/tmp/document.bin",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/no_encapsulated_document_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_instance_create_unsupported_image_error() {
+    assert_result(
+        vec![
+            "instance",
+            "create",
+            "-t",
+            "PatientID=patient_create",
+            "--image",
+            "Cargo.toml",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/unsupported_image_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_lookup() {
+    assert_result(
+        vec!["lookup", STUDY_INSTANCE_UID],
         CommandResult::new(
             0,
-            include_str!("data/studies_search.stdout").to_string(),
+            include_str!("data/lookup.stdout").to_string(),
             "".to_string(),
         ),
     );
 }
 
+#[test]
+fn test_lookup_not_found() {
+    assert_result(
+        vec!["lookup", "uid:1.2.3.4"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/lookup_not_found_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_plugin_show_features() {
+    // Plugins are only loaded by the orthanc-plugins images of the CI matrix
+    let plugins = client().plugins().unwrap();
+    if !plugins.iter().any(|p| p == "dicom-web") {
+        return;
+    }
+    let res = run_command(vec!["plugin", "show", "dicom-web"]);
+    assert_eq!(res.exit_code, 0);
+    assert!(res.stdout.lines().any(|l| l.trim_end()
+        == " CLI features         None, DICOMweb is only served to DICOMweb clients under /dicom-web"));
+}
+
+#[test]
+fn test_plugin_show_not_found() {
+    assert_result(
+        vec!["plugin", "show", "foo"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/plugin_not_found_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_changes_since_last() {
+    let last = client().changes_last().unwrap().last;
+    assert_result(
+        vec!["changes", "--since", &last.to_string()],
+        CommandResult::new(
+            0,
+            include_str!("data/changes_empty.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_changes_type() {
+    let res = run_command(vec!["changes", "--type", "NewStudy,StableStudy", "-n"]);
+    assert_eq!(res.exit_code, 0);
+    assert!(!res.stdout.is_empty());
+    for line in res.stdout.lines() {
+        let change_type = line.split_whitespace().nth(1).unwrap();
+        assert!(change_type == "NewStudy" || change_type == "StableStudy");
+    }
+}
+
+#[test]
+fn test_changes_state_file_error() {
+    fs::write("/tmp/changes_state_error.seq", "foo").unwrap();
+    assert_result(
+        vec![
+            "changes",
+            "--follow",
+            "--state-file",
+            "/tmp/changes_state_error.seq",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/changes_state_file_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_system_info() {
+    let system = client().system().unwrap();
+    let res = run_command(vec!["system", "info"]);
+    assert_eq!(res.exit_code, 0);
+    let rows: Vec<(&str, &str)> = res
+        .stdout
+        .lines()
+        .map(|l| {
+            let mut cells = l.trim().splitn(2, "   ");
+            (cells.next().unwrap(), cells.next().unwrap_or("").trim())
+        })
+        .collect();
+    assert_eq!(
+        rows.iter().map(|(k, _)| *k).collect::<Vec<&str>>(),
+        vec![
+            "Name",
+            "Version",
+            "API version",
+            "DICOM AET",
+            "DICOM port",
+            "HTTP port",
+            "HTTPS",
+            "Database backend",
+            "Database version",
+            "Storage area",
+            "Plugins",
+        ]
+    );
+    assert_eq!(rows[1].1, system.version);
+    assert_eq!(rows[3].1, system.dicom_aet);
+}
+
+#[test]
+fn test_system_stats() {
+    let res = run_command(vec!["system", "stats"]);
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(
+        res.stdout
+            .lines()
+            .map(|l| l.trim().split("   ").next().unwrap())
+            .collect::<Vec<&str>>(),
+        vec![
+            "Patients",
+            "Studies",
+            "Series",
+            "Instances",
+            "Disk size",
+            "Uncompressed size",
+        ]
+    );
+}
+
+#[test]
+fn test_instance_stats() {
+    let instance = find_instance_by_sop_instance_uid(SOP_INSTANCE_UID).unwrap();
+    let stats = client().instance_statistics(&instance.id).unwrap();
+    assert_result(
+        vec!["instance", "stats", &instance.id],
+        CommandResult::new(
+            0,
+            format!(
+                concat!(
+                    " Instances           1\n",
+                    " Disk size           {} ({} MB)\n",
+                    " Uncompressed size   {} ({} MB)\n",
+                ),
+                stats.disk_size,
+                stats.disk_size / 1024 / 1024,
+                stats.uncompressed_size,
+                stats.uncompressed_size / 1024 / 1024,
+            ),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_patient_stats() {
+    let patient = find_patient_by_patient_id(PATIENT_ID).unwrap();
+    let stats = client().patient_statistics(&patient.id).unwrap();
+    let res = run_command(vec!["patient", "stats", &patient.id]);
+    assert_eq!(res.exit_code, 0);
+    assert!(res.stdout.starts_with(&format!(
+        concat!(" Studies             {}\n", " Series              {}\n"),
+        stats.count_studies.unwrap(),
+        stats.count_series.unwrap(),
+    )));
+}
+
+#[test]
+fn test_study_split_and_merge() {
+    let series = find_series_by_series_instance_uid(SERIES_INSTANCE_UID).unwrap();
+    let split = |description: &str| {
+        let res = run_command(vec![
+            "study",
+            "split",
+            &series.parent_study,
+            "--series",
+            &series.id,
+            "--replace",
+            &format!("StudyDescription={}", description),
+            "--keep-source",
+        ]);
+        assert!(
+            res == CommandResult::new(
+                0,
+                include_str!("data/anonymize_study.stdout").to_string(),
+                "".to_string(),
+            ),
+        );
+        res.new_entity_id()
+    };
+    let target_id = split("split target");
+    let source_id = split("split source");
+    let target = client().study(&target_id).unwrap();
+    assert_eq!(target.series.len(), 1);
+    assert_eq!(target.main_dicom_tags["StudyDescription"], "split target");
+
+    let res = run_command(vec!["study", "merge", &target_id, &source_id]);
+    assert!(
+        res == CommandResult::new(
+            0,
+            include_str!("data/anonymize_study.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+    assert_eq!(res.new_entity_id(), target_id);
+    assert_eq!(client().study(&target_id).unwrap().series.len(), 2);
+    assert!(client().study(&source_id).is_err());
+    // The split kept its source
+    assert!(client().series(&series.id).is_ok());
+}
+
+#[test]
+fn test_study_split_replace_error() {
+    assert_result(
+        vec![
+            "study",
+            "split",
+            "foo",
+            "--series",
+            "bar",
+            "--replace",
+            "StudyDescription",
+        ],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/split_replace_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_reconstruct_study() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    assert_result(
+        vec!["study", "reconstruct", &study.id],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    let reconstructed = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    assert_eq!(reconstructed.main_dicom_tags, study.main_dicom_tags);
+}
+
+#[test]
+fn test_check_main_tags() {
+    if server_version_at_least("1.11.0") {
+        let res = run_command(vec!["system", "check-main-tags", "-n"]);
+        assert_eq!(res.exit_code, 0);
+    } else {
+        assert_result(
+            vec!["system", "check-main-tags"],
+            CommandResult::new(
+                1,
+                "".to_string(),
+                format!(
+                    concat!(
+                        " Error     Command error\n",
+                        " Message   Main DICOM tags configuration requires Orthanc 1.11.0 or later\n",
+                        " Details   Server version: {}\n",
+                    ),
+                    client().system().unwrap().version
+                ),
+            ),
+        );
+    }
+}
+
+#[test]
+fn test_label_error() {
+    assert_result(
+        vec!["patient", "label", "add", "foo/bar", "patient_2"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/label_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_labels() {
+    let study = find_study_by_study_instance_uid(STUDY_INSTANCE_UID).unwrap();
+    if !server_version_at_least("1.12.0") {
+        assert_result(
+            vec!["study", "label", "add", "cohort_1", &study.id],
+            CommandResult::new(
+                1,
+                "".to_string(),
+                format!(
+                    concat!(
+                        " Error     Command error\n",
+                        " Message   Labeling requires Orthanc 1.12.0 or later\n",
+                        " Details   Server version: {}\n",
+                    ),
+                    client().system().unwrap().version
+                ),
+            ),
+        );
+        return;
+    }
+    assert_result(
+        vec!["study", "label", "add", "cohort_1", &study.id],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec!["study", "label", "list", &study.id],
+        CommandResult::new(0, "cohort_1\n".to_string(), "".to_string()),
+    );
+    let res = run_command(vec![
+        "study", "search", "--label", "cohort_1", "-c", "ID", "-n",
+    ]);
+    assert_eq!(res.exit_code, 0);
+    assert_eq!(
+        res.stdout.lines().map(|l| l.trim()).collect::<Vec<&str>>(),
+        vec![study.id.as_str()]
+    );
+    assert_result(
+        vec!["study", "label", "remove", "cohort_1", &study.id],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec!["study", "label", "list", &study.id],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+}
+
+#[test]
+fn test_search_by_label_only() {
+    let command = vec!["study", "search", "--label", "no_such_label", "-n"];
+    if server_version_at_least("1.12.0") {
+        assert_result(
+            command,
+            CommandResult::new(0, "".to_string(), "".to_string()),
+        );
+    } else {
+        assert_result(
+            command,
+            CommandResult::new(
+                1,
+                "".to_string(),
+                format!(
+                    concat!(
+                        " Error     Command error\n",
+                        " Message   Labeling requires Orthanc 1.12.0 or later\n",
+                        " Details   Server version: {}\n",
+                    ),
+                    client().system().unwrap().version
+                ),
+            ),
+        );
+    }
+}
+
+#[test]
+fn test_log_level() {
+    assert_result(
+        vec!["system", "log-level", "set", "verbose"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    // Per-category logging requires Orthanc 1.8.1 or later
+    if server_version_at_least("1.8.1") {
+        assert_result(
+            vec!["system", "log-level", "get"],
+            CommandResult::new(
+                0,
+                include_str!("data/log_level_verbose.stdout").to_string(),
+                "".to_string(),
+            ),
+        );
+        assert_result(
+            vec!["system", "log-level", "set", "trace", "--category", "http"],
+            CommandResult::new(0, "".to_string(), "".to_string()),
+        );
+        assert_result(
+            vec!["system", "log-level", "get", "--category", "http"],
+            CommandResult::new(0, " http   trace\n".to_string(), "".to_string()),
+        );
+    } else {
+        assert_result(
+            vec!["system", "log-level", "get"],
+            CommandResult::new(
+                0,
+                include_str!("data/log_level_verbose_global.stdout").to_string(),
+                "".to_string(),
+            ),
+        );
+    }
+    assert_result(
+        vec!["system", "log-level", "set", "default"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+}
+
+#[test]
+fn test_exec_lua() {
+    fs::write("/tmp/script.lua", "print(greeting .. ', ' .. name)").unwrap();
+    assert_result(
+        vec![
+            "tools",
+            "exec-lua",
+            "/tmp/script.lua",
+            "--var",
+            "greeting=Hello",
+            "--var",
+            "name=\"Orthanc\"",
+        ],
+        CommandResult::new(0, "Hello, \"Orthanc\"\n".to_string(), "".to_string()),
+    );
+}
+
+#[test]
+fn test_search_patients() {
+    assert_result(
+        vec!["patient", "search", "-q", "PatientID=patient*"],
+        CommandResult::new(
+            0,
+            include_str!("data/patients_search.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_search_patients_no_header() {
+    assert_result(
+        vec!["patient", "search", "-q", "PatientID=patient*", "-n"],
+        CommandResult::new(
+            0,
+            include_str!("data/patients_search_no_header.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_search_studies() {
+    assert_result(
+        vec!["study", "search", "-q", "StudyDescription=Study*"],
+        CommandResult::new(
+            0,
+            include_str!("data/studies_search.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_search_studies_ignore_case() {
+    assert_result(
+        vec!["study", "search", "-q", "StudyDescription=study*", "-i"],
+        CommandResult::new(
+            0,
+            include_str!("data/studies_search.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_search_studies_date_range() {
+    assert_result(
+        vec![
+            "study",
+            "search",
+            "-q",
+            "StudyDescription=Study*",
+            "StudyDate=20110101-20111231",
+        ],
+        CommandResult::new(
+            0,
+            include_str!("data/studies_search.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_search_studies_date_range_error() {
+    assert_result(
+        vec!["study", "search", "-q", "StudyDate=20111231-20110101"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/date_range_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_saved_query() {
+    let config_dir = tempfile::tempdir().unwrap();
+    env::set_var("ORC_CONFIG_DIR", config_dir.path());
+    assert_result(
+        vec![
+            "query",
+            "save",
+            "studies",
+            "--level",
+            "study",
+            "-q",
+            "StudyDescription={{description}}",
+        ],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec!["query", "run", "studies", "--param", "description=Study*"],
+        CommandResult::new(
+            0,
+            include_str!("data/studies_search.stdout").to_string(),
+            "".to_string(),
+        ),
+    );
+    assert_result(
+        vec!["query", "delete", "studies"],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec!["query", "run", "studies"],
+        CommandResult::new(
+            1,
+            "".to_string(),
+            include_str!("data/query_not_found_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_search_studies_count() {
+    assert_result(
+        vec![
+            "study",
+            "search",
+            "-q",
+            "StudyDescription=Study*",
+            "--count",
+        ],
+        CommandResult::new(0, "3\n".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec![
+            "study",
+            "search",
+            "-q",
+            "StudyDescription=Study*",
+            "--count",
+            "--limit",
+            "2",
+        ],
+        CommandResult::new(
+            2,
+            "".to_string(),
+            include_str!("data/search_count_limit_error.stderr").to_string(),
+        ),
+    );
+}
+
+#[test]
+fn test_search_studies_exists() {
+    assert_result(
+        vec![
+            "study",
+            "search",
+            "-q",
+            "StudyDescription=Study*",
+            "--exists",
+        ],
+        CommandResult::new(0, "".to_string(), "".to_string()),
+    );
+    assert_result(
+        vec![
+            "study",
+            "search",
+            "-q",
+            "AccessionNumber=foobar",
+            "--exists",
+        ],
+        CommandResult::new(1, "".to_string(), "".to_string()),
+    );
+
+    // A failed search isn't "nothing found"
+    let res = run_command(vec![
+        "--server",
+        "http://localhost:8901",
+        "study",
+        "search",
+        "-q",
+        "AccessionNumber=foobar",
+        "--exists",
+    ]);
+    assert_eq!(res.exit_code, 2);
+}
+
 #[test]
 fn test_search_studies_no_header() {
     assert_result(